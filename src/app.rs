@@ -1,27 +1,496 @@
 use crate::config::AppConfig;
-use crate::error::Result;
+use crate::error::{apply_error_format, Result};
 use crate::handlers;
+use crate::middleware::admin::require_admin_token;
+use crate::middleware::api_key::{require_api_key, API_KEY_HEADER};
+use crate::middleware::body_limit::reject_oversized_bodies;
+use crate::middleware::maintenance::{reject_during_maintenance, MaintenanceMode};
+use crate::middleware::metrics::count_requests;
+use crate::middleware::numbers::encode_numbers;
+use crate::middleware::pretty::pretty_print_json;
+use crate::middleware::priority::PriorityLimitLayer;
+use crate::middleware::rate_limit::{limit_clients, ClientRateLimit};
+use crate::middleware::slow_request::{warn_slow_requests, REQUEST_ID_HEADER};
+use crate::services::bundler::Bundler;
 use crate::services::ethereum::EthereumService;
-use axum::{routing::post, Router};
+use crate::utils::jwt::ResponseSigner;
+use crate::utils::shutdown::wait_for_signal;
+use axum::{
+    extract::{DefaultBodyLimit, FromRef},
+    http::{
+        header::{ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method,
+    },
+    middleware::{from_fn, from_fn_with_state},
+    routing::post,
+    Router,
+};
+use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
 use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::trace::TraceLayer;
 
+/// State shared by all handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<AppConfig>,
+    pub service: Arc<EthereumService>,
+    pub signer: Option<Arc<ResponseSigner>>,
+    pub maintenance: MaintenanceMode,
+    /// Only with a `RATE_LIMIT_PER_SECOND`
+    pub rate_limit: Option<ClientRateLimit>,
+    /// Only with a `BUNDLER_URL`
+    pub bundler: Option<Arc<Bundler>>,
+}
+
+impl AppState {
+    pub fn new(config: &AppConfig, ethereum_service: EthereumService) -> Self {
+        let signer = match (&config.signing_key, config.sign_responses) {
+            (Some(key), true) => Some(Arc::new(ResponseSigner::new(
+                key.expose(),
+                config.signed_response_ttl,
+            ))),
+            _ => None,
+        };
+
+        let rate_limit = NonZeroU32::new(config.rate_limit_per_second).map(|per_second| {
+            let burst = NonZeroU32::new(config.rate_limit_burst).unwrap_or(per_second);
+            ClientRateLimit::new(per_second, burst, api_keys(config))
+        });
+
+        Self {
+            config: Arc::new(config.clone()),
+            service: Arc::new(ethereum_service),
+            signer,
+            maintenance: MaintenanceMode::new(
+                config.maintenance_mode,
+                &config.maintenance_message,
+                config.maintenance_retry_after_secs,
+            ),
+            rate_limit,
+            bundler: config
+                .bundler_url
+                .clone()
+                .map(|url| Arc::new(Bundler::new(url, config.entry_point, config.rpc_timeout))),
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<AppConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EthereumService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.service.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<ResponseSigner>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.signer.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<Bundler>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.bundler.clone()
+    }
+}
+
 pub async fn create_app(config: AppConfig) -> Result<Router> {
     let ethereum_service = EthereumService::new(&config).await?;
-    let service = Arc::new(ethereum_service);
+    let state = AppState::new(&config, ethereum_service);
+
+    match tokio::try_join!(state.service.chain_id(), state.service.supports_eip1559()) {
+        Ok((chain_id, eip1559)) => {
+            tracing::info!("Connected to chain {} (EIP-1559: {})", chain_id, eip1559)
+        }
+        Err(e) => tracing::warn!("Failed to probe chain capabilities: {}", e),
+    }
+    tracing::info!("Node client: {}", state.service.client_version().await);
+
+    if config.cache_background_refresh {
+        if config.cache_duration.is_zero() {
+            tracing::warn!("CACHE_BACKGROUND_REFRESH ignored, caching is disabled");
+        } else {
+            let service = state.service.clone();
+            // Twice per TTL, so entries are replaced before they expire
+            let interval = config.cache_duration / 2;
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = service.keep_cache_warm(interval) => {}
+                    _ = wait_for_signal() => tracing::debug!("Background cache refresh stopped"),
+                }
+            });
+        }
+    }
+
+    if let Some(limit) = state.rate_limit.clone() {
+        let sweep = async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                ticks.tick().await;
+                limit.forget_idle_clients();
+            }
+        };
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sweep => {}
+                _ = wait_for_signal() => tracing::debug!("Rate limiter sweep stopped"),
+            }
+        });
+    }
+
+    match config.statsd_addr {
+        #[cfg(feature = "statsd")]
+        Some(addr) => {
+            tokio::spawn(crate::utils::statsd::push_metrics(
+                addr,
+                config.statsd_interval,
+            ));
+        }
+        #[cfg(not(feature = "statsd"))]
+        Some(_) => tracing::warn!("STATSD_ADDR ignored, built without the statsd feature"),
+        None => {}
+    }
+
+    if config.cors_allowed_origins.is_empty() {
+        tracing::warn!("CORS allows any origin, set CORS_ALLOWED_ORIGINS in production");
+    }
+
+    #[cfg(unix)]
+    tokio::spawn(crate::middleware::maintenance::reload_on_sighup(
+        state.maintenance.clone(),
+    ));
+
+    Ok(create_router(state))
+}
+
+/// CORS restricted to `origins`, or allowing any origin for development when empty
+fn cors_layer(origins: &[HeaderValue]) -> CorsLayer {
+    if origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins.iter().cloned()))
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([
+            ACCEPT,
+            AUTHORIZATION,
+            CONTENT_ENCODING,
+            CONTENT_TYPE,
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            HeaderName::from_static(API_KEY_HEADER),
+        ])
+}
+
+/// Keys accepted by `require_api_key`, `None` leaves the API open
+fn api_keys(config: &AppConfig) -> Option<Arc<[String]>> {
+    (!config.api_keys.is_empty()).then(|| {
+        config
+            .api_keys
+            .iter()
+            .map(|key| key.expose().to_string())
+            .collect()
+    })
+}
+
+/// Builds the router around an already connected service
+pub fn create_router(state: AppState) -> Router {
+    let config = state.config.clone();
 
     let middleware = ServiceBuilder::new()
         .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::permissive())
+        .layer(cors_layer(&config.cors_allowed_origins))
         .into_inner();
 
-    let app = Router::new()
+    let api_keys = api_keys(&config);
+
+    let mut api = Router::new()
         .route("/api/v1/estimate-gas", post(handlers::gas::estimate_gas))
+        .route(
+            "/api/v1/estimate-gas/batch",
+            post(handlers::gas::estimate_batch),
+        )
+        .route(
+            "/api/v1/estimate-replacement",
+            post(handlers::gas::estimate_replacement),
+        )
+        .route("/api/v1/estimate-diff", post(handlers::gas::estimate_diff))
+        .route("/api/v1/call", post(handlers::gas::call))
+        .route(
+            "/api/v1/estimate-budget",
+            post(handlers::gas::estimate_budget),
+        )
+        .route("/api/v1/fee-sweep", post(handlers::gas::fee_sweep))
+        .route(
+            "/api/v1/estimate-user-operation",
+            post(handlers::user_operation::estimate_user_operation),
+        )
+        .route(
+            "/api/v1/gas-price",
+            axum::routing::get(handlers::fees::gas_price),
+        )
+        .route(
+            "/api/v1/fee-for-blocks",
+            axum::routing::get(handlers::fees::fee_for_blocks),
+        )
+        .route(
+            "/api/v1/method-gas",
+            axum::routing::get(handlers::history::method_gas),
+        )
+        .route_layer(from_fn_with_state(
+            state.maintenance.clone(),
+            reject_during_maintenance,
+        ))
+        .route(
+            "/api/v1/capabilities",
+            axum::routing::get(handlers::capabilities),
+        )
+        .route(
+            "/api/v1/schema/estimate-gas",
+            axum::routing::get(handlers::estimate_gas_schema),
+        )
+        .route(
+            "/api/v1/schema/estimate-gas/batch",
+            axum::routing::get(handlers::estimate_batch_schema),
+        );
+    if let Some(keys) = &api_keys {
+        api = api.route_layer(from_fn_with_state(keys.clone(), require_api_key));
+    }
+    // Runs before the key check, so invalid keys are throttled too
+    if let Some(limit) = &state.rate_limit {
+        api = api.route_layer(from_fn_with_state(limit.clone(), limit_clients));
+    }
+
+    let mut app = api
         .route("/health", axum::routing::get(handlers::health))
+        .route("/version", axum::routing::get(handlers::version))
+        .route("/metrics", axum::routing::get(handlers::metrics))
+        .route("/ready", axum::routing::get(handlers::ready))
+        .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+        .layer(from_fn_with_state(
+            config.max_body_bytes,
+            reject_oversized_bodies,
+        ))
+        // MAX_BODY_BYTES replaces axum's own limit
+        .layer(DefaultBodyLimit::disable())
+        // Body limits then apply to the decompressed size
+        .layer(RequestDecompressionLayer::new());
+
+    #[cfg(feature = "graphql")]
+    {
+        let schema = crate::graphql::schema(state.service.clone(), config.clone());
+        let graphql = Router::new()
+            .route("/graphql", post(crate::graphql::graphql))
+            .route_layer(from_fn_with_state(
+                state.maintenance.clone(),
+                reject_during_maintenance,
+            ))
+            .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+            .layer(from_fn_with_state(
+                config.max_body_bytes,
+                reject_oversized_bodies,
+            ))
+            .layer(DefaultBodyLimit::disable());
+        let graphql = match &api_keys {
+            Some(keys) => graphql.route_layer(from_fn_with_state(keys.clone(), require_api_key)),
+            None => graphql,
+        };
+        let graphql = match &state.rate_limit {
+            Some(limit) => graphql.route_layer(from_fn_with_state(limit.clone(), limit_clients)),
+            None => graphql,
+        }
+        .with_state(schema);
+        app = app.merge(graphql);
+    }
+
+    if config.status_page {
+        app = app.route("/", axum::routing::get(handlers::status::status_page));
+    }
+
+    if let Some(token) = &config.admin_token {
+        let admin = Router::new()
+            .route(
+                "/admin/cache",
+                axum::routing::get(handlers::admin::inspect_cache),
+            )
+            .route("/admin/cache/clear", post(handlers::admin::clear_cache))
+            .route_layer(from_fn_with_state(
+                Arc::<str>::from(token.expose()),
+                require_admin_token,
+            ));
+        app = app.merge(admin);
+    }
+
+    if config.max_concurrent_requests > 0 {
+        app = app.layer(PriorityLimitLayer::new(
+            config.max_concurrent_requests,
+            config.low_priority_body_bytes,
+            config.queue_timeout,
+        ));
+    }
+
+    app.layer(from_fn_with_state(config.error_format, apply_error_format))
+        .layer(from_fn_with_state(config.number_encoding, encode_numbers))
+        .layer(from_fn(pretty_print_json))
+        .layer(from_fn(count_requests))
+        .layer(from_fn_with_state(
+            config.slow_request_threshold,
+            warn_slow_requests,
+        ))
         .layer(middleware)
-        .with_state(service);
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{body_bytes, json_request, transfer_body, transfer_transport};
+    use axum::body::Body;
+    use axum::http::{header::CONTENT_ENCODING, Request, StatusCode};
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tower::ServiceExt;
+
+    fn gzip_request(uri: &str, body: &[u8]) -> Request<Body> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(Body::from(encoder.finish().unwrap()))
+            .unwrap()
+    }
+
+    fn test_router() -> Router {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        create_router(AppState::new(&config, service))
+    }
+
+    #[tokio::test]
+    async fn test_gzip_body_handled_like_plain_body() {
+        let app = test_router();
+
+        let plain = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        let body = transfer_body().to_string();
+        let compressed = app
+            .oneshot(gzip_request("/api/v1/estimate-gas", body.as_bytes()))
+            .await
+            .unwrap();
+
+        assert_eq!(compressed.status(), StatusCode::OK);
+        assert_eq!(body_bytes(compressed).await, body_bytes(plain).await);
+    }
+
+    #[tokio::test]
+    async fn test_cors_restricted_to_allowed_origins() {
+        let config = AppConfig {
+            cors_allowed_origins: vec![HeaderValue::from_static("https://app.example.com")],
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let preflight = |origin: &'static str| {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/api/v1/estimate-gas")
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(preflight("https://app.example.com"))
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://app.example.com"
+        );
+        assert!(headers["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+
+        let response = app
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+
+        // Any origin without an allow-list
+        let response = test_router()
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    }
+
+    #[tokio::test]
+    async fn test_body_limit_applies_after_decompression() {
+        // Compresses to a few KB but expands past the default 64 KB limit
+        let mut body = transfer_body().to_string().into_bytes();
+        body.splice(1..1, vec![b' '; 4 * 1024 * 1024]);
+
+        let response = test_router()
+            .oneshot(gzip_request("/api/v1/estimate-gas", &body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["error"]["type"], "payload_too_large");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected_as_error() {
+        let config = AppConfig {
+            max_body_bytes: 1024,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let mut body = transfer_body();
+        body["data"] = format!("0x{}", "00".repeat(1024)).into();
+
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["error"]["type"], "payload_too_large");
+        assert_eq!(
+            body["error"]["message"],
+            "Request body is larger than 1024 bytes"
+        );
 
-    Ok(app)
+        // Within the limit
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -1,32 +1,392 @@
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorFormat, Result};
+use crate::middleware::numbers::NumberEncoding;
+use crate::utils::format::CostFormat;
+use alloy_primitives::{address, utils::parse_ether, Address, U256};
+use axum::http::HeaderValue;
 use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::time::Duration;
+use url::Url;
+
+const DEFAULT_MAINTENANCE_MESSAGE: &str =
+    "The service is undergoing planned maintenance, please retry later";
+
+/// EntryPoint v0.7, deployed at the same address on every chain
+const DEFAULT_ENTRY_POINT: Address = address!("0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+/// Up to finality (two epochs, 64 blocks) for transfers of 100 ETH or more
+const DEFAULT_CONFIRMATION_THRESHOLDS: &str = "0:1,1:3,10:12,100:64";
+
+/// How the gas limit of a transaction is determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasEstimationMethod {
+    /// The node's `eth_estimateGas`
+    Node,
+    /// Client-side binary search over `eth_call`, for nodes with inaccurate estimates
+    BinarySearch,
+}
+
+impl FromStr for GasEstimationMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "node" => Ok(Self::Node),
+            "binary_search" => Ok(Self::BinarySearch),
+            _ => Err(Error::Config(format!(
+                "Unknown gas estimation method: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for GasEstimationMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Node => write!(f, "node"),
+            Self::BinarySearch => write!(f, "binary_search"),
+        }
+    }
+}
+
+/// How several `eth_estimateGas` samples of the same transaction are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasSampleAggregation {
+    /// Highest sample, the safest against under-estimates
+    Max,
+    /// Middle sample, the upper one for an even count
+    Median,
+}
+
+impl GasSampleAggregation {
+    pub fn aggregate(self, mut samples: Vec<u64>) -> Option<u64> {
+        match self {
+            Self::Max => samples.into_iter().max(),
+            Self::Median => {
+                samples.sort_unstable();
+                samples.get(samples.len() / 2).copied()
+            }
+        }
+    }
+}
+
+impl FromStr for GasSampleAggregation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "max" => Ok(Self::Max),
+            "median" => Ok(Self::Median),
+            _ => Err(Error::Config(format!(
+                "Unknown gas sample aggregation: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Pricing path for requests that carry no fee fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultTxType {
+    Legacy,
+    Eip1559,
+    /// EIP-1559 when the chain has a base fee, legacy otherwise
+    Auto,
+}
+
+impl FromStr for DefaultTxType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "legacy" => Ok(Self::Legacy),
+            "eip1559" => Ok(Self::Eip1559),
+            "auto" => Ok(Self::Auto),
+            _ => Err(Error::Config(format!(
+                "Unknown default transaction type: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Confirmations to wait for by transferred value, as `min_eth:confirmations` pairs
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConfirmationTable(Vec<(U256, u64)>);
+
+impl ConfirmationTable {
+    /// Confirmations of the largest threshold `value` reaches, `None` below all of them
+    pub fn confirmations_for(&self, value: U256) -> Option<u64> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(threshold, _)| value >= *threshold)
+            .map(|(_, confirmations)| *confirmations)
+    }
+}
+
+impl FromStr for ConfirmationTable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            |entry: &str| Error::Config(format!("Invalid confirmation threshold: {}", entry));
+        let mut thresholds = s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (eth, confirmations) = entry.split_once(':').ok_or_else(|| invalid(entry))?;
+                let threshold = parse_ether(eth.trim()).map_err(|_| invalid(entry))?;
+                let confirmations = confirmations.trim().parse().map_err(|_| invalid(entry))?;
+                Ok((threshold, confirmations))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        thresholds.sort_unstable();
+        Ok(Self(thresholds))
+    }
+}
+
+/// Credential kept out of `Debug` output, so logging the configuration doesn't leak it
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
 
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct AppConfig {
-    pub ethereum_rpc_url: String,
+    /// Nodes in failover order
+    pub ethereum_rpc_urls: Vec<String>,
+    /// Chain the nodes must serve, checked at startup
+    pub chain_id: Option<u64>,
     pub cache_duration: Duration,
+    /// Refresh the cached gas price and block number in the background instead of on
+    /// the first request after they expire
+    pub cache_background_refresh: bool,
     pub host: IpAddr,
     pub port: u16,
     pub log_level: String,
+    /// Maximum number of requests processed concurrently, 0 disables the limiter
+    pub max_concurrent_requests: usize,
+    /// Requests with a body larger than this are admitted with low priority
+    pub low_priority_body_bytes: u64,
+    /// Larger (decompressed) request bodies are rejected with a 413
+    pub max_body_bytes: usize,
+    /// How long a request may wait for a slot before a 503, zero waits indefinitely
+    pub queue_timeout: Duration,
+    /// Sign estimations as JWTs for clients sending `Accept: application/jwt`
+    pub sign_responses: bool,
+    pub signing_key: Option<Secret>,
+    /// How long a signed estimation stays valid
+    pub signed_response_ttl: Duration,
+    /// Make `/ready` also require a successful gas price fetch
+    pub readiness_check_gas_price: bool,
+    /// Serve an HTML status page at `/`
+    pub status_page: bool,
+    /// Answer estimation endpoints with a 503, can be toggled at runtime through SIGHUP
+    pub maintenance_mode: bool,
+    pub maintenance_message: String,
+    pub maintenance_retry_after_secs: u64,
+    pub gas_estimation_method: GasEstimationMethod,
+    /// Number of concurrent `eth_estimateGas` calls per estimate
+    pub gas_estimate_samples: usize,
+    pub gas_sample_aggregation: GasSampleAggregation,
+    /// Reject request bodies with fields the endpoint doesn't know
+    pub strict_request_fields: bool,
+    /// Validate estimate-gas bodies against their published JSON Schema
+    pub request_schema_validation: bool,
+    /// Largest `target` accepted by `/api/v1/fee-for-blocks`
+    pub fee_target_max_blocks: u64,
+    /// Most transactions accepted by a single `/api/v1/estimate-gas/batch` request
+    pub max_batch_size: usize,
+    /// Most priority fees priced by a single `/api/v1/fee-sweep` request
+    pub fee_sweep_max_points: usize,
+    /// Requests taking longer than this are logged as a warning
+    pub slow_request_threshold: Duration,
+    /// Rendering of the `*_eth` cost fields
+    pub cost_format: CostFormat,
+    /// Upper bound of concurrent calls to the node, lowered while it rate limits
+    pub rpc_max_concurrency: usize,
+    /// Calls to the node taking longer than this fail with a 504
+    pub rpc_timeout: Duration,
+    /// Retries of a call to the node failing at the transport level, 0 disables
+    pub rpc_max_retries: u32,
+    /// Delay before the first retry, doubling with each one
+    pub rpc_retry_base_delay: Duration,
+    /// Longest execution time reported, also used for underpriced transactions
+    pub max_execution_time: Duration,
+    /// Expected inclusion time of legacy and EIP-1559 transactions, about a block or
+    /// two of the chain
+    pub legacy_execution_time: Duration,
+    pub eip1559_execution_time: Duration,
+    /// Priority fee in wei of EIP-1559 transactions that don't set one, and of fee
+    /// suggestions without fee history
+    pub default_priority_fee: u128,
+    /// Retries of the initial connectivity check, for nodes starting alongside the service
+    pub startup_retry_attempts: u32,
+    /// Delay before the first startup retry, doubled after each one
+    pub startup_retry_delay: Duration,
+    pub error_format: ErrorFormat,
+    /// Whether integer amounts of JSON responses may be written as numbers
+    pub number_encoding: NumberEncoding,
+    /// Browser origins allowed by CORS, empty allows any origin
+    pub cors_allowed_origins: Vec<HeaderValue>,
+    /// Keys accepted in `X-API-Key` by the API routes, empty leaves them open
+    pub api_keys: Vec<Secret>,
+    /// StatsD server the metrics are pushed to, with the `statsd` feature
+    pub statsd_addr: Option<SocketAddr>,
+    pub statsd_interval: Duration,
+    /// Requests per second each API client may make, 0 disables the limit
+    pub rate_limit_per_second: u32,
+    /// Requests a client may make at once after idling, 0 for `rate_limit_per_second`
+    pub rate_limit_burst: u32,
+    /// Enables the `/admin` routes, guarded by this bearer token
+    pub admin_token: Option<Secret>,
+    pub default_tx_type: DefaultTxType,
+    /// Longest decoded `data` accepted, 0 disables the check
+    pub max_calldata_bytes: usize,
+    /// Largest block range scanned by `/api/v1/method-gas`
+    pub method_gas_max_blocks: u64,
+    /// Warn when the priority fee exceeds this multiple of the base fee, 0 disables
+    pub priority_fee_warning_multiple: f64,
+    /// Render addresses in responses in EIP-55 checksummed form
+    pub checksum_output: bool,
+    /// Headroom added to the node's gas estimate, in percent
+    pub gas_limit_buffer_percent: u64,
+    /// Suggested confirmations of value transfers, empty leaves them out
+    pub confirmation_thresholds: ConfirmationTable,
+    /// Log estimate-gas request and response bodies at debug level. Sensitive.
+    pub log_bodies: bool,
+    /// Replace addresses in logged bodies
+    pub redact_addresses: bool,
+    /// ETH/USD price endpoint behind `estimated_cost_usd`, unset leaves it out
+    pub price_feed_url: Option<String>,
+    /// ERC-4337 bundler behind `/api/v1/estimate-user-operation`
+    pub bundler_url: Option<Url>,
+    /// EntryPoint of user operations that don't name one
+    pub entry_point: Address,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            ethereum_rpc_urls: vec!["http://localhost:8545".into()],
+            chain_id: None,
+            cache_duration: Duration::from_secs(0),
+            cache_background_refresh: false,
+            host: IpAddr::from([0, 0, 0, 0]),
+            port: 8080,
+            log_level: "info".into(),
+            max_concurrent_requests: 0,
+            low_priority_body_bytes: 4096,
+            max_body_bytes: 64 * 1024,
+            queue_timeout: Duration::ZERO,
+            sign_responses: false,
+            signing_key: None,
+            signed_response_ttl: Duration::from_secs(30),
+            readiness_check_gas_price: false,
+            status_page: true,
+            maintenance_mode: false,
+            maintenance_message: DEFAULT_MAINTENANCE_MESSAGE.into(),
+            maintenance_retry_after_secs: 300,
+            gas_estimation_method: GasEstimationMethod::Node,
+            gas_estimate_samples: 1,
+            gas_sample_aggregation: GasSampleAggregation::Max,
+            strict_request_fields: false,
+            request_schema_validation: false,
+            fee_target_max_blocks: 20,
+            max_batch_size: 50,
+            fee_sweep_max_points: 50,
+            slow_request_threshold: Duration::from_millis(10_000),
+            cost_format: CostFormat::Fixed18,
+            rpc_max_concurrency: 64,
+            rpc_timeout: Duration::from_millis(5000),
+            rpc_max_retries: 3,
+            rpc_retry_base_delay: Duration::from_millis(100),
+            max_execution_time: Duration::from_secs(600),
+            legacy_execution_time: Duration::from_secs(30),
+            eip1559_execution_time: Duration::from_secs(15),
+            default_priority_fee: 1_500_000_000,
+            startup_retry_attempts: 0,
+            startup_retry_delay: Duration::from_millis(1000),
+            error_format: ErrorFormat::Nested,
+            number_encoding: NumberEncoding::String,
+            cors_allowed_origins: Vec::new(),
+            api_keys: Vec::new(),
+            rate_limit_per_second: 0,
+            rate_limit_burst: 0,
+            statsd_addr: None,
+            statsd_interval: Duration::from_secs(10),
+            admin_token: None,
+            default_tx_type: DefaultTxType::Legacy,
+            max_calldata_bytes: 0,
+            method_gas_max_blocks: 50,
+            priority_fee_warning_multiple: 1.0,
+            gas_limit_buffer_percent: 0,
+            confirmation_thresholds: ConfirmationTable::default(),
+            checksum_output: true,
+            log_bodies: false,
+            redact_addresses: true,
+            price_feed_url: None,
+            bundler_url: None,
+            entry_point: DEFAULT_ENTRY_POINT,
+        }
+    }
 }
 
 impl AppConfig {
     pub fn from_env() -> Result<Self> {
         let _ = dotenv::dotenv();
 
-        let ethereum_rpc_url = match std::env::var("ETHEREUM_RPC_URLS") {
-            Ok(val) => val,
-            Err(_) => {
-                return Err(Error::Config("No Ethereum RPC URLs provided".into()));
-            }
+        let ethereum_rpc_urls: Vec<String> = std::env::var("ETHEREUM_RPC_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(String::from)
+            .collect();
+        if ethereum_rpc_urls.is_empty() {
+            return Err(Error::Config("No Ethereum RPC URLs provided".into()));
+        }
+
+        let chain_id = match std::env::var("CHAIN_ID") {
+            Ok(chain_id) if !chain_id.is_empty() => Some(
+                chain_id
+                    .parse::<u64>()
+                    .map_err(|_| Error::Config("Invalid CHAIN_ID".into()))?,
+            ),
+            _ => None,
         };
 
         let cache_duration_secs = std::env::var("CACHE_DURATION_SECONDS")
             .unwrap_or_else(|_| "0".into())
             .parse::<u64>()
             .map_err(|_| Error::Config("Invalid CACHE_DURATION_SECS".into()))?;
+        let cache_background_refresh = env_or("CACHE_BACKGROUND_REFRESH", "false")?;
 
         let host = std::env::var("HOST")
             .unwrap_or_else(|_| "0.0.0.0".into())
@@ -40,12 +400,195 @@ impl AppConfig {
 
         let log_level = std::env::var("LOG_LEVEL").unwrap_or_else(|_| "info".into());
 
+        let max_concurrent_requests = env_or("MAX_CONCURRENT_REQUESTS", "0")?;
+        let low_priority_body_bytes = env_or("LOW_PRIORITY_BODY_BYTES", "4096")?;
+        let max_body_bytes = env_or("MAX_BODY_BYTES", "65536")?;
+        let queue_timeout_ms = env_or("QUEUE_TIMEOUT_MS", "0")?;
+
+        let sign_responses = env_or("SIGN_RESPONSES", "false")?;
+        let signing_key = std::env::var("SIGNING_KEY").ok().map(Secret::from);
+        if sign_responses && signing_key.is_none() {
+            return Err(Error::Config(
+                "SIGN_RESPONSES requires a SIGNING_KEY".into(),
+            ));
+        }
+        let signed_response_ttl_secs = env_or("SIGNED_RESPONSE_TTL_SECS", "30")?;
+
+        let readiness_check_gas_price = env_or("READINESS_CHECK_GAS_PRICE", "false")?;
+        let status_page = env_or("STATUS_PAGE", "true")?;
+
+        let maintenance_mode = env_or("MAINTENANCE_MODE", "false")?;
+        let maintenance_message = std::env::var("MAINTENANCE_MESSAGE")
+            .unwrap_or_else(|_| DEFAULT_MAINTENANCE_MESSAGE.into());
+        let maintenance_retry_after_secs = env_or("MAINTENANCE_RETRY_AFTER_SECS", "300")?;
+
+        let gas_estimation_method = std::env::var("GAS_ESTIMATION_METHOD")
+            .unwrap_or_else(|_| "node".into())
+            .parse()?;
+        let gas_estimate_samples = env_or("GAS_ESTIMATE_SAMPLES", "1")?;
+        if gas_estimate_samples == 0 {
+            return Err(Error::Config(
+                "GAS_ESTIMATE_SAMPLES must be at least 1".into(),
+            ));
+        }
+        let gas_sample_aggregation = std::env::var("GAS_SAMPLE_AGGREGATION")
+            .unwrap_or_else(|_| "max".into())
+            .parse()?;
+
+        let strict_request_fields = env_or("STRICT_REQUEST_FIELDS", "false")?;
+        let request_schema_validation = env_or("REQUEST_SCHEMA_VALIDATION", "false")?;
+        let fee_target_max_blocks = env_or("FEE_TARGET_MAX_BLOCKS", "20")?;
+        let max_batch_size = env_or("MAX_BATCH_SIZE", "50")?;
+        let fee_sweep_max_points = env_or("FEE_SWEEP_MAX_POINTS", "50")?;
+        let slow_request_ms = env_or("SLOW_REQUEST_MS", "10000")?;
+        let rpc_max_concurrency = env_or("RPC_MAX_CONCURRENCY", "64")?;
+        let rpc_timeout_ms = env_or("RPC_TIMEOUT_MS", "5000")?;
+        let rpc_max_retries = env_or("RPC_MAX_RETRIES", "3")?;
+        let rpc_retry_base_delay_ms = env_or("RPC_RETRY_BASE_DELAY_MS", "100")?;
+        let max_execution_time_secs = env_or("MAX_EXECUTION_TIME_SECS", "600")?;
+        let legacy_execution_time_ms = env_or("LEGACY_EXECUTION_TIME_MS", "30000")?;
+        let eip1559_execution_time_ms = env_or("EIP1559_EXECUTION_TIME_MS", "15000")?;
+        let default_priority_fee = env_or("DEFAULT_PRIORITY_FEE_WEI", "1500000000")?;
+        let startup_retry_attempts = env_or("STARTUP_RETRY_ATTEMPTS", "0")?;
+        let startup_retry_delay_ms = env_or("STARTUP_RETRY_DELAY_MS", "1000")?;
+        let max_calldata_bytes = env_or("MAX_CALLDATA_BYTES", "0")?;
+        let method_gas_max_blocks = env_or("METHOD_GAS_MAX_BLOCKS", "50")?;
+        let checksum_output = env_or("CHECKSUM_OUTPUT", "true")?;
+        let gas_limit_buffer_percent = env_or("GAS_LIMIT_BUFFER_PERCENT", "0")?;
+        let confirmation_thresholds = std::env::var("CONFIRMATION_THRESHOLDS")
+            .unwrap_or_else(|_| DEFAULT_CONFIRMATION_THRESHOLDS.into())
+            .parse()?;
+        let log_bodies = env_or("LOG_BODIES", "false")?;
+        let redact_addresses = env_or("REDACT_ADDRESSES", "true")?;
+        let priority_fee_warning_multiple: f64 = env_or("PRIORITY_FEE_WARNING_MULTIPLE", "1")?;
+        if priority_fee_warning_multiple.is_nan() || priority_fee_warning_multiple < 0.0 {
+            return Err(Error::Config(
+                "PRIORITY_FEE_WARNING_MULTIPLE must not be negative".into(),
+            ));
+        }
+        let price_feed_url = std::env::var("PRICE_FEED_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let bundler_url = match std::env::var("BUNDLER_URL") {
+            Ok(url) if !url.is_empty() => Some(
+                url.parse::<Url>()
+                    .map_err(|e| Error::Config(format!("Invalid BUNDLER_URL: {}", e)))?,
+            ),
+            _ => None,
+        };
+        let entry_point = match std::env::var("ENTRY_POINT_ADDRESS") {
+            Ok(entry_point) if !entry_point.is_empty() => {
+                entry_point.parse::<Address>().map_err(|_| {
+                    Error::Config(format!("Invalid ENTRY_POINT_ADDRESS: {}", entry_point))
+                })?
+            }
+            _ => DEFAULT_ENTRY_POINT,
+        };
+        let cors_allowed_origins = std::env::var("CORS_ALLOWED_ORIGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| Error::Config(format!("Invalid CORS origin: {}", origin)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let api_keys = std::env::var("API_KEY")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(Secret::from)
+            .collect();
+        let rate_limit_per_second = env_or("RATE_LIMIT_PER_SECOND", "0")?;
+        let rate_limit_burst = env_or("RATE_LIMIT_BURST", "0")?;
+        let statsd_addr = match std::env::var("STATSD_ADDR") {
+            Ok(addr) if !addr.is_empty() => Some(
+                addr.parse::<SocketAddr>()
+                    .map_err(|_| Error::Config("Invalid STATSD_ADDR".into()))?,
+            ),
+            _ => None,
+        };
+        let statsd_interval_ms = env_or("STATSD_INTERVAL_MS", "10000")?;
+        let admin_token = std::env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(Secret::from);
+        let default_tx_type = std::env::var("DEFAULT_TX_TYPE")
+            .unwrap_or_else(|_| "legacy".into())
+            .parse()?;
+        let error_format = std::env::var("ERROR_FORMAT")
+            .unwrap_or_else(|_| "nested".into())
+            .parse()?;
+        let number_encoding = std::env::var("NUMBER_ENCODING")
+            .unwrap_or_else(|_| "string".into())
+            .parse()?;
+        let cost_format = std::env::var("COST_FORMAT")
+            .unwrap_or_else(|_| "fixed18".into())
+            .parse()?;
+
         Ok(Self {
-            ethereum_rpc_url,
+            ethereum_rpc_urls,
+            chain_id,
             cache_duration: Duration::from_secs(cache_duration_secs),
+            cache_background_refresh,
             host,
             port,
             log_level,
+            max_concurrent_requests,
+            low_priority_body_bytes,
+            max_body_bytes,
+            queue_timeout: Duration::from_millis(queue_timeout_ms),
+            sign_responses,
+            signing_key,
+            signed_response_ttl: Duration::from_secs(signed_response_ttl_secs),
+            readiness_check_gas_price,
+            status_page,
+            maintenance_mode,
+            maintenance_message,
+            maintenance_retry_after_secs,
+            gas_estimation_method,
+            gas_estimate_samples,
+            gas_sample_aggregation,
+            strict_request_fields,
+            request_schema_validation,
+            fee_target_max_blocks,
+            max_batch_size,
+            fee_sweep_max_points,
+            slow_request_threshold: Duration::from_millis(slow_request_ms),
+            cost_format,
+            rpc_max_concurrency,
+            rpc_timeout: Duration::from_millis(rpc_timeout_ms),
+            rpc_max_retries,
+            rpc_retry_base_delay: Duration::from_millis(rpc_retry_base_delay_ms),
+            max_execution_time: Duration::from_secs(max_execution_time_secs),
+            legacy_execution_time: Duration::from_millis(legacy_execution_time_ms),
+            eip1559_execution_time: Duration::from_millis(eip1559_execution_time_ms),
+            default_priority_fee,
+            startup_retry_attempts,
+            startup_retry_delay: Duration::from_millis(startup_retry_delay_ms),
+            error_format,
+            number_encoding,
+            cors_allowed_origins,
+            api_keys,
+            rate_limit_per_second,
+            rate_limit_burst,
+            statsd_addr,
+            statsd_interval: Duration::from_millis(statsd_interval_ms),
+            admin_token,
+            default_tx_type,
+            max_calldata_bytes,
+            method_gas_max_blocks,
+            priority_fee_warning_multiple,
+            gas_limit_buffer_percent,
+            confirmation_thresholds,
+            checksum_output,
+            log_bodies,
+            redact_addresses,
+            price_feed_url,
+            bundler_url,
+            entry_point,
         })
     }
 
@@ -53,3 +596,11 @@ impl AppConfig {
         SocketAddr::new(self.host, self.port)
     }
 }
+
+/// Reads an optional environment variable, falling back to `default` when unset
+fn env_or<T: FromStr>(key: &str, default: &str) -> Result<T> {
+    std::env::var(key)
+        .unwrap_or_else(|_| default.into())
+        .parse::<T>()
+        .map_err(|_| Error::Config(format!("Invalid {}", key)))
+}
@@ -1,15 +1,78 @@
+use crate::utils::cbor::{accepts_cbor, Cbor};
+use crate::utils::rpc::{is_rate_limited, revert_reason};
+use crate::utils::rpc_timeout::as_rpc_timeout;
 use alloy_json_rpc::RpcError;
+use alloy_primitives::Bytes;
 use axum::{
+    extract::{Request, State},
     http::StatusCode,
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::str::FromStr;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Shape of error response bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// `{"error": {"message": ..., "type": ...}}`
+    #[default]
+    Nested,
+    /// `{"error": "message"}`, for clients that can't handle the nested shape
+    Flat,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nested" => Ok(Self::Nested),
+            "flat" => Ok(Self::Flat),
+            _ => Err(Error::Config(format!("Unknown error format: {}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nested => write!(f, "nested"),
+            Self::Flat => write!(f, "flat"),
+        }
+    }
+}
+
+/// How errors of the current request are rendered
+#[derive(Debug, Clone, Copy, Default)]
+struct ErrorStyle {
+    format: ErrorFormat,
+    /// Encode the body as CBOR, for clients that asked for it with `Accept`
+    cbor: bool,
+}
+
+tokio::task_local! {
+    static ERROR_STYLE: ErrorStyle;
+}
+
+/// Renders errors of the wrapped request in `format`, as CBOR when the client accepts it
+pub async fn apply_error_format(
+    State(format): State<ErrorFormat>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let style = ErrorStyle {
+        format,
+        cbor: accepts_cbor(request.headers()),
+    };
+    ERROR_STYLE.scope(style, next.run(request)).await
+}
+
 #[derive(Error, Debug, Deserialize)]
 pub enum Error {
     #[error("Configuration error: {0}")]
@@ -19,30 +82,125 @@ pub enum Error {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
     #[error("Gas estimation failed: {0}")]
-    GasEstimation(String),
+    GasEstimation(EstimationFailure),
     #[error("Server error: {0}")]
     Server(String),
+    #[error("Service under maintenance: {0}")]
+    Maintenance(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Service overloaded: {0}")]
+    Overloaded(String),
+    #[error("Timeout: {0}")]
+    Timeout(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+    #[error("Insufficient funds: {0}")]
+    InsufficientFunds(String),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("Invalid input: {} schema violation(s)", .0.len())]
+    SchemaViolations(Vec<SchemaViolation>),
+}
+
+/// Part of a request body that doesn't match the published schema
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value, `""` for the body itself
+    pub path: String,
+    pub message: String,
+}
+
+/// Why a transaction can't be estimated
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct EstimationFailure {
+    pub message: String,
+    /// Decoded reason of a revert, see `revert_reason`
+    pub revert_reason: Option<String>,
+}
+
+impl std::fmt::Display for EstimationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for EstimationFailure {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            revert_reason: None,
+        }
+    }
+}
+
+impl From<&str> for EstimationFailure {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         let err_type = self.error_type();
+        let revert_reason = match &self {
+            Error::GasEstimation(failure) => failure.revert_reason.clone(),
+            _ => None,
+        };
+        let violations = match &self {
+            Error::SchemaViolations(violations) => Some(json!(violations)),
+            _ => None,
+        };
         let (status, error_message) = match self {
             Error::Config(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Error::Provider(_) => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
             Error::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
-            Error::GasEstimation(msg) => (StatusCode::BAD_REQUEST, msg),
+            Error::GasEstimation(failure) => (StatusCode::BAD_REQUEST, failure.message),
             Error::Server(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            Error::Maintenance(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            Error::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            Error::Overloaded(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            Error::Timeout(msg) => (StatusCode::GATEWAY_TIMEOUT, msg),
+            Error::RateLimited(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
+            Error::Unsupported(msg) => (StatusCode::NOT_IMPLEMENTED, msg),
+            Error::InsufficientFunds(msg) => (StatusCode::BAD_REQUEST, msg),
+            Error::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
+            Error::SchemaViolations(_) => (
+                StatusCode::BAD_REQUEST,
+                "Request body doesn't match the schema".into(),
+            ),
         };
 
-        let body = Json(json!({
-            "error": {
-                "message": error_message,
-                "type": err_type  // Use the error_type method here
+        let style = ERROR_STYLE.try_with(|style| *style).unwrap_or_default();
+        let mut body = match style.format {
+            ErrorFormat::Nested => json!({
+                "error": {
+                    "message": error_message,
+                    "type": err_type  // Use the error_type method here
+                }
+            }),
+            ErrorFormat::Flat => json!({ "error": error_message }),
+        };
+        if let Some(revert_reason) = revert_reason {
+            match style.format {
+                ErrorFormat::Nested => body["error"]["revert_reason"] = revert_reason.into(),
+                ErrorFormat::Flat => body["revert_reason"] = revert_reason.into(),
             }
-        }));
+        }
+        if let Some(violations) = violations {
+            match style.format {
+                ErrorFormat::Nested => body["error"]["violations"] = violations,
+                ErrorFormat::Flat => body["violations"] = violations,
+            }
+        }
 
-        (status, body).into_response()
+        if style.cbor {
+            (status, Cbor(body)).into_response()
+        } else {
+            (status, Json(body)).into_response()
+        }
     }
 }
 
@@ -54,36 +212,57 @@ impl Error {
             Error::InvalidInput(_) => "invalid_input",
             Error::GasEstimation(_) => "gas_estimation_error",
             Error::Server(_) => "server_error",
+            Error::Maintenance(_) => "maintenance",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Overloaded(_) => "overloaded",
+            Error::Timeout(_) => "timeout",
+            Error::RateLimited(_) => "rate_limited",
+            Error::Unsupported(_) => "unsupported",
+            Error::InsufficientFunds(_) => "insufficient_funds",
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::SchemaViolations(_) => "invalid_input",
         }
     }
 }
 
-impl<T> From<RpcError<T>> for Error {
+impl<T: std::any::Any> From<RpcError<T>> for Error {
     fn from(error: RpcError<T>) -> Self {
+        if is_rate_limited(&error) {
+            return Error::RateLimited("RPC node is rate limiting requests".into());
+        }
         match error {
             RpcError::ErrorResp(payload) => {
                 let message = payload.message.to_lowercase();
                 if message.contains("execution reverted") {
-                    Error::GasEstimation(format!(
-                        "Transaction would fail: Details: {}",
-                        payload.message
-                    ))
+                    let data = payload.try_data_as::<Bytes>().and_then(|data| data.ok());
+                    Error::GasEstimation(EstimationFailure {
+                        message: format!("Transaction would fail: Details: {}", payload.message),
+                        revert_reason: data.and_then(|data| revert_reason(&data)),
+                    })
                 } else if message.contains("gas required exceeds allowance") {
                     Error::GasEstimation(
                         "Transaction would fail: gas required exceeds allowance".into(),
                     )
+                } else if message.contains("insufficient funds") {
+                    Error::InsufficientFunds(format!(
+                        "The sender can't cover the transaction value plus gas. Details: {}",
+                        payload.message
+                    ))
                 } else {
                     Error::Provider(format!("RPC error: {}", payload.message))
                 }
             }
-            RpcError::Transport(_) => Error::Provider(format!("Transport error")),
+            RpcError::Transport(kind) => match as_rpc_timeout(&kind) {
+                Some(timeout) => Error::Timeout(timeout.to_string()),
+                None => Error::Provider("Transport error".into()),
+            },
             RpcError::NullResp => Error::Provider("Received null response".into()),
             RpcError::SerError(e) => Error::Provider(format!("Serialization error: {}", e)),
             RpcError::DeserError { err, text } => {
                 Error::Provider(format!("Deserialization error: {} for text: {}", err, text))
             }
             RpcError::UnsupportedFeature(msg) => {
-                Error::Provider(format!("Unsupported feature: {}", msg))
+                Error::Unsupported(format!("The RPC node doesn't support {}", msg))
             }
             RpcError::LocalUsageError(e) => Error::Provider(format!("Local usage error: {}", e)),
         }
@@ -91,3 +270,99 @@ impl<T> From<RpcError<T>> for Error {
 }
 
 // Other From implementations remain the same...
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::body_bytes;
+    use serde_json::Value;
+
+    async fn render(format: Option<ErrorFormat>) -> Value {
+        let error = || Error::InvalidInput("Missing 'from' address".into()).into_response();
+        let response = match format {
+            Some(format) => {
+                let style = ErrorStyle {
+                    format,
+                    cbor: false,
+                };
+                ERROR_STYLE.scope(style, async { error() }).await
+            }
+            None => error(),
+        };
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        serde_json::from_slice(&body_bytes(response).await).unwrap()
+    }
+
+    fn reverted(data: &str) -> Error {
+        let data = serde_json::value::to_raw_value(data).unwrap();
+        Error::from(RpcError::<()>::ErrorResp(alloy_json_rpc::ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(data),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_revert_reason_in_body() {
+        // Error("Paused")
+        let data = concat!(
+            "0x08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000006",
+            "5061757365640000000000000000000000000000000000000000000000000000"
+        );
+        let response = reverted(data).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!({ "error": {
+                "message": "Transaction would fail: Details: execution reverted",
+                "type": "gas_estimation_error",
+                "revert_reason": "Paused"
+            } })
+        );
+
+        // A custom error, e.g. InsufficientBalance(uint256)
+        let Error::GasEstimation(failure) = reverted("0xcf479181000000000000") else {
+            panic!("expected a gas estimation error");
+        };
+        assert_eq!(failure.revert_reason.as_deref(), Some("0xcf479181"));
+        // No revert data at all
+        let Error::GasEstimation(failure) = reverted("0x") else {
+            panic!("expected a gas estimation error");
+        };
+        assert_eq!(failure.revert_reason, None);
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_funds_is_a_client_error() {
+        let error = Error::from(RpcError::<()>::ErrorResp(alloy_json_rpc::ErrorPayload {
+            code: -32000,
+            message: "insufficient funds for gas * price + value".into(),
+            data: None,
+        }));
+        assert_eq!(error.error_type(), "insufficient_funds");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "The sender can't cover the transaction value plus gas. \
+             Details: insufficient funds for gas * price + value"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_formats() {
+        let nested = json!({
+            "error": { "message": "Missing 'from' address", "type": "invalid_input" }
+        });
+        assert_eq!(render(None).await, nested);
+        assert_eq!(render(Some(ErrorFormat::Nested)).await, nested);
+        assert_eq!(
+            render(Some(ErrorFormat::Flat)).await,
+            json!({ "error": "Missing 'from' address" })
+        );
+    }
+}
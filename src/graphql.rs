@@ -0,0 +1,235 @@
+//! GraphQL endpoint over the estimation service, built with the `graphql` feature
+
+use alloy_rpc_types::FeeHistory as RpcFeeHistory;
+use async_graphql::{
+    EmptyMutation, EmptySubscription, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema,
+    SimpleObject,
+};
+use axum::{extract::State, Json};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::handlers::gas::prepare;
+use crate::models::transaction::{FeeUnit, GasEstimation, Recipient, TransactionInput};
+use crate::services::ethereum::EthereumService;
+
+/// Reward percentiles reported when `feeHistory` isn't given any
+const DEFAULT_PERCENTILES: [f64; 3] = [25.0, 50.0, 90.0];
+
+pub type GasSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn schema(service: Arc<EthereumService>, config: Arc<AppConfig>) -> GasSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(service)
+        .data(config)
+        .finish()
+}
+
+pub async fn graphql(
+    State(schema): State<GasSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Same estimate as `POST /api/v1/estimate-gas`
+    async fn estimate_gas(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: EstimateGasInput,
+    ) -> async_graphql::Result<GasEstimation> {
+        let service = ctx.data::<Arc<EthereumService>>()?;
+        let mut tx = TransactionInput::from(input);
+        prepare(&mut tx).map_err(graphql_error)?;
+        service.estimate_gas(tx).await.map_err(graphql_error)
+    }
+
+    /// Current gas price in wei, cached like the REST endpoints'
+    async fn gas_price(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<String> {
+        let service = ctx.data::<Arc<EthereumService>>()?;
+        let config = ctx.data::<Arc<AppConfig>>()?;
+        let gas_price = service.gas_price(config.cache_duration).await;
+        Ok(gas_price.map_err(graphql_error)?.to_string())
+    }
+
+    /// Base fees, gas usage and priority fees at `percentiles` of the last 20 blocks,
+    /// `null` when the node doesn't serve `eth_feeHistory`
+    async fn fee_history(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        percentiles: Option<Vec<f64>>,
+    ) -> async_graphql::Result<Option<FeeHistory>> {
+        let service = ctx.data::<Arc<EthereumService>>()?;
+        let percentiles = percentiles.unwrap_or_else(|| DEFAULT_PERCENTILES.to_vec());
+        let history = service.fee_history(&percentiles).await;
+        Ok(history.map_err(graphql_error)?.map(FeeHistory::from))
+    }
+}
+
+/// Fields of the estimate-gas body. `to: null` is a contract creation, as in JSON.
+#[derive(InputObject)]
+pub struct EstimateGasInput {
+    pub from: String,
+    #[graphql(default)]
+    pub to: MaybeUndefined<String>,
+    pub data: Option<String>,
+    pub value: Option<String>,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub gas_search_cap: Option<String>,
+    #[graphql(default)]
+    pub fee_unit: FeeUnit,
+}
+
+impl From<EstimateGasInput> for TransactionInput {
+    fn from(input: EstimateGasInput) -> Self {
+        Self {
+            from: input.from,
+            to: match input.to {
+                MaybeUndefined::Undefined => Recipient::Missing,
+                MaybeUndefined::Null => Recipient::Create,
+                MaybeUndefined::Value(to) => Recipient::Address(to),
+            },
+            data: input.data,
+            value: input.value,
+            gas_price: input.gas_price,
+            max_fee_per_gas: input.max_fee_per_gas,
+            max_priority_fee_per_gas: input.max_priority_fee_per_gas,
+            nonce: None,
+            gas_search_cap: input.gas_search_cap,
+            fee_unit: input.fee_unit,
+            access_list: None,
+            chain_id: None,
+            block: None,
+        }
+    }
+}
+
+/// `eth_feeHistory` with amounts as decimal wei strings
+#[derive(SimpleObject)]
+pub struct FeeHistory {
+    pub oldest_block: String,
+    /// One more entry than blocks: the last one is the next block's base fee
+    pub base_fee_per_gas: Vec<String>,
+    pub gas_used_ratio: Vec<f64>,
+    /// Per block, the priority fee at each requested percentile
+    pub reward: Vec<Vec<String>>,
+}
+
+impl From<RpcFeeHistory> for FeeHistory {
+    fn from(history: RpcFeeHistory) -> Self {
+        let wei = |amounts: &[u128]| amounts.iter().map(u128::to_string).collect();
+        Self {
+            oldest_block: history.oldest_block.to_string(),
+            base_fee_per_gas: wei(&history.base_fee_per_gas),
+            gas_used_ratio: history.gas_used_ratio,
+            reward: history
+                .reward
+                .unwrap_or_default()
+                .iter()
+                .map(|block| wei(block))
+                .collect(),
+        }
+    }
+}
+
+/// The error message, with the REST error `type` and any `revertReason` as extensions
+fn graphql_error(error: Error) -> async_graphql::Error {
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensions| {
+        extensions.set("type", error.error_type());
+        if let Error::GasEstimation(failure) = &error {
+            if let Some(reason) = &failure.revert_reason {
+                extensions.set("revertReason", reason.as_str());
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{
+        body_bytes, fee_history_rewards, json_request, mock_block, quantity, MockTransport,
+    };
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn query(query: &str) -> Value {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20_000_000_000)),
+            "eth_getBlockByNumber" => Ok(mock_block(
+                100,
+                Some(10_000_000_000),
+                30_000_000,
+                15_000_000,
+            )),
+            "eth_feeHistory" => Ok(fee_history_rewards(
+                &[10_000_000_000; 3],
+                &[0.5, 0.5],
+                &[1_000_000_000, 2_000_000_000],
+            )),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(json_request("/graphql", json!({ "query": query })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(&body_bytes(response).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_estimate_gas_query() {
+        let response = query(
+            r#"{
+                estimateGas(input: {
+                    from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+                    to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5",
+                    value: "1000000000000000"
+                }) { gasLimit gasPrice estimatedCostWei typeOfTransaction }
+                gasPrice
+                feeHistory(percentiles: [25, 50]) { baseFeePerGas gasUsedRatio reward }
+            }"#,
+        )
+        .await;
+
+        assert_eq!(
+            response["data"]["estimateGas"],
+            json!({
+                "gasLimit": "21000",
+                "gasPrice": "20000000000",
+                "estimatedCostWei": "420000000000000",
+                "typeOfTransaction": "legacy"
+            })
+        );
+        assert_eq!(response["data"]["gasPrice"], "20000000000");
+        let history = &response["data"]["feeHistory"];
+        assert_eq!(history["baseFeePerGas"].as_array().unwrap().len(), 3);
+        assert_eq!(history["gasUsedRatio"], json!([0.5, 0.5]));
+        assert_eq!(history["reward"][0], json!(["1000000000", "2000000000"]));
+    }
+
+    #[tokio::test]
+    async fn test_error_carries_rest_error_type() {
+        let response = query(
+            r#"{ estimateGas(input: { from: "0x123", to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5" }) { gasLimit } }"#,
+        )
+        .await;
+
+        assert_eq!(response["data"], Value::Null);
+        let error = &response["errors"][0];
+        assert_eq!(error["extensions"]["type"], "invalid_input");
+    }
+}
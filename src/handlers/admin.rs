@@ -0,0 +1,111 @@
+use axum::{extract::State, Json};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::services::ethereum::EthereumService;
+
+/// Cached values with their age, next to the node's current block for reference
+pub async fn inspect_cache(State(service): State<Arc<EthereumService>>) -> Result<Json<Value>> {
+    let (entries, block_number) =
+        tokio::join!(service.price_cache().entries(), service.block_number());
+
+    Ok(Json(json!({
+        "entries": entries,
+        "block_number": block_number?,
+    })))
+}
+
+/// Drops every cached value so the next requests fetch fresh data
+pub async fn clear_cache(State(service): State<Arc<EthereumService>>) -> Json<Value> {
+    let cleared = service.clear_caches().await;
+    tracing::info!("Cleared {} cache entries", cleared);
+
+    Json(json!({ "cleared": cleared }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, quantity, MockTransport};
+    use axum::body::Body;
+    use axum::http::{header::AUTHORIZATION, Request, StatusCode};
+    use axum::Router;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn admin_request(method: &str, uri: &str, token: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = body_bytes(response).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn transport() -> MockTransport {
+        MockTransport::new(|method, _| match method {
+            "eth_blockNumber" => Ok(quantity(100)),
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_gasPrice" => Ok(quantity(20_000_000_000)),
+            "eth_feeHistory" => Err("the method eth_feeHistory does not exist".into()),
+            _ => Err(format!("unexpected method {}", method)),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_inspect_and_clear_cache() {
+        let transport = transport();
+        let config = AppConfig {
+            admin_token: Some("s3cret".into()),
+            cache_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        service.gas_price(Duration::MAX).await.unwrap();
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_none());
+        let app = create_router(AppState::new(&config, service));
+
+        let (status, _) = send(&app, admin_request("GET", "/admin/cache", None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&app, admin_request("GET", "/admin/cache", Some("wrong"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = send(&app, admin_request("GET", "/admin/cache", Some("s3cret"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["block_number"], 100);
+        assert_eq!(body["entries"][0]["key"], "gas_price:1");
+        assert_eq!(body["entries"][0]["value"], "20000000000");
+
+        let (status, body) = send(
+            &app,
+            admin_request("POST", "/admin/cache/clear", Some("s3cret")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // The gas price and the node lacking eth_feeHistory
+        assert_eq!(body["cleared"], 2);
+
+        let (_, body) = send(&app, admin_request("GET", "/admin/cache", Some("s3cret"))).await;
+        assert!(body["entries"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_absent_without_token() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let (status, _) = send(&app, admin_request("GET", "/admin/cache", Some("s3cret"))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
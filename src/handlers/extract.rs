@@ -0,0 +1,86 @@
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::models::{schema::violations, transaction::TransactionInput, KnownFields};
+use axum::{
+    extract::{FromRef, FromRequest, Request},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// JSON body extractor that, with `STRICT_REQUEST_FIELDS`, rejects fields the
+/// target type doesn't know instead of silently ignoring them. With
+/// `REQUEST_SCHEMA_VALIDATION`, bodies of types with a schema must match it.
+pub struct InputJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for InputJson<T>
+where
+    T: DeserializeOwned + KnownFields,
+    S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<AppConfig>::from_ref(state);
+        let schema = T::schema().filter(|_| config.request_schema_validation);
+        if !config.strict_request_fields && schema.is_none() {
+            let Json(input) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self(input));
+        }
+
+        let Json(value) = Json::<Value>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Some(schema) = schema {
+            let violations = violations(schema, &value);
+            if !violations.is_empty() {
+                return Err(Error::SchemaViolations(violations).into_response());
+            }
+        }
+        if let Some(unknown) = config
+            .strict_request_fields
+            .then(|| unknown_field::<T>(&value))
+            .flatten()
+        {
+            return Err(Error::InvalidInput(format!("unknown field: {}", unknown)).into_response());
+        }
+
+        serde_json::from_value(value)
+            .map(Self)
+            .map_err(|e| Error::InvalidInput(e.to_string()).into_response())
+    }
+}
+
+/// First field of the body, or of a transaction nested in it, that `T` doesn't know,
+/// with its path, e.g. `transactions[1].maxFee`
+fn unknown_field<T: KnownFields>(value: &Value) -> Option<String> {
+    let fields = value.as_object()?;
+    let known = |key: &str| {
+        T::FIELDS.contains(&key)
+            || (T::FLATTENS_TRANSACTION && TransactionInput::FIELDS.contains(&key))
+    };
+    if let Some(key) = fields.keys().find(|key| !known(key)) {
+        return Some(key.clone());
+    }
+
+    T::NESTED_TRANSACTIONS
+        .iter()
+        .find_map(|&field| match fields.get(field)? {
+            Value::Array(transactions) => {
+                transactions
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, transaction)| {
+                        unknown_field::<TransactionInput>(transaction)
+                            .map(|key| format!("{}[{}].{}", field, i, key))
+                    })
+            }
+            transaction => unknown_field::<TransactionInput>(transaction)
+                .map(|key| format!("{}.{}", field, key)),
+        })
+}
@@ -0,0 +1,116 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::fees::{FeeForBlocks, NetworkFees};
+use crate::services::ethereum::EthereumService;
+
+#[derive(Debug, Deserialize)]
+pub struct FeeForBlocksParams {
+    /// Number of blocks the transaction should be included within
+    pub target: u64,
+}
+
+pub async fn fee_for_blocks(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    Query(params): Query<FeeForBlocksParams>,
+) -> Result<Json<FeeForBlocks>> {
+    let target = params.target.clamp(1, config.fee_target_max_blocks.max(1));
+
+    Ok(Json(service.fee_for_blocks(target).await?))
+}
+
+pub async fn gas_price(State(service): State<Arc<EthereumService>>) -> Result<Json<NetworkFees>> {
+    Ok(Json(service.network_fees().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, fee_history, mock_block, quantity, MockTransport};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_target_is_clamped() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_feeHistory" => Ok(fee_history(&[10_000_000_000; 5], &[0.5; 4], 1_000_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            fee_target_max_blocks: 5,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let request = Request::builder()
+            .uri("/api/v1/fee-for-blocks?target=100")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["target_blocks"], 5);
+        assert_eq!(body["projected_base_fees"].as_array().unwrap().len(), 5);
+        assert_eq!(body["max_fee_per_gas"], "11000000000");
+    }
+
+    #[tokio::test]
+    async fn test_gas_price_served_from_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let transport = MockTransport::new(move |method, _| {
+            if method != "eth_chainId" {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            match method {
+                "eth_chainId" => Ok(quantity(1)),
+                "eth_gasPrice" => Ok(quantity(12_000_000_000)),
+                "eth_getBlockByNumber" => Ok(mock_block(
+                    100,
+                    Some(10_000_000_000),
+                    30_000_000,
+                    15_000_000,
+                )),
+                "eth_feeHistory" => Ok(fee_history(&[10_000_000_000; 5], &[0.5; 4], 2_000_000_000)),
+                _ => Err(format!("unexpected method {}", method)),
+            }
+        });
+        let config = AppConfig {
+            cache_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        for _ in 0..2 {
+            let request = Request::builder()
+                .uri("/api/v1/gas-price")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["base_fee"], "10000000000");
+            assert_eq!(body["suggested_priority_fee"], "2000000000");
+            assert_eq!(body["suggested_max_fee"], "22000000000");
+            assert_eq!(body["gas_price_legacy"], "12000000000");
+        }
+        // The EIP-1559 probe, then one gas price, block and fee history fetch for both
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -1,26 +1,1214 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::join_all;
+use serde::Deserialize;
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::config::AppConfig;
 use crate::error::{Error, Result};
-use crate::models::transaction::{GasEstimation, TransactionInput};
-use crate::services::ethereum::EthereumService;
+use crate::handlers::extract::InputJson;
+use crate::models::transaction::{
+    BatchError, BatchEstimation, BatchInput, BatchItem, BatchSavings, BudgetEstimation,
+    BudgetInput, CallInput, CallResult, DiffInput, EstimateDiff, FeeSweep, FeeSweepInput, FeeUnit,
+    GasEstimation, Recipient, ReplacementEstimation, ReplacementInput, TransactionInput,
+};
+use crate::services::ethereum::{missing_recipient, EthereumService, TRANSFER_GAS};
+use crate::utils::cache::bypass_cache;
+use crate::utils::cbor::{accepts_cbor, Cbor};
+use crate::utils::format::{parse_gwei, redact_addresses};
+use crate::utils::jwt::{ResponseSigner, JWT_CONTENT_TYPE};
+use crate::utils::metrics::GAS_ESTIMATION_DURATION;
+use crate::utils::rpc_stats::record_rpc_calls;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EstimateParams {
+    /// List the RPC calls made for the estimate, off by default so node internals stay private
+    pub include_rpc_stats: bool,
+    /// Fetch a fresh gas price even when a cached one is still valid
+    pub no_cache: bool,
+    /// Try the access list from `eth_createAccessList`, using it when it saves gas
+    pub optimize: bool,
+    /// Trace the transaction to report the gas refunded for clearing storage
+    pub include_refund: bool,
+    /// Estimate without the request's access list and each of its slots too
+    pub access_list_impact: bool,
+    /// Answer with an approximate estimate when the full one takes longer than this
+    pub max_latency_ms: Option<u64>,
+}
+
+/// Query parameters of a replacement estimate, which has no access list or trace options
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ReplacementParams {
+    pub include_rpc_stats: bool,
+    /// Fetch a fresh gas price even when a cached one is still valid
+    pub no_cache: bool,
+}
 
 pub async fn estimate_gas(
     State(service): State<Arc<EthereumService>>,
-    Json(tx_input): Json<TransactionInput>,
-) -> Result<Json<GasEstimation>> {
-    if tx_input.from.is_empty() {
+    State(config): State<Arc<AppConfig>>,
+    State(signer): State<Option<Arc<ResponseSigner>>>,
+    Query(params): Query<EstimateParams>,
+    headers: HeaderMap,
+    InputJson(mut tx_input): InputJson<TransactionInput>,
+) -> Result<Response> {
+    prepare(&mut tx_input)?;
+    if params.optimize && params.access_list_impact {
+        return Err(Error::InvalidInput(
+            "optimize and access_list_impact can't be combined".into(),
+        ));
+    }
+
+    log_body(&config, "Estimating gas for transaction", &tx_input);
+
+    // Observed when dropped, failed estimations included
+    let timer = GAS_ESTIMATION_DURATION.start_timer();
+    let approximated = params.max_latency_ms.map(|_| tx_input.clone());
+    // Boxed, the estimate futures nest deep enough to overflow test thread stacks
+    let full_estimate = Box::pin(bypass_cache(params.no_cache, async {
+        let traced = params.include_refund.then(|| tx_input.clone());
+        let estimate = async {
+            if params.optimize {
+                service.estimate_gas_optimized(tx_input).await
+            } else if params.access_list_impact {
+                service.estimate_access_list_impact(tx_input).await
+            } else {
+                service.estimate_gas(tx_input).await
+            }
+        };
+        let refund = async {
+            match &traced {
+                Some(tx) => service.gas_refund(tx).await,
+                None => None,
+            }
+        };
+        let (estimation, gas_refund) = tokio::join!(estimate, refund);
+        Ok::<_, Error>(GasEstimation {
+            gas_refund: gas_refund.map(|refund| refund.to_string()),
+            ..estimation?
+        })
+    }));
+    let estimate = async {
+        let (Some(max_latency_ms), Some(tx)) = (params.max_latency_ms, &approximated) else {
+            return full_estimate.await;
+        };
+        match tokio::time::timeout(Duration::from_millis(max_latency_ms), full_estimate).await {
+            Ok(estimation) => estimation,
+            Err(_) => service.approximate_estimate(tx).await,
+        }
+    };
+    let estimation = if params.include_rpc_stats {
+        let (estimation, calls) = record_rpc_calls(estimate).await;
+        GasEstimation {
+            rpc_calls: Some(calls),
+            ..estimation?
+        }
+    } else {
+        estimate.await?
+    };
+    drop(timer);
+
+    log_body(&config, "Estimated gas", &estimation);
+
+    if let Some(signer) = signer.filter(|_| accepts_jwt(&headers)) {
+        let token = signer.sign(&estimation, service.chain_id().await?)?;
+        return Ok(([(CONTENT_TYPE, JWT_CONTENT_TYPE)], token).into_response());
+    }
+    if accepts_cbor(&headers) {
+        return Ok(Cbor(estimation).into_response());
+    }
+
+    Ok(Json(estimation).into_response())
+}
+
+/// Logs `body` at debug level with `LOG_BODIES`, without addresses with
+/// `REDACT_ADDRESSES`
+fn log_body(config: &AppConfig, message: &str, body: &impl std::fmt::Debug) {
+    if !config.log_bodies {
+        return;
+    }
+    let body = format!("{:?}", body);
+    if config.redact_addresses {
+        tracing::debug!("{}: {}", message, redact_addresses(&body));
+    } else {
+        tracing::debug!("{}: {}", message, body);
+    }
+}
+
+/// Estimates every transaction concurrently. A transaction that fails gets an inline
+/// error in its slot instead of failing the batch.
+pub async fn estimate_batch(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    InputJson(input): InputJson<BatchInput>,
+) -> Result<Json<BatchEstimation>> {
+    if input.transactions.len() > config.max_batch_size {
+        return Err(Error::InvalidInput(format!(
+            "Batch has {} transactions, at most {} allowed",
+            input.transactions.len(),
+            config.max_batch_size
+        )));
+    }
+
+    tracing::debug!(
+        "Estimating batch of {} transactions",
+        input.transactions.len()
+    );
+
+    let estimates = input.transactions.into_iter().map(|mut tx| {
+        let service = service.clone();
+        async move {
+            prepare(&mut tx)?;
+            service.estimate_gas(tx).await
+        }
+    });
+    let results: Vec<_> = join_all(estimates)
+        .await
+        .into_iter()
+        .map(|result| match result {
+            Ok(estimation) => BatchItem::Estimation(Box::new(estimation)),
+            Err(e) => BatchItem::Error {
+                error: BatchError {
+                    message: e.to_string(),
+                    error_type: e.error_type().to_string(),
+                },
+            },
+        })
+        .collect();
+    let estimated = results
+        .iter()
+        .filter(|item| matches!(item, BatchItem::Estimation(_)))
+        .count();
+
+    Ok(Json(BatchEstimation {
+        results,
+        batching_savings: batching_savings(estimated),
+    }))
+}
+
+/// Every transaction pays `TRANSFER_GAS` before execution, a multicall only once
+fn batching_savings(transactions: usize) -> BatchSavings {
+    let separate = TRANSFER_GAS * transactions as u64;
+    let batched = if transactions > 0 { TRANSFER_GAS } else { 0 };
+    BatchSavings {
+        transactions,
+        separate_base_gas: separate.to_string(),
+        batched_base_gas: batched.to_string(),
+        saved_gas: (separate - batched).to_string(),
+    }
+}
+
+pub async fn estimate_diff(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    InputJson(mut input): InputJson<DiffInput>,
+) -> Result<Json<EstimateDiff>> {
+    prepare(&mut input.baseline)?;
+    prepare(&mut input.candidate)?;
+
+    log_body(&config, "Comparing transactions", &input);
+    let diff = service.estimate_diff(input).await?;
+    log_body(&config, "Compared transactions", &diff);
+
+    Ok(Json(diff))
+}
+
+pub async fn call(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    InputJson(mut input): InputJson<CallInput>,
+) -> Result<Json<CallResult>> {
+    prepare(&mut input.transaction)?;
+
+    log_body(&config, "Executing call", &input);
+    let result = service.call(input).await?;
+    log_body(&config, "Executed call", &result);
+
+    Ok(Json(result))
+}
+
+/// Rejects empty addresses and a missing `to`, unless the transaction is a contract
+/// creation: a `null` `to`, or init code in `data`
+fn check_addresses(tx: &TransactionInput) -> Result<()> {
+    if tx.from.is_empty() {
         return Err(Error::InvalidInput("Missing 'from' address".into()));
     }
-    if tx_input.to.is_empty() {
-        return Err(Error::InvalidInput("Missing 'to' address".into()));
+    if tx.is_contract_creation() {
+        return Ok(());
+    }
+    match &tx.to {
+        Recipient::Missing => Err(missing_recipient()),
+        Recipient::Address(to) if to.is_empty() => {
+            Err(Error::InvalidInput("Missing 'to' address".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks a transaction from the request and brings its fees to wei, so malformed
+/// fields are rejected with their name before reaching the node
+pub(crate) fn prepare(tx: &mut TransactionInput) -> Result<()> {
+    check_addresses(tx)?;
+    fees_in_wei(tx)?;
+    tx.validate()
+}
+
+/// Converts the fee fields to wei, the unit the rest of the service works in
+fn fees_in_wei(tx: &mut TransactionInput) -> Result<()> {
+    if tx.fee_unit == FeeUnit::Gwei {
+        for fee in [
+            &mut tx.gas_price,
+            &mut tx.max_fee_per_gas,
+            &mut tx.max_priority_fee_per_gas,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *fee = parse_gwei(fee)?.to_string();
+        }
+        tx.fee_unit = FeeUnit::Wei;
     }
+    Ok(())
+}
 
-    tracing::debug!("Estimating gas for transaction: {:?}", tx_input);
+fn accepts_jwt(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(JWT_CONTENT_TYPE))
+}
 
-    let estimation = service.estimate_gas(tx_input).await?;
+pub async fn estimate_replacement(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    Query(params): Query<ReplacementParams>,
+    InputJson(mut input): InputJson<ReplacementInput>,
+) -> Result<Json<ReplacementEstimation>> {
+    prepare(&mut input.transaction)?;
 
-    tracing::debug!("Estimated gas: {:?}", estimation);
+    log_body(&config, "Estimating replacement for transaction", &input);
+
+    let estimation = bypass_cache(params.no_cache, async {
+        if params.include_rpc_stats {
+            let (estimation, calls) = record_rpc_calls(service.estimate_replacement(input)).await;
+            Ok::<_, Error>(ReplacementEstimation {
+                rpc_calls: Some(calls),
+                ..estimation?
+            })
+        } else {
+            service.estimate_replacement(input).await
+        }
+    })
+    .await?;
+    log_body(&config, "Estimated replacement", &estimation);
 
     Ok(Json(estimation))
 }
+
+pub async fn estimate_budget(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    InputJson(mut input): InputJson<BudgetInput>,
+) -> Result<Json<BudgetEstimation>> {
+    prepare(&mut input.transaction)?;
+
+    log_body(&config, "Estimating transaction against budget", &input);
+    let estimation = service.estimate_budget(input).await?;
+    log_body(&config, "Estimated against budget", &estimation);
+
+    Ok(Json(estimation))
+}
+
+pub async fn fee_sweep(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    InputJson(mut input): InputJson<FeeSweepInput>,
+) -> Result<Json<FeeSweep>> {
+    prepare(&mut input.transaction)?;
+
+    log_body(&config, "Sweeping priority fees", &input);
+    let sweep = service
+        .fee_sweep(input, config.fee_sweep_max_points)
+        .await?;
+    log_body(&config, "Swept priority fees", &sweep);
+
+    Ok(Json(sweep))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::batching_savings;
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::models::transaction::GasEstimation;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{
+        body_bytes, json_request, mock_block, quantity, transfer_body, transfer_transport,
+        MockTransport,
+    };
+    use axum::http::{header::CONTENT_TYPE, StatusCode};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn signing_config() -> AppConfig {
+        AppConfig {
+            sign_responses: true,
+            signing_key: Some("secret".into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_estimate_signed_only_when_jwt_accepted() {
+        let config = signing_config();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut request = json_request("/api/v1/estimate-gas", transfer_body());
+        request
+            .headers_mut()
+            .insert("accept", "application/jwt".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/jwt");
+        let token = body_bytes(response).await;
+        assert_eq!(token.split(|b| *b == b'.').count(), 3);
+
+        let request = json_request("/api/v1/estimate-gas", transfer_body());
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_estimate_and_errors_as_cbor() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut request = json_request("/api/v1/estimate-gas", transfer_body());
+        request
+            .headers_mut()
+            .insert("accept", "application/cbor".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/cbor");
+        let estimation: GasEstimation =
+            ciborium::from_reader(&body_bytes(response).await[..]).unwrap();
+        assert_eq!(estimation.gas_limit, "21000");
+
+        let mut body = transfer_body();
+        body["from"] = "".into();
+        let mut request = json_request("/api/v1/estimate-gas", body);
+        request
+            .headers_mut()
+            .insert("accept", "application/cbor".parse().unwrap());
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/cbor");
+        let body: Value = ciborium::from_reader(&body_bytes(response).await[..]).unwrap();
+        assert_eq!(body["error"]["type"], "invalid_input");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_calldata_rejected() {
+        let config = AppConfig {
+            max_calldata_bytes: 4,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut body = transfer_body();
+        body["data"] = "0xa9059cbb".into();
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", body.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        body["data"] = "0xa9059cbb00".into();
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Calldata is 5 bytes, at most 4 allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_reports_errors_inline() {
+        let gas_price_calls = Arc::new(AtomicUsize::new(0));
+        let counter = gas_price_calls.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_gasPrice" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(quantity(20_000_000_000))
+            }
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            cache_duration: Duration::from_secs(60),
+            max_batch_size: 3,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut invalid = transfer_body();
+        invalid["from"] = "".into();
+        let body = json!({ "transactions": [transfer_body(), invalid, transfer_body()] });
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas/batch", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let results = body["results"].as_array().unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["gas_limit"], "21000");
+        assert_eq!(results[2]["gas_limit"], "21000");
+        assert_eq!(
+            results[1],
+            json!({ "error": {
+                "message": "Invalid input: Missing 'from' address",
+                "type": "invalid_input"
+            } })
+        );
+        // Only the two estimated transactions count towards the savings
+        assert_eq!(body["batching_savings"]["transactions"], 2);
+        assert_eq!(body["batching_savings"]["saved_gas"], "21000");
+        // The gas price is fetched once and cached for the rest of the batch
+        assert_eq!(gas_price_calls.load(Ordering::SeqCst), 1);
+
+        let body = json!({ "transactions": vec![transfer_body(); 4] });
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas/batch", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Batch has 4 transactions, at most 3 allowed"
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bodies_logged_only_when_enabled() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let post = |config: AppConfig, uri: &'static str, body: Value| {
+            let logs = logs.clone();
+            async move {
+                logs.0.lock().unwrap().clear();
+                let service =
+                    EthereumService::with_provider(transfer_transport().provider(), &config);
+                let response = create_router(AppState::new(&config, service))
+                    .oneshot(json_request(uri, body))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                String::from_utf8(logs.0.lock().unwrap().clone()).unwrap()
+            }
+        };
+        let estimate = |config: AppConfig| post(config, "/api/v1/estimate-gas", transfer_body());
+        let from = transfer_body()["from"].as_str().unwrap().to_string();
+
+        let output = estimate(AppConfig::default()).await;
+        assert!(!output.contains("Estimating gas for transaction"));
+        assert!(!output.contains(&from));
+
+        let output = estimate(AppConfig {
+            log_bodies: true,
+            ..Default::default()
+        })
+        .await;
+        assert!(output.contains("Estimating gas for transaction"));
+        assert!(output.contains("Estimated gas"));
+        assert!(output.contains("0x[redacted]"));
+        assert!(!output.contains(&from));
+
+        let output = estimate(AppConfig {
+            log_bodies: true,
+            redact_addresses: false,
+            ..Default::default()
+        })
+        .await;
+        assert!(output.contains(&from));
+
+        // The other endpoints go through the same switch
+        let mut budget = transfer_body();
+        budget["max_budget_wei"] = "500000000000000".into();
+        let output = post(
+            AppConfig::default(),
+            "/api/v1/estimate-budget",
+            budget.clone(),
+        )
+        .await;
+        assert!(!output.contains("Estimating transaction against budget"));
+        let config = AppConfig {
+            log_bodies: true,
+            ..Default::default()
+        };
+        let output = post(config, "/api/v1/estimate-budget", budget).await;
+        assert!(output.contains("Estimating transaction against budget"));
+        assert!(output.contains("Estimated against budget"));
+        assert!(!output.contains(&from));
+    }
+
+    #[tokio::test]
+    async fn test_batch_savings_of_transfers() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let body = json!({ "transactions": vec![transfer_body(); 5] });
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas/batch", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(
+            body["batching_savings"],
+            json!({
+                "transactions": 5,
+                "separate_base_gas": "105000",
+                "batched_base_gas": "21000",
+                "saved_gas": "84000"
+            })
+        );
+        assert_eq!(batching_savings(0).saved_gas, "0");
+        assert_eq!(batching_savings(1).saved_gas, "0");
+    }
+
+    #[tokio::test]
+    async fn test_fee_unit() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let gas_price = |body: Value| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(json_request("/api/v1/estimate-gas", body))
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body: Value =
+                    serde_json::from_slice(&body_bytes(response).await).unwrap_or_default();
+                (status, body["gas_price"].clone())
+            }
+        };
+
+        let mut body = transfer_body();
+        body["gas_price"] = "50".into();
+        assert_eq!(gas_price(body.clone()).await, (StatusCode::OK, "50".into()));
+
+        body["fee_unit"] = "wei".into();
+        assert_eq!(gas_price(body.clone()).await, (StatusCode::OK, "50".into()));
+
+        body["fee_unit"] = "gwei".into();
+        assert_eq!(
+            gas_price(body.clone()).await,
+            (StatusCode::OK, "50000000000".into())
+        );
+
+        body["gas_price"] = "1.5".into();
+        assert_eq!(
+            gas_price(body.clone()).await,
+            (StatusCode::OK, "1500000000".into())
+        );
+
+        body["fee_unit"] = "ether".into();
+        assert_eq!(gas_price(body).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_fee_sweep() {
+        const GWEI: u128 = 1_000_000_000;
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            // Median priority fees of 1 to 4 gwei, 10 gwei base fee
+            "eth_feeHistory" => Ok(json!({
+                "oldestBlock": quantity(100),
+                "baseFeePerGas": vec![quantity(10 * GWEI); 5],
+                "gasUsedRatio": [0.5, 0.5, 0.5, 0.5],
+                "reward": (1..=4).map(|fee| vec![quantity(fee * GWEI)]).collect::<Vec<_>>(),
+            })),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            fee_sweep_max_points: 10,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut body = transfer_body();
+        body["priority_fee_from"] = GWEI.to_string().into();
+        body["priority_fee_to"] = (5 * GWEI).to_string().into();
+        body["priority_fee_step"] = GWEI.to_string().into();
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/fee-sweep", body.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let sweep: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(sweep["gas_limit"], "21000");
+        assert_eq!(sweep["base_fee_per_gas"], (10 * GWEI).to_string());
+
+        let points = sweep["points"].as_array().unwrap();
+        assert_eq!(points.len(), 5);
+        let outlook: Vec<_> = points
+            .iter()
+            .map(|point| {
+                (
+                    point["inclusion_probability"].as_f64().unwrap(),
+                    point["expected_blocks"].as_u64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            outlook,
+            vec![
+                (0.25, Some(4)),
+                (0.5, Some(2)),
+                (0.75, Some(2)),
+                (1.0, Some(1)),
+                (1.0, Some(1))
+            ]
+        );
+        assert_eq!(points[0]["max_priority_fee_per_gas"], GWEI.to_string());
+        assert_eq!(points[0]["max_fee_per_gas"], (21 * GWEI).to_string());
+        assert_eq!(
+            points[0]["estimated_cost_wei"],
+            (11 * GWEI * 21_000).to_string()
+        );
+
+        // Too fine a step for the point cap
+        body["priority_fee_step"] = (GWEI / 10).to_string().into();
+        let response = app
+            .oneshot(json_request("/api/v1/fee-sweep", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Sweep has 41 points, at most 10 allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_stats_only_when_requested() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let request = json_request("/api/v1/estimate-gas", transfer_body());
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(body.get("rpc_calls").is_none());
+
+        let request = json_request(
+            "/api/v1/estimate-gas?include_rpc_stats=true",
+            transfer_body(),
+        );
+        let response = app.oneshot(request).await.unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let mut methods: Vec<_> = body["rpc_calls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|call| {
+                assert!(call["duration_ms"].is_u64());
+                call["method"].as_str().unwrap()
+            })
+            .collect();
+        methods.sort_unstable();
+        assert_eq!(
+            methods,
+            vec!["eth_estimateGas", "eth_gasPrice", "eth_getBlockByNumber"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replacement_honors_no_cache() {
+        let gas_price_calls = Arc::new(AtomicUsize::new(0));
+        let counter = gas_price_calls.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(quantity(20_000_000_000))
+            }
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            cache_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let mut body = transfer_body();
+        body["gas_price"] = "10000000000".into();
+        body["nonce"] = 3.into();
+
+        for uri in [
+            "/api/v1/estimate-replacement",
+            "/api/v1/estimate-replacement",
+            "/api/v1/estimate-replacement?no_cache=true",
+        ] {
+            let response = app
+                .clone()
+                .oneshot(json_request(uri, body.clone()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(gas_price_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_latency_falls_back_to_approximation() {
+        let slow_node =
+            || transfer_transport().delayed("eth_estimateGas", Duration::from_millis(300));
+        let config = AppConfig {
+            cache_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let estimate = |uri: &'static str, app: axum::Router| async move {
+            let response = app
+                .oneshot(json_request(uri, transfer_body()))
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = serde_json::from_slice::<Value>(&body_bytes(response).await).unwrap();
+            (status, body)
+        };
+
+        let service = EthereumService::with_provider(slow_node().provider(), &config);
+        service.gas_price(Duration::MAX).await.unwrap();
+        let app = create_router(AppState::new(&config, service));
+
+        let started = std::time::Instant::now();
+        let (status, body) = estimate("/api/v1/estimate-gas?max_latency_ms=20", app.clone()).await;
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["approximate"], true);
+        assert_eq!(body["gas_limit"], "21000");
+        assert_eq!(body["gas_price"], "20000000000");
+        assert_eq!(body["warnings"].as_array().unwrap().len(), 1);
+
+        // Within the budget the full estimate is returned
+        let (_, body) = estimate("/api/v1/estimate-gas?max_latency_ms=5000", app).await;
+        assert!(body.get("approximate").is_none());
+
+        // Nothing to approximate with before a gas price was cached
+        let transport = slow_node().delayed("eth_gasPrice", Duration::from_millis(300));
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let (status, body) = estimate("/api/v1/estimate-gas?max_latency_ms=20", app).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["error"]["type"], "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_gas_refund_from_trace() {
+        // Clearing a slot: the refund counter reaches 4800 on SSTORE
+        let transport = MockTransport::new(|method, params| match method {
+            "debug_traceCall" => {
+                assert_eq!(params[2]["disableStorage"], true);
+                Ok(json!({
+                    "gas": 26_000,
+                    "failed": false,
+                    "returnValue": "",
+                    "structLogs": [
+                        { "pc": 0, "op": "SSTORE", "gas": 5_000, "gasCost": 2_900, "depth": 1 },
+                        { "pc": 1, "op": "STOP", "gas": 2_100, "gasCost": 0, "depth": 1, "refund": 4_800 }
+                    ]
+                }))
+            }
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20_000_000_000)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let estimate = |uri: &'static str, app: axum::Router| async move {
+            let response = app
+                .oneshot(json_request(uri, transfer_body()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            serde_json::from_slice::<Value>(&body_bytes(response).await).unwrap()
+        };
+
+        let body = estimate("/api/v1/estimate-gas?include_refund=true", app.clone()).await;
+        assert_eq!(body["gas_refund"], "4800");
+        let body = estimate("/api/v1/estimate-gas", app).await;
+        assert!(body.get("gas_refund").is_none());
+
+        // Left out when the node doesn't allow tracing
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let body = estimate("/api/v1/estimate-gas?include_refund=true", app).await;
+        assert!(body.get("gas_refund").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_rejects_unknown_fields() {
+        let mut body = transfer_body();
+        body["maxFee"] = "30000000000".into();
+
+        let config = AppConfig {
+            strict_request_fields: true,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", body.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(error["error"]["message"], "unknown field: maxFee");
+
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_rejects_unknown_nested_fields() {
+        let mut typo = transfer_body();
+        typo["maxFee"] = "30000000000".into();
+
+        let config = AppConfig {
+            strict_request_fields: true,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        for (uri, body, field) in [
+            (
+                "/api/v1/estimate-diff",
+                json!({ "baseline": transfer_body(), "candidate": typo.clone() }),
+                "candidate.maxFee",
+            ),
+            (
+                "/api/v1/estimate-gas/batch",
+                json!({ "transactions": [transfer_body(), typo.clone()] }),
+                "transactions[1].maxFee",
+            ),
+            (
+                "/api/v1/estimate-budget",
+                json!({ "max_budget_wei": "1", "maxBudget": "1" }),
+                "maxBudget",
+            ),
+        ] {
+            let response = app.clone().oneshot(json_request(uri, body)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let error: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(
+                error["error"]["message"],
+                format!("unknown field: {}", field)
+            );
+        }
+
+        let response = app
+            .oneshot(json_request(
+                "/api/v1/estimate-diff",
+                json!({ "baseline": transfer_body(), "candidate": transfer_body() }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_schema_validation_reports_each_violation() {
+        let config = AppConfig {
+            request_schema_validation: true,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+        let violations = |uri: &'static str, body: Value| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(json_request(uri, body)).await.unwrap();
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+                let error: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+                assert_eq!(error["error"]["type"], "invalid_input");
+                let mut violations: Vec<(String, String)> = error["error"]["violations"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|v| {
+                        let field = |name: &str| v[name].as_str().unwrap().to_string();
+                        (field("path"), field("message"))
+                    })
+                    .collect();
+                violations.sort();
+                violations
+            }
+        };
+
+        // Wrong type and additional property
+        let mut body = transfer_body();
+        body["value"] = 1000.into();
+        body["maxFee"] = "30000000000".into();
+        assert_eq!(
+            violations("/api/v1/estimate-gas", body).await,
+            vec![
+                (
+                    "".to_string(),
+                    "Additional properties are not allowed ('maxFee' was unexpected)".to_string()
+                ),
+                (
+                    "/value".to_string(),
+                    r#"1000 is not of type "string""#.to_string()
+                ),
+            ]
+        );
+
+        // Missing required field
+        let mut body = transfer_body();
+        body.as_object_mut().unwrap().remove("from");
+        assert_eq!(
+            violations("/api/v1/estimate-gas", body).await,
+            vec![(
+                "".to_string(),
+                r#""from" is a required property"#.to_string()
+            )]
+        );
+
+        // Batches point at the offending transaction
+        let mut transaction = transfer_body();
+        transaction["from"] = "0x1234".into();
+        let body = json!({ "transactions": [transfer_body(), transaction] });
+        let found = violations("/api/v1/estimate-gas/batch", body).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "/transactions/1/from");
+
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn post_budget(max_budget_wei: &str) -> Value {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let mut body = transfer_body();
+        body["max_budget_wei"] = max_budget_wei.into();
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-budget", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(&body_bytes(response).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_budget_within() {
+        // 21000 gas at 20 gwei costs 420000 gwei
+        let body = post_budget("500000000000000").await;
+        assert_eq!(body["estimated_cost_wei"], "420000000000000");
+        assert_eq!(body["within_budget"], true);
+        assert_eq!(body["max_fee_per_gas_within_budget"], "23809523809");
+    }
+
+    #[tokio::test]
+    async fn test_budget_exceeded() {
+        let body = post_budget("400000000000000").await;
+        assert_eq!(body["within_budget"], false);
+        assert_eq!(body["max_fee_per_gas_within_budget"], "19047619047");
+    }
+
+    async fn post_estimate(body: Value) -> (StatusCode, Value) {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_estimateGas" if params[0]["to"].is_null() => Ok(quantity(120_000)),
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20_000_000_000)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(json_request("/api/v1/estimate-gas", body))
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_slice(&body_bytes(response).await).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_malformed_fields_named_in_error() {
+        for (field, value, message) in [
+            (
+                "from",
+                "0x1234",
+                r#"Invalid 'from': expected a 20-byte hex address, got "0x1234""#,
+            ),
+            (
+                "to",
+                "0xZZ5222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5",
+                r#"Invalid 'to': expected a 20-byte hex address, got "0xZZ5222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5""#,
+            ),
+            (
+                "data",
+                "0xa9059cbg",
+                r#"Invalid 'data': expected hex bytes, got "0xa9059cbg""#,
+            ),
+            (
+                "value",
+                "0xZZ",
+                r#"Invalid 'value': expected a 256-bit integer, got "0xZZ""#,
+            ),
+            (
+                "max_fee_per_gas",
+                "-1",
+                r#"Invalid 'max_fee_per_gas': expected an integer amount of wei, got "-1""#,
+            ),
+            (
+                "gas_search_cap",
+                "lots",
+                r#"Invalid 'gas_search_cap': expected a 64-bit integer, got "lots""#,
+            ),
+        ] {
+            let mut body = transfer_body();
+            body[field] = value.into();
+            let (status, body) = post_estimate(body).await;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", field);
+            assert_eq!(body["error"]["message"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_to_rejected() {
+        let mut body = transfer_body();
+        body.as_object_mut().unwrap().remove("to");
+        let (status, body) = post_estimate(body).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"]["message"],
+            "to is required; use null for contract creation"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_null_to_is_contract_creation() {
+        let mut body = transfer_body();
+        body["to"] = Value::Null;
+        body["data"] = "0x6080604052".into();
+        let (status, body) = post_estimate(body).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["gas_limit"], "120000");
+        assert_eq!(body["is_contract_creation"], true);
+    }
+
+    #[tokio::test]
+    async fn test_missing_or_empty_to_with_data_is_contract_creation() {
+        let mut body = transfer_body();
+        body.as_object_mut().unwrap().remove("to");
+        body["data"] = "0x6080604052".into();
+        let (status, response) = post_estimate(body.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response["gas_limit"], "120000");
+        assert_eq!(response["is_contract_creation"], true);
+
+        body["to"] = "".into();
+        let (status, response) = post_estimate(body.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response["is_contract_creation"], true);
+
+        // Without init code there is nothing to deploy
+        body["data"] = "0x".into();
+        let (status, _) = post_estimate(body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_zero_address_to_allowed() {
+        let mut body = transfer_body();
+        body["to"] = "0x0000000000000000000000000000000000000000".into();
+        let (status, body) = post_estimate(body).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["gas_limit"], "21000");
+    }
+}
@@ -0,0 +1,102 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::history::MethodGas;
+use crate::services::ethereum::EthereumService;
+
+#[derive(Debug, Deserialize)]
+pub struct MethodGasParams {
+    pub to: String,
+    /// 4-byte function selector, hex encoded
+    pub selector: String,
+    /// Number of recent blocks to scan, `METHOD_GAS_MAX_BLOCKS` when omitted
+    pub blocks: Option<u64>,
+}
+
+pub async fn method_gas(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+    Query(params): Query<MethodGasParams>,
+) -> Result<Json<MethodGas>> {
+    let max_blocks = config.method_gas_max_blocks.max(1);
+    let blocks = params.blocks.unwrap_or(max_blocks).clamp(1, max_blocks);
+
+    Ok(Json(
+        service
+            .method_gas(&params.to, &params.selector, blocks)
+            .await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, mock_block, quantity, MockTransport};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn empty_chain() -> MockTransport {
+        MockTransport::new(|method, params| match method {
+            "eth_blockNumber" => Ok(quantity(100)),
+            "eth_getBlockByNumber" => {
+                let number = params[0].as_str().unwrap().trim_start_matches("0x");
+                let number = u64::from_str_radix(number, 16).unwrap();
+                Ok(mock_block(number, Some(1), 30_000_000, 15_000_000))
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        })
+    }
+
+    async fn get(app: Router, uri: &str) -> Value {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(&body_bytes(response).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_block_range_is_clamped() {
+        let transport = empty_chain();
+        let config = AppConfig {
+            method_gas_max_blocks: 3,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let body = get(app, "/api/v1/method-gas?to=0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5&selector=0xa9059cbb&blocks=1000").await;
+        assert_eq!(body["from_block"], 98);
+        assert_eq!(body["to_block"], 100);
+        assert_eq!(body["transactions"], 0);
+        assert_eq!(body["average_gas_used"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_address_is_checksummed() {
+        let uri = "/api/v1/method-gas?to=0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5&selector=0xa9059cbb&blocks=1";
+
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(empty_chain().provider(), &config);
+        let body = get(create_router(AppState::new(&config, service)), uri).await;
+        assert_eq!(body["to"], "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5");
+
+        let config = AppConfig {
+            checksum_output: false,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(empty_chain().provider(), &config);
+        let body = get(create_router(AppState::new(&config, service)), uri).await;
+        assert_eq!(body["to"], "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5");
+    }
+}
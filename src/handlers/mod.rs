@@ -1,12 +1,347 @@
+pub mod admin;
+pub mod extract;
+pub mod fees;
 pub mod gas;
+pub mod history;
+pub mod status;
+pub mod user_operation;
 
-use axum::response::IntoResponse;
+use crate::app::AppState;
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::capabilities::{Capabilities, Features};
+use crate::models::schema;
+use crate::services::ethereum::EthereumService;
+use axum::extract::State;
+use axum::http::{header::CONTENT_TYPE, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub async fn health() -> impl IntoResponse {
+/// Maximum age of the gas price used by the readiness probe
+const READINESS_GAS_PRICE_TTL: Duration = Duration::from_secs(5);
+
+/// Prometheus metrics in the text exposition format
+pub async fn metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::utils::metrics::render(),
+    )
+}
+
+/// Liveness, with the chain id of the node when it can be fetched
+pub async fn health(State(service): State<Arc<EthereumService>>) -> impl IntoResponse {
     Json(json!({
         "status": "ok",
-        "version": env!("CARGO_PKG_VERSION")
+        "version": env!("CARGO_PKG_VERSION"),
+        "chain_id": service.chain_id().await.ok()
+    }))
+}
+
+/// Version of this service and of the node client behind it
+pub async fn version(State(service): State<Arc<EthereumService>>) -> impl IntoResponse {
+    Json(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "client_version": service.client_version().await
+    }))
+}
+
+pub async fn ready(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+) -> Response {
+    let block_number = match service.block_number().await {
+        Ok(block_number) => block_number,
+        Err(e) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({ "status": "unavailable", "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+
+    if config.readiness_check_gas_price {
+        if let Err(e) = service.gas_price(READINESS_GAS_PRICE_TTL).await {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({
+                    "status": "degraded",
+                    "block_number": block_number,
+                    "error": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    }
+
+    Json(json!({ "status": "ready", "block_number": block_number })).into_response()
+}
+
+pub async fn capabilities(State(state): State<AppState>) -> Result<Json<Capabilities>> {
+    let (chain_id, eip1559) =
+        tokio::try_join!(state.service.chain_id(), state.service.supports_eip1559())?;
+    let client_version = state.service.client_version().await;
+    let config = &state.config;
+
+    Ok(Json(Capabilities {
+        chain_id,
+        eip1559,
+        client_version,
+        features: Features {
+            signed_responses: state.signer.is_some(),
+            gas_estimation_method: config.gas_estimation_method.to_string(),
+            gas_estimate_samples: config.gas_estimate_samples,
+            strict_request_fields: config.strict_request_fields,
+            request_schema_validation: config.request_schema_validation,
+            // Built in, with nothing to configure
+            rpc_stats: true,
+            gzip_requests: true,
+            cbor_responses: true,
+            maintenance_mode: state.maintenance.is_enabled(),
+            user_operations: state.bundler.is_some(),
+            graphql: cfg!(feature = "graphql"),
+            error_format: config.error_format.to_string(),
+            number_encoding: config.number_encoding.to_string(),
+            admin: config.admin_token.is_some(),
+            fee_tiers: eip1559 && state.service.serves_fee_history(),
+        },
     }))
 }
+
+/// JSON Schema of the estimate-gas body, enforced with `REQUEST_SCHEMA_VALIDATION`
+pub async fn estimate_gas_schema() -> Json<serde_json::Value> {
+    Json(schema::transaction_input())
+}
+
+/// JSON Schema of the batch estimate-gas body
+pub async fn estimate_batch_schema() -> Json<serde_json::Value> {
+    Json(schema::batch_input())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::error::ErrorFormat;
+    use crate::middleware::numbers::NumberEncoding;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{
+        body_bytes, json_request, quantity, transfer_body, transfer_transport, MockTransport,
+    };
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn get_ready(config: AppConfig) -> (StatusCode, Value) {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_blockNumber" => Ok(quantity(100)),
+            _ => Err("gas price unavailable".into()),
+        });
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let request = Request::builder()
+            .uri("/ready")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_slice(&body_bytes(response).await).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_ready_degraded_when_gas_price_fails() {
+        let config = AppConfig {
+            readiness_check_gas_price: true,
+            ..Default::default()
+        };
+        let (status, body) = get_ready(config).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["block_number"], 100);
+    }
+
+    #[tokio::test]
+    async fn test_ready_ignores_gas_price_by_default() {
+        let (status, body) = get_ready(AppConfig::default()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+    }
+
+    #[tokio::test]
+    async fn test_capabilities() {
+        let config = AppConfig {
+            strict_request_fields: true,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let request = Request::builder()
+            .uri("/api/v1/capabilities")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["chain_id"], 1);
+        assert_eq!(body["eip1559"], true);
+        assert_eq!(body["features"]["strict_request_fields"], true);
+        assert_eq!(body["features"]["signed_responses"], false);
+        assert_eq!(body["features"]["gas_estimation_method"], "node");
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_report_every_feature() {
+        let get_features = |config: AppConfig| async move {
+            let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+            let request = Request::builder()
+                .uri("/api/v1/capabilities")
+                .body(Body::empty())
+                .unwrap();
+            let response = create_router(AppState::new(&config, service))
+                .oneshot(request)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            body["features"].as_object().unwrap().clone()
+        };
+
+        // Everything optional turned on: a feature added without being wired to its
+        // config stays false and fails here
+        let features = get_features(AppConfig {
+            sign_responses: true,
+            signing_key: Some("secret".into()),
+            strict_request_fields: true,
+            request_schema_validation: true,
+            maintenance_mode: true,
+            bundler_url: Some("http://localhost:4337".parse().unwrap()),
+            admin_token: Some("s3cret".into()),
+            error_format: ErrorFormat::Flat,
+            number_encoding: NumberEncoding::NumberWhenSafe,
+            ..Default::default()
+        })
+        .await;
+        for (name, value) in &features {
+            match value {
+                Value::Bool(enabled) if name == "graphql" => {
+                    assert_eq!(*enabled, cfg!(feature = "graphql"))
+                }
+                Value::Bool(enabled) => assert!(enabled, "{} not reported", name),
+                _ => {}
+            }
+        }
+        assert_eq!(features["error_format"], "flat");
+        assert_eq!(features["number_encoding"], "number_when_safe");
+
+        let features = get_features(AppConfig::default()).await;
+        let enabled: Vec<_> = features
+            .iter()
+            .filter(|(_, value)| value.as_bool() == Some(true))
+            .map(|(name, _)| name.as_str())
+            .filter(|name| *name != "graphql")
+            .collect();
+        assert_eq!(
+            enabled,
+            ["rpc_stats", "gzip_requests", "cbor_responses", "fee_tiers"]
+        );
+        assert_eq!(features["error_format"], "nested");
+        assert_eq!(features["number_encoding"], "string");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_estimations() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let app = create_router(AppState::new(&config, service));
+
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = String::from_utf8(body_bytes(response).await.to_vec()).unwrap();
+
+        assert!(body.contains(
+            r#"http_requests_total{method="POST",path="/api/v1/estimate-gas",status="200"}"#
+        ));
+        assert!(body.contains("gas_estimation_duration_seconds_count"));
+        assert!(body.contains(r#"rpc_calls_total{method="eth_estimateGas"}"#));
+        assert!(body.contains("gas_price_cache_hits_total"));
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_chain_id() {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let request = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["chain_id"], 1);
+    }
+
+    async fn get_version(client_version: Result<&'static str, &'static str>) -> Value {
+        let transport = MockTransport::new(move |method, _| match method {
+            "web3_clientVersion" => client_version.map(Value::from).map_err(String::from),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let request = Request::builder()
+            .uri("/version")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(&body_bytes(response).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_version_reports_node_client() {
+        let body = get_version(Ok("Geth/v1.14.0-stable/linux-amd64/go1.22.2")).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            body["client_version"],
+            "Geth/v1.14.0-stable/linux-amd64/go1.22.2"
+        );
+
+        let body = get_version(Err("the method web3_clientVersion does not exist")).await;
+        assert_eq!(body["client_version"], "unknown");
+    }
+}
@@ -0,0 +1,130 @@
+use alloy_primitives::U256;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::services::ethereum::EthereumService;
+use crate::utils::format::CostFormat;
+
+/// Seconds between reloads of the status page
+const REFRESH_SECS: u64 = 10;
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{refresh}">
+<title>eth-gas-estimator</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+th { text-align: left; padding-right: 2em; }
+</style>
+</head>
+<body>
+<h1>eth-gas-estimator</h1>
+<table>
+<tr><th>Version</th><td>{version}</td></tr>
+<tr><th>Chain id</th><td>{chain_id}</td></tr>
+<tr><th>Gas price</th><td>{gas_price}</td></tr>
+<tr><th>Latest block</th><td>{block_number}</td></tr>
+</table>
+<p>Refreshes every {refresh} seconds.</p>
+</body>
+</html>
+"#;
+
+/// Human-readable overview of the deployment. Values the node can't provide show as
+/// unavailable instead of failing the page.
+pub async fn status_page(
+    State(service): State<Arc<EthereumService>>,
+    State(config): State<Arc<AppConfig>>,
+) -> impl IntoResponse {
+    let (chain_id, gas_price, block_number) = tokio::join!(
+        service.chain_id(),
+        service.gas_price(config.cache_duration),
+        service.cached_block_number()
+    );
+    let unavailable = || "unavailable".to_string();
+
+    Html(
+        TEMPLATE
+            .replace("{refresh}", &REFRESH_SECS.to_string())
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+            .replace(
+                "{chain_id}",
+                &chain_id.map_or_else(|_| unavailable(), |id| id.to_string()),
+            )
+            .replace(
+                "{gas_price}",
+                &gas_price.map_or_else(
+                    |_| unavailable(),
+                    |wei| format!("{} gwei", CostFormat::Trimmed.format_gwei(U256::from(wei))),
+                ),
+            )
+            .replace(
+                "{block_number}",
+                &block_number.map_or_else(|_| unavailable(), |n| n.to_string()),
+            ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, quantity, MockTransport};
+    use axum::body::Body;
+    use axum::http::{header::CONTENT_TYPE, Request, StatusCode};
+    use tower::ServiceExt;
+
+    async fn get_status(config: AppConfig) -> (StatusCode, String, String) {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_gasPrice" => Ok(quantity(12_500_000_000)),
+            "eth_blockNumber" => Ok(quantity(19_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+
+        let response = create_router(AppState::new(&config, service))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let body = String::from_utf8(body_bytes(response).await.to_vec()).unwrap();
+        (status, content_type, body)
+    }
+
+    #[tokio::test]
+    async fn test_status_page_renders() {
+        let (status, content_type, body) = get_status(AppConfig::default()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/html"));
+        assert!(body.contains(env!("CARGO_PKG_VERSION")));
+        assert!(body.contains("<td>1</td>"));
+        assert!(body.contains("12.5 gwei"));
+        assert!(body.contains("19000000"));
+        assert!(body.contains(r#"http-equiv="refresh""#));
+    }
+
+    #[tokio::test]
+    async fn test_status_page_can_be_disabled() {
+        let config = AppConfig {
+            status_page: false,
+            ..Default::default()
+        };
+        let (status, _, _) = get_status(config).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,132 @@
+use axum::{extract::State, Json};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::handlers::extract::InputJson;
+use crate::models::user_operation::{UserOperationGasEstimation, UserOperationInput};
+use crate::services::bundler::Bundler;
+
+/// Gas limits of an ERC-4337 UserOperation, estimated by the configured bundler
+pub async fn estimate_user_operation(
+    State(bundler): State<Option<Arc<Bundler>>>,
+    InputJson(input): InputJson<UserOperationInput>,
+) -> Result<Json<UserOperationGasEstimation>> {
+    let bundler = bundler.ok_or_else(|| {
+        Error::Unsupported("No bundler configured, user operations need a BUNDLER_URL".into())
+    })?;
+
+    Ok(Json(bundler.estimate_user_operation(input).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::bundler::Bundler;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{
+        body_bytes, json_request, quantity, transfer_transport, MockTransport,
+    };
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    const SENDER: &str = "0x742d35Cc6634C0532925a3b844Bc454e4438f44e";
+
+    fn user_operation() -> Value {
+        json!({
+            "user_operation": {
+                "sender": SENDER,
+                "nonce": "0x0",
+                "callData": "0xb61d27f6",
+                "signature": "0x"
+            }
+        })
+    }
+
+    async fn post(bundler: Option<Bundler>, body: Value) -> (StatusCode, Value) {
+        let config = AppConfig::default();
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let mut state = AppState::new(&config, service);
+        state.bundler = bundler.map(Arc::new);
+
+        let response = create_router(state)
+            .oneshot(json_request("/api/v1/estimate-user-operation", body))
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_slice(&body_bytes(response).await).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_estimate_user_operation() {
+        let params = Arc::new(Mutex::new(Value::Null));
+        let sent = params.clone();
+        let transport = MockTransport::new(move |method, params| match method {
+            "eth_estimateUserOperationGas" => {
+                *sent.lock().unwrap() = params.clone();
+                Ok(json!({
+                    "callGasLimit": quantity(33_100),
+                    "verificationGasLimit": quantity(98_500),
+                    // Some bundlers answer with numbers
+                    "preVerificationGas": 48_900
+                }))
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let entry_point = AppConfig::default().entry_point;
+        let bundler = || Bundler::with_provider(transport.provider(), entry_point);
+
+        let (status, body) = post(Some(bundler()), user_operation()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["call_gas_limit"], "33100");
+        assert_eq!(body["verification_gas_limit"], "98500");
+        assert_eq!(body["pre_verification_gas"], "48900");
+        assert_eq!(
+            body["entry_point"],
+            "0x0000000071727De22E5E9d8BAf0edAc6f37da032"
+        );
+
+        // The operation is passed through, along with the EntryPoint
+        let params = params.lock().unwrap().clone();
+        assert_eq!(params[0], user_operation()["user_operation"]);
+        assert_eq!(
+            params[1].as_str().unwrap().to_lowercase(),
+            "0x0000000071727de22e5e9d8baf0edac6f37da032"
+        );
+
+        let mut body = user_operation();
+        body["user_operation"]
+            .as_object_mut()
+            .unwrap()
+            .remove("callData");
+        let (status, body) = post(Some(bundler()), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"]["message"],
+            "Missing 'callData' in user_operation"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bundler_rejection_is_a_client_error() {
+        let transport = MockTransport::new(|_, _| Err("AA21 didn't pay prefund".into()));
+        let bundler = Bundler::with_provider(transport.provider(), Default::default());
+
+        let (status, body) = post(Some(bundler), user_operation()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["type"], "gas_estimation_error");
+        assert_eq!(
+            body["error"]["message"],
+            "Bundler rejected the user operation: AA21 didn't pay prefund"
+        );
+
+        let (status, body) = post(None, user_operation()).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(body["error"]["type"], "unsupported");
+    }
+}
@@ -1,7 +1,13 @@
 pub mod app;
 pub mod config;
 pub mod error;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 pub mod handlers;
+pub mod middleware;
 pub mod models;
 pub mod services;
 pub mod utils;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
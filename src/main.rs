@@ -22,6 +22,8 @@ async fn main() -> Result<()> {
 
     tracing::info!("Listening on {}", addr);
 
+    // Client addresses key the per-client rate limit
+    let router = router.into_make_service_with_connect_info::<std::net::SocketAddr>();
     axum::serve(listener, router)
         .with_graceful_shutdown(shutdown_signal())
         .await
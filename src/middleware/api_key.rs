@@ -0,0 +1,97 @@
+use crate::error::Error;
+use crate::middleware::admin::constant_time_eq;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Lets through requests whose `X-API-Key` header holds one of `keys`
+pub async fn require_api_key(
+    State(keys): State<Arc<[String]>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok());
+
+    if provided.is_some_and(|provided| is_known_key(&keys, provided)) {
+        next.run(request).await
+    } else {
+        Error::Unauthorized("Invalid or missing API key".into()).into_response()
+    }
+}
+
+/// Every key is compared, so timing doesn't tell which one came close
+pub(crate) fn is_known_key(keys: &[String], provided: &str) -> bool {
+    keys.iter().fold(false, |valid, key| {
+        valid | constant_time_eq(provided.as_bytes(), key.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, json_request, transfer_body, transfer_transport};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn router(api_keys: &[&str]) -> axum::Router {
+        let config = AppConfig {
+            api_keys: api_keys.iter().map(|&key| key.into()).collect(),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        create_router(AppState::new(&config, service))
+    }
+
+    fn estimate(api_key: Option<&str>) -> Request<Body> {
+        let mut request = json_request("/api/v1/estimate-gas", transfer_body());
+        if let Some(api_key) = api_key {
+            request
+                .headers_mut()
+                .insert("x-api-key", api_key.parse().unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_api_routes_need_a_key() {
+        let app = router(&["first", "second"]);
+
+        let response = app.clone().oneshot(estimate(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["error"]["type"], "unauthorized");
+        let response = app.clone().oneshot(estimate(Some("third"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for key in ["first", "second"] {
+            let response = app.clone().oneshot(estimate(Some(key))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Health checks stay open
+        let health = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(health).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_no_keys_configured_allows_all() {
+        let response = router(&[]).oneshot(estimate(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
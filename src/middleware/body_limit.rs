@@ -0,0 +1,26 @@
+use crate::error::Error;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Renders the `413` answered to bodies over `MAX_BODY_BYTES`, by `RequestBodyLimitLayer`
+/// when the `Content-Length` is too large or by the extractors while reading the body,
+/// as an [`Error`] instead of plain text
+pub async fn reject_oversized_bodies(
+    State(max_body_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    Error::PayloadTooLarge(format!(
+        "Request body is larger than {} bytes",
+        max_body_bytes
+    ))
+    .into_response()
+}
@@ -0,0 +1,138 @@
+use crate::error::Error;
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Runtime switch short-circuiting estimation requests during planned maintenance
+#[derive(Clone)]
+pub struct MaintenanceMode {
+    enabled: Arc<AtomicBool>,
+    message: Arc<str>,
+    retry_after_secs: u64,
+}
+
+impl MaintenanceMode {
+    pub fn new(enabled: bool, message: &str, retry_after_secs: u64) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            message: message.into(),
+            retry_after_secs,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        if self.enabled.swap(enabled, Ordering::Relaxed) != enabled {
+            tracing::info!(
+                "Maintenance mode {}",
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+}
+
+pub async fn reject_during_maintenance(
+    State(mode): State<MaintenanceMode>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !mode.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let mut response = Error::Maintenance(mode.message.to_string()).into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(mode.retry_after_secs));
+    response
+}
+
+/// Re-reads `MAINTENANCE_MODE` from the `.env` file (falling back to the process
+/// environment) every time the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_sighup(mode: MaintenanceMode) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        let from_file = std::fs::read_to_string(".env").ok().and_then(|contents| {
+            contents.lines().find_map(|line| {
+                let value = line.trim().strip_prefix("MAINTENANCE_MODE=")?;
+                Some(value.trim().trim_matches('"').to_string())
+            })
+        });
+        let value = from_file.or_else(|| std::env::var("MAINTENANCE_MODE").ok());
+
+        match value.as_deref().unwrap_or("false").parse::<bool>() {
+            Ok(enabled) => mode.set_enabled(enabled),
+            Err(_) => tracing::warn!("Ignoring invalid MAINTENANCE_MODE on reload"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{body_bytes, json_request, transfer_body, transfer_transport};
+    use axum::body::Body;
+    use axum::http::{header::RETRY_AFTER, Request, StatusCode};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_maintenance_rejects_estimation_but_not_health() {
+        let config = AppConfig {
+            maintenance_mode: true,
+            maintenance_message: "Node upgrade in progress".into(),
+            maintenance_retry_after_secs: 120,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        let state = AppState::new(&config, service);
+        let app = create_router(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "120");
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["error"]["message"], "Node upgrade in progress");
+        assert_eq!(body["error"]["type"], "maintenance");
+
+        let health = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(health).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        state.maintenance.set_enabled(false);
+        let response = app
+            .oneshot(json_request("/api/v1/estimate-gas", transfer_body()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
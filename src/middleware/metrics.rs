@@ -0,0 +1,24 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::utils::metrics::HTTP_REQUESTS;
+
+/// Counts responses by method, route and status. Routes are labelled by their pattern
+/// so path parameters don't multiply the series.
+pub async fn count_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_owned();
+
+    let response = next.run(request).await;
+    HTTP_REQUESTS
+        .with_label_values(&[&method, &path, response.status().as_str()])
+        .inc();
+    response
+}
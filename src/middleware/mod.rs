@@ -0,0 +1,52 @@
+pub mod admin;
+pub mod api_key;
+pub mod body_limit;
+pub mod maintenance;
+pub mod metrics;
+pub mod numbers;
+pub mod pretty;
+pub mod priority;
+pub mod rate_limit;
+pub mod slow_request;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE},
+    response::Response,
+};
+use serde_json::Value;
+
+/// Parses a JSON response body and writes it back with `rewrite`. Other responses,
+/// and bodies that aren't valid JSON, pass through unchanged.
+pub(crate) async fn rewrite_json(
+    response: Response,
+    rewrite: impl FnOnce(Value) -> serde_json::Result<Vec<u8>>,
+) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Failed to read JSON response body: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    match serde_json::from_slice(&bytes).and_then(rewrite) {
+        Ok(rewritten) => {
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(rewritten))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to rewrite JSON response body: {}", e);
+            Response::from_parts(parts, Body::from(bytes))
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use std::str::FromStr;
+
+use super::rewrite_json;
+use crate::error::{Error, Result};
+
+/// Largest integer JavaScript numbers (IEEE 754 doubles) hold exactly, 2^53 - 1
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// How integer amounts are written in JSON responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberEncoding {
+    /// Always as decimal strings, e.g. `"21000"`
+    #[default]
+    String,
+    /// As numbers up to `MAX_SAFE_INTEGER`, as strings above it
+    NumberWhenSafe,
+}
+
+impl FromStr for NumberEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "string" => Ok(Self::String),
+            "number_when_safe" => Ok(Self::NumberWhenSafe),
+            _ => Err(Error::Config(format!("Unknown number encoding: {}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for NumberEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::NumberWhenSafe => write!(f, "number_when_safe"),
+        }
+    }
+}
+
+/// Integer amounts of the responses, written as decimal strings: gas, fees and costs in
+/// wei, and block numbers
+const NUMERIC_FIELDS: &[&str] = &[
+    "average_gas_used",
+    "base_fee",
+    "base_fee_per_gas",
+    "batched_base_gas",
+    "block",
+    "call_gas_limit",
+    "cost_delta_wei",
+    "estimated_cost_wei",
+    "execution_gas",
+    "gas_delta",
+    "gas_estimate",
+    "gas_limit",
+    "gas_limit_raw",
+    "gas_price",
+    "gas_price_legacy",
+    "gas_refund",
+    "gas_with_access_list",
+    "gas_without_access_list",
+    "intrinsic_gas",
+    "l1_data_gas",
+    "legacy_gas_price",
+    "max_budget_wei",
+    "max_fee_per_gas",
+    "max_fee_per_gas_within_budget",
+    "max_priority_fee_per_gas",
+    "median_gas_used",
+    "next_base_fee_max",
+    "next_base_fee_min",
+    "pre_verification_gas",
+    "saved_gas",
+    "separate_base_gas",
+    "suggested_max_fee",
+    "suggested_priority_fee",
+    "verification_gas_limit",
+];
+
+/// Rewrites `NUMERIC_FIELDS` of JSON responses as numbers with
+/// `NumberEncoding::NumberWhenSafe`
+pub async fn encode_numbers(
+    State(encoding): State<NumberEncoding>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    if encoding == NumberEncoding::String {
+        return response;
+    }
+    rewrite_json(response, |mut value| {
+        unquote_safe_integers(&mut value);
+        serde_json::to_vec(&value)
+    })
+    .await
+}
+
+/// Turns `NUMERIC_FIELDS` holding a decimal integer JavaScript holds exactly into
+/// numbers, at any depth
+fn unquote_safe_integers(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                match value {
+                    Value::String(digits) if NUMERIC_FIELDS.contains(&key.as_str()) => {
+                        if let Some(number) = safe_integer(digits) {
+                            *value = number.into();
+                        }
+                    }
+                    value => unquote_safe_integers(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(unquote_safe_integers),
+        _ => {}
+    }
+}
+
+/// Integer of at most `MAX_SAFE_INTEGER` in magnitude, written without a sign other
+/// than `-` and without leading zeros
+fn safe_integer(digits: &str) -> Option<i64> {
+    digits
+        .parse::<i64>()
+        .ok()
+        .filter(|value| value.unsigned_abs() <= MAX_SAFE_INTEGER)
+        .filter(|value| value.to_string() == digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        middleware::from_fn_with_state,
+        routing::get,
+        Json, Router,
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    async fn estimate(encoding: NumberEncoding) -> Value {
+        let app = Router::new()
+            .route(
+                "/estimate",
+                get(|| async {
+                    Json(json!({
+                        "gas_limit": "21000",
+                        "estimated_cost_wei": "9007199254740993000",
+                        "estimated_cost_eth": "9.007199254740993",
+                        "estimate_id": "0x0123",
+                        "block": "latest",
+                        "base_fee_per_gas": "9007199254740991",
+                        "max_fee_per_gas": "9007199254740992",
+                        "gas_price": "0021",
+                        "gas_delta": "-5",
+                        "nonce_label": "7",
+                        "tiers": { "fast": { "max_priority_fee_per_gas": "2" } },
+                        "results": [{ "gas_estimate": "7" }, "8"]
+                    }))
+                }),
+            )
+            .layer(from_fn_with_state(encoding, encode_numbers));
+        let request = Request::builder()
+            .uri("/estimate")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_numbers_only_when_safe() {
+        let body = estimate(NumberEncoding::String).await;
+        assert_eq!(body["gas_limit"], "21000");
+
+        let body = estimate(NumberEncoding::NumberWhenSafe).await;
+        assert_eq!(body["gas_limit"], 21000);
+        assert_eq!(body["base_fee_per_gas"], 9_007_199_254_740_991u64);
+        assert_eq!(body["gas_delta"], -5);
+        // Past 2^53 a JavaScript client would round the number
+        assert_eq!(body["estimated_cost_wei"], "9007199254740993000");
+        assert_eq!(body["max_fee_per_gas"], "9007199254740992");
+        assert_eq!(body["estimated_cost_eth"], "9.007199254740993");
+        assert_eq!(body["estimate_id"], "0x0123");
+        assert_eq!(body["block"], "latest");
+        assert_eq!(body["gas_price"], "0021");
+        // Only amounts, whatever other strings look like
+        assert_eq!(body["nonce_label"], "7");
+        assert_eq!(body["tiers"]["fast"]["max_priority_fee_per_gas"], 2);
+        assert_eq!(body["results"], json!([{ "gas_estimate": 7 }, "8"]));
+        // Fields keep their order
+        let fields: Vec<_> = body.as_object().unwrap().keys().take(2).collect();
+        assert_eq!(fields, ["gas_limit", "estimated_cost_wei"]);
+    }
+
+    #[test]
+    fn test_encoding_parses() {
+        assert_eq!(
+            "number_when_safe".parse::<NumberEncoding>().unwrap(),
+            NumberEncoding::NumberWhenSafe
+        );
+        assert!("numbers".parse::<NumberEncoding>().is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use super::rewrite_json;
+use axum::{extract::Request, middleware::Next, response::Response};
+
+/// Re-indents JSON responses, errors included, for requests with `?pretty=true`.
+/// Fields keep their order.
+pub async fn pretty_print_json(request: Request, next: Next) -> Response {
+    let pretty = request
+        .uri()
+        .query()
+        .is_some_and(|query| query.split('&').any(|pair| pair == "pretty=true"));
+    let response = next.run(request).await;
+    if !pretty {
+        return response;
+    }
+    rewrite_json(response, |value| serde_json::to_vec_pretty(&value)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use axum::{
+        body::{to_bytes, Body},
+        middleware::from_fn,
+        routing::get,
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_pretty_only_when_asked() {
+        let app = Router::new()
+            .route(
+                "/ok",
+                get(|| async { Json(json!({ "gas_price": "1", "gas_limit": "21000" })) }),
+            )
+            .route(
+                "/error",
+                get(|| async { Err::<(), _>(Error::InvalidInput("bad".into())) }),
+            )
+            .layer(from_fn(pretty_print_json));
+        let get_body = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                String::from_utf8(bytes.to_vec()).unwrap()
+            }
+        };
+
+        let compact = get_body("/ok").await;
+        assert!(!compact.contains('\n'));
+        let pretty = get_body("/ok?pretty=true").await;
+        assert_eq!(
+            pretty,
+            "{\n  \"gas_price\": \"1\",\n  \"gas_limit\": \"21000\"\n}"
+        );
+
+        assert!(!get_body("/error").await.contains('\n'));
+        let error = get_body("/error?pretty=true").await;
+        assert!(error.contains("\n    \"message\": \"bad\""));
+        let parsed: Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(parsed["error"]["type"], "invalid_input");
+    }
+}
@@ -0,0 +1,416 @@
+use crate::error::Error;
+use axum::{
+    body::Body,
+    http::header::{CONTENT_LENGTH, RETRY_AFTER},
+    http::{HeaderValue, Request},
+    response::{IntoResponse, Response},
+};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tower::{Layer, Service};
+
+/// Admission class of a request, higher classes are served first when saturated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// Classifies a request by path and declared body size
+    pub fn classify(req: &Request<Body>, low_priority_body_bytes: u64) -> Self {
+        let path = req.uri().path();
+        if path == "/health" || path == "/ready" || path == "/metrics" {
+            return Priority::High;
+        }
+        if path.ends_with("/batch") || path.ends_with("/simulate") {
+            return Priority::Low;
+        }
+
+        let body_len = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        if body_len > low_priority_body_bytes {
+            Priority::Low
+        } else {
+            Priority::Normal
+        }
+    }
+}
+
+/// Concurrency limiter handing freed slots to the highest-priority waiter
+pub struct PriorityLimiter {
+    max_concurrent: usize,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    in_flight: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    tx: oneshot::Sender<Permit>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Highest priority first, FIFO within a class
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Slot held for the lifetime of a request, released on drop
+pub struct Permit {
+    limiter: Option<Arc<PriorityLimiter>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release();
+        }
+    }
+}
+
+impl PriorityLimiter {
+    pub fn new(max_concurrent: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_concurrent,
+            state: Mutex::new(LimiterState {
+                in_flight: 0,
+                next_seq: 0,
+                waiters: BinaryHeap::new(),
+            }),
+        })
+    }
+
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> Permit {
+        let rx = {
+            let mut state = self.state.lock().expect("priority limiter poisoned");
+            if state.in_flight < self.max_concurrent {
+                state.in_flight += 1;
+                return Permit {
+                    limiter: Some(self.clone()),
+                };
+            }
+
+            let (tx, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter { priority, seq, tx });
+            rx
+        };
+
+        tracing::debug!("Limiter saturated, queued {:?} priority request", priority);
+        // Waiters are only dropped after a permit was sent to them, a caller giving
+        // up drops the receiver instead and `release` skips it
+        rx.await.expect("priority limiter dropped a queued request")
+    }
+
+    fn release(self: &Arc<Self>) {
+        loop {
+            let waiter = {
+                let mut state = self.state.lock().expect("priority limiter poisoned");
+                match state.waiters.pop() {
+                    Some(waiter) => waiter,
+                    None => {
+                        state.in_flight -= 1;
+                        return;
+                    }
+                }
+            };
+
+            // Hand the slot over directly; if the waiter went away, try the next one
+            match waiter.tx.send(Permit {
+                limiter: Some(self.clone()),
+            }) {
+                Ok(()) => return,
+                Err(mut permit) => {
+                    permit.limiter.take();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PriorityLimitLayer {
+    limiter: Arc<PriorityLimiter>,
+    low_priority_body_bytes: u64,
+    queue_timeout: Duration,
+}
+
+impl PriorityLimitLayer {
+    /// A zero `queue_timeout` lets requests wait for a slot indefinitely
+    pub fn new(
+        max_concurrent: usize,
+        low_priority_body_bytes: u64,
+        queue_timeout: Duration,
+    ) -> Self {
+        Self {
+            limiter: PriorityLimiter::new(max_concurrent),
+            low_priority_body_bytes,
+            queue_timeout,
+        }
+    }
+}
+
+impl<S> Layer<S> for PriorityLimitLayer {
+    type Service = PriorityLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PriorityLimit {
+            inner,
+            limiter: self.limiter.clone(),
+            low_priority_body_bytes: self.low_priority_body_bytes,
+            queue_timeout: self.queue_timeout,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PriorityLimit<S> {
+    inner: S,
+    limiter: Arc<PriorityLimiter>,
+    low_priority_body_bytes: u64,
+    queue_timeout: Duration,
+}
+
+fn queue_timeout_response(queue_timeout: Duration) -> Response {
+    let mut response = Error::Overloaded(format!(
+        "No capacity available within {} ms",
+        queue_timeout.as_millis()
+    ))
+    .into_response();
+    // Whole seconds, rounded up so clients don't retry immediately
+    let retry_after = queue_timeout.as_secs() + u64::from(queue_timeout.subsec_nanos() > 0);
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+impl<S> Service<Request<Body>> for PriorityLimit<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let priority = Priority::classify(&req, self.low_priority_body_bytes);
+        let limiter = self.limiter.clone();
+        let queue_timeout = self.queue_timeout;
+        // Take the service that was driven to readiness, leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let _permit = if queue_timeout.is_zero() {
+                limiter.acquire(priority).await
+            } else {
+                match tokio::time::timeout(queue_timeout, limiter.acquire(priority)).await {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        tracing::warn!(
+                            "{:?} priority request timed out waiting for a slot",
+                            priority
+                        );
+                        return Ok(queue_timeout_response(queue_timeout));
+                    }
+                }
+            };
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, routing::post, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn request(method: &str, uri: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            Priority::classify(&request("GET", "/health"), 1024),
+            Priority::High
+        );
+        assert_eq!(
+            Priority::classify(&request("POST", "/api/v1/estimate-gas/batch"), 1024),
+            Priority::Low
+        );
+        assert_eq!(
+            Priority::classify(&request("POST", "/api/v1/estimate-gas"), 1024),
+            Priority::Normal
+        );
+
+        let large = Request::builder()
+            .method("POST")
+            .uri("/api/v1/estimate-gas")
+            .header(CONTENT_LENGTH, "4096")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(Priority::classify(&large, 1024), Priority::Low);
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_overtakes_queued_low_priority() {
+        // Spawned requests queue up while the paused clock is advanced, without
+        // depending on how fast a loaded machine schedules them
+        tokio::time::pause();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let release_rx = Arc::new(tokio::sync::Mutex::new(Some(release_rx)));
+
+        let batch_order = order.clone();
+        let health_order = order.clone();
+        let app = Router::new()
+            .route(
+                "/api/v1/estimate-gas/batch",
+                post(move || {
+                    let order = batch_order.clone();
+                    let release_rx = release_rx.clone();
+                    async move {
+                        order.lock().unwrap().push("batch");
+                        // Only the first request blocks, holding the single slot
+                        if let Some(rx) = release_rx.lock().await.take() {
+                            let _ = rx.await;
+                        }
+                    }
+                }),
+            )
+            .route(
+                "/health",
+                get(move || {
+                    let order = health_order.clone();
+                    async move {
+                        order.lock().unwrap().push("health");
+                    }
+                }),
+            )
+            .layer(PriorityLimitLayer::new(1, 1024, Duration::ZERO));
+
+        let mut handles = Vec::new();
+        for _ in 0..3 {
+            let app = app.clone();
+            handles.push(tokio::spawn(async move {
+                app.oneshot(request("POST", "/api/v1/estimate-gas/batch"))
+                    .await
+                    .unwrap()
+            }));
+            tokio::time::advance(Duration::from_millis(20)).await;
+        }
+
+        let health_app = app.clone();
+        handles.push(tokio::spawn(async move {
+            health_app.oneshot(request("GET", "/health")).await.unwrap()
+        }));
+        tokio::time::advance(Duration::from_millis(20)).await;
+
+        release_tx.send(()).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["batch", "health", "batch", "batch"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_times_out() {
+        let app = Router::new()
+            .route(
+                "/api/v1/estimate-gas",
+                post(|| tokio::time::sleep(Duration::from_millis(500))),
+            )
+            .layer(PriorityLimitLayer::new(2, 1024, Duration::from_millis(100)));
+
+        let mut slow = Vec::new();
+        for _ in 0..2 {
+            let app = app.clone();
+            slow.push(tokio::spawn(async move {
+                app.oneshot(request("POST", "/api/v1/estimate-gas"))
+                    .await
+                    .unwrap()
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let started = std::time::Instant::now();
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/api/v1/estimate-gas"))
+            .await
+            .unwrap();
+        let waited = started.elapsed();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+        assert!(
+            waited >= Duration::from_millis(100) && waited < Duration::from_millis(400),
+            "waited {:?}",
+            waited
+        );
+
+        // The requests holding the slots are unaffected
+        for handle in slow {
+            assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+        }
+        // And the timed out waiter doesn't hold on to a slot
+        let response = app
+            .oneshot(request("POST", "/api/v1/estimate-gas"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
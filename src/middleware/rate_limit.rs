@@ -0,0 +1,188 @@
+use crate::error::Error;
+use crate::middleware::api_key::{is_known_key, API_KEY_HEADER};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultKeyedRateLimiter, Quota, RateLimiter,
+};
+use std::net::SocketAddr;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+/// Token bucket per API client: per API key for one of `api_keys`, per IP address
+/// otherwise
+#[derive(Clone)]
+pub struct ClientRateLimit {
+    limiter: Arc<DefaultKeyedRateLimiter<String>>,
+    api_keys: Option<Arc<[String]>>,
+}
+
+impl ClientRateLimit {
+    /// `per_second` requests refill each bucket, which holds at most `burst`
+    pub fn new(per_second: NonZeroU32, burst: NonZeroU32, api_keys: Option<Arc<[String]>>) -> Self {
+        let quota = Quota::per_second(per_second).allow_burst(burst);
+        Self {
+            limiter: Arc::new(RateLimiter::keyed(quota)),
+            api_keys,
+        }
+    }
+
+    /// The limiter runs before `require_api_key`, so unknown keys share the bucket of
+    /// their address: made-up keys neither get a fresh bucket nor skip the limit
+    fn client(&self, request: &Request) -> String {
+        let api_key = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|provided| {
+                self.api_keys
+                    .as_ref()
+                    .is_some_and(|keys| is_known_key(keys, provided))
+            });
+        if let Some(api_key) = api_key {
+            return format!("key:{}", api_key);
+        }
+        match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".into(),
+        }
+    }
+
+    /// Forgets clients whose bucket filled up again, so memory doesn't grow with every
+    /// client ever seen
+    pub fn forget_idle_clients(&self) {
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+    }
+}
+
+/// Answers a `429` with a `Retry-After` to clients out of tokens
+pub async fn limit_clients(
+    State(limit): State<ClientRateLimit>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = limit.client(&request);
+    let Err(not_until) = limit.limiter.check_key(&client) else {
+        return next.run(request).await;
+    };
+
+    let wait = not_until.wait_time_from(DefaultClock::default().now());
+    let retry_after_secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let mut response = Error::RateLimited(format!(
+        "Too many requests, retry in {} seconds",
+        retry_after_secs
+    ))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{create_router, AppState};
+    use crate::config::AppConfig;
+    use crate::services::ethereum::EthereumService;
+    use crate::test_utils::{json_request, transfer_body, transfer_transport};
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::{header::RETRY_AFTER, Request, StatusCode};
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    fn router(api_keys: &[&str]) -> axum::Router {
+        let config = AppConfig {
+            rate_limit_per_second: 1,
+            rate_limit_burst: 2,
+            api_keys: api_keys.iter().map(|&key| key.into()).collect(),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transfer_transport().provider(), &config);
+        create_router(AppState::new(&config, service))
+    }
+
+    fn estimate(ip: [u8; 4], api_key: Option<&str>) -> Request<Body> {
+        let mut request = json_request("/api/v1/estimate-gas", transfer_body());
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((ip, 40_000))));
+        if let Some(api_key) = api_key {
+            request
+                .headers_mut()
+                .insert("x-api-key", api_key.parse().unwrap());
+        }
+        request
+    }
+
+    async fn status(app: &axum::Router, request: Request<Body>) -> StatusCode {
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_clients_limited_per_ip() {
+        let app = router(&[]);
+        let client = [203, 0, 113, 1];
+
+        // The burst, then nothing until the bucket refills
+        assert_eq!(status(&app, estimate(client, None)).await, StatusCode::OK);
+        assert_eq!(status(&app, estimate(client, None)).await, StatusCode::OK);
+        let response = app.clone().oneshot(estimate(client, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+
+        // Other clients have their own bucket
+        let other = [203, 0, 113, 2];
+        assert_eq!(status(&app, estimate(other, None)).await, StatusCode::OK);
+        // Without API keys configured, the header doesn't buy a fresh bucket
+        let made_up = estimate(client, Some("made-up"));
+        assert_eq!(status(&app, made_up).await, StatusCode::TOO_MANY_REQUESTS);
+
+        let health = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(&app, health).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_clients_limited_per_api_key() {
+        let app = router(&["first", "second"]);
+        let client = [203, 0, 113, 1];
+
+        for _ in 0..2 {
+            let request = estimate(client, Some("first"));
+            assert_eq!(status(&app, request).await, StatusCode::OK);
+        }
+        let request = estimate(client, Some("first"));
+        assert_eq!(status(&app, request).await, StatusCode::TOO_MANY_REQUESTS);
+        // Same address, another key
+        let request = estimate(client, Some("second"));
+        assert_eq!(status(&app, request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_api_keys_limited_per_ip() {
+        let app = router(&["first"]);
+        let client = [203, 0, 113, 1];
+
+        for key in ["guess-1", "guess-2"] {
+            let request = estimate(client, Some(key));
+            assert_eq!(status(&app, request).await, StatusCode::UNAUTHORIZED);
+        }
+        // Neither a new guess nor a missing key gets past the address's bucket
+        let request = estimate(client, Some("guess-3"));
+        assert_eq!(status(&app, request).await, StatusCode::TOO_MANY_REQUESTS);
+        let request = estimate(client, None);
+        assert_eq!(status(&app, request).await, StatusCode::TOO_MANY_REQUESTS);
+        // The valid key still has its own bucket
+        let request = estimate(client, Some("first"));
+        assert_eq!(status(&app, request).await, StatusCode::OK);
+    }
+}
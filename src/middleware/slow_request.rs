@@ -0,0 +1,99 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Logs a warning for every request taking longer than `threshold`
+pub async fn warn_slow_requests(
+    State(threshold): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_owned();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_owned();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+
+    if elapsed > threshold {
+        tracing::warn!(
+            path = %path,
+            duration_ms = elapsed.as_millis() as u64,
+            request_id = %request_id,
+            "Slow request"
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warns_only_for_slow_requests() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| tokio::time::sleep(Duration::from_millis(50))),
+            )
+            .route("/fast", get(|| async {}))
+            .layer(from_fn_with_state(
+                Duration::from_millis(20),
+                warn_slow_requests,
+            ));
+
+        let request = |uri| {
+            Request::builder()
+                .uri(uri)
+                .header(REQUEST_ID_HEADER, "req-42")
+                .body(Body::empty())
+                .unwrap()
+        };
+        app.clone().oneshot(request("/fast")).await.unwrap();
+        assert!(logs.0.lock().unwrap().is_empty());
+
+        app.oneshot(request("/slow")).await.unwrap();
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("WARN"));
+        assert!(output.contains("path=/slow"));
+        assert!(output.contains("request_id=req-42"));
+        assert!(output.contains("duration_ms="));
+    }
+}
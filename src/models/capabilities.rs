@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// What this deployment and the chain behind it support
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Capabilities {
+    pub chain_id: u64,
+    /// Whether the chain has a base fee, i.e. accepts EIP-1559 transactions
+    pub eip1559: bool,
+    /// `web3_clientVersion` of the node, `"unknown"` when it doesn't expose it
+    pub client_version: String,
+    pub features: Features,
+}
+
+/// Optional, config-gated features
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Features {
+    /// JWT-signed estimates with `Accept: application/jwt`
+    pub signed_responses: bool,
+    /// `node` or `binary_search`
+    pub gas_estimation_method: String,
+    pub gas_estimate_samples: usize,
+    pub strict_request_fields: bool,
+    pub request_schema_validation: bool,
+    /// `?include_rpc_stats=true` on estimation endpoints
+    pub rpc_stats: bool,
+    pub gzip_requests: bool,
+    /// Estimates and errors as CBOR with `Accept: application/cbor`
+    pub cbor_responses: bool,
+    pub maintenance_mode: bool,
+    /// `/api/v1/estimate-user-operation`, with a configured bundler
+    pub user_operations: bool,
+    /// `/graphql`, in builds with the `graphql` feature
+    pub graphql: bool,
+    /// `nested` or `flat`
+    pub error_format: String,
+    /// `string` or `number_when_safe`
+    pub number_encoding: String,
+    /// `/admin/cache` routes, with an admin token
+    pub admin: bool,
+    /// `tiers` in EIP-1559 estimates, on nodes serving `eth_feeHistory`
+    pub fee_tiers: bool,
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Current fee conditions, for clients without a transaction to estimate
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkFees {
+    /// Base fee of the latest block, `null` on chains without EIP-1559 like the two below
+    pub base_fee: Option<String>,
+    /// Median priority fee over the last 20 blocks
+    pub suggested_priority_fee: Option<String>,
+    /// Twice `base_fee` plus `suggested_priority_fee`
+    pub suggested_max_fee: Option<String>,
+    /// The node's `eth_gasPrice`
+    pub gas_price_legacy: String,
+}
+
+/// Fees expected to get a transaction included within `target_blocks` blocks
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeeForBlocks {
+    pub target_blocks: u64,
+    pub max_fee_per_gas: String,
+    pub max_priority_fee_per_gas: String,
+    /// Projected base fee of each of the next `target_blocks` blocks, starting with the pending one
+    pub projected_base_fees: Vec<String>,
+    /// Lowest and highest base fee the block after the pending one can have, reached
+    /// when the pending block is empty or full
+    pub next_base_fee_min: String,
+    pub next_base_fee_max: String,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Gas used by the recent calls of one contract method
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MethodGas {
+    pub to: String,
+    pub selector: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Number of matching transactions found in the range
+    pub transactions: usize,
+    /// `None` when no transaction matched
+    pub average_gas_used: Option<String>,
+    pub median_gas_used: Option<String>,
+}
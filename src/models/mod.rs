@@ -1 +1,23 @@
+pub mod capabilities;
+pub mod fees;
+pub mod history;
+pub mod schema;
 pub mod transaction;
+pub mod user_operation;
+
+use jsonschema::Validator;
+
+/// Field names a request body may carry
+pub trait KnownFields {
+    /// Fields besides those of a flattened transaction
+    const FIELDS: &'static [&'static str];
+    /// Whether the body is a `TransactionInput` with `FIELDS` added
+    const FLATTENS_TRANSACTION: bool = false;
+    /// Fields holding a `TransactionInput`, or a list of them
+    const NESTED_TRANSACTIONS: &'static [&'static str] = &[];
+
+    /// Schema the body must match with `REQUEST_SCHEMA_VALIDATION`, if one is published
+    fn schema() -> Option<&'static Validator> {
+        None
+    }
+}
@@ -0,0 +1,96 @@
+//! JSON Schemas of request bodies, published under `/api/v1/schema` and enforced with
+//! `REQUEST_SCHEMA_VALIDATION`. They follow the serde definitions in `transaction`.
+use crate::error::SchemaViolation;
+use jsonschema::Validator;
+use serde_json::{json, Value};
+
+/// Integers sent as strings, decimal or `0x` hex
+const INTEGER_PATTERN: &str = "^(0x[0-9a-fA-F]+|[0-9]+)$";
+/// Fees, which may be decimal with `fee_unit: "gwei"`
+const FEE_PATTERN: &str = "^(0x[0-9a-fA-F]+|[0-9]+(\\.[0-9]+)?)$";
+const ADDRESS_PATTERN: &str = "^0x[0-9a-fA-F]{40}$";
+
+lazy_static::lazy_static! {
+    pub(crate) static ref TRANSACTION_INPUT: Validator = compile(transaction_input());
+    pub(crate) static ref BATCH_INPUT: Validator = compile(batch_input());
+}
+
+/// Schema of the estimate-gas body, `TransactionInput`
+pub fn transaction_input() -> Value {
+    let integer = json!({ "type": "string", "pattern": INTEGER_PATTERN });
+    let fee = json!({ "type": "string", "pattern": FEE_PATTERN });
+    let nonce = json!({ "type": "integer", "minimum": 0 });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "TransactionInput",
+        "type": "object",
+        "required": ["from"],
+        "additionalProperties": false,
+        "properties": {
+            "from": { "type": "string", "pattern": ADDRESS_PATTERN },
+            // `null` for a contract creation, empty along with init code in `data`
+            "to": { "type": ["string", "null"], "pattern": "^(0x[0-9a-fA-F]{40})?$" },
+            "data": { "type": "string", "pattern": "^(0x)?([0-9a-fA-F]{2})*$" },
+            "value": integer,
+            "gas_price": fee,
+            "max_fee_per_gas": fee,
+            "max_priority_fee_per_gas": fee,
+            "nonce": nonce,
+            "_nonce": nonce,
+            "gas_search_cap": integer,
+            "fee_unit": { "enum": ["wei", "gwei"] },
+            "access_list": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["address", "storageKeys"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "address": { "type": "string", "pattern": ADDRESS_PATTERN },
+                        "storageKeys": {
+                            "type": "array",
+                            "items": { "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" }
+                        }
+                    }
+                }
+            },
+            "chain_id": integer,
+            "block": { "type": "string" }
+        }
+    })
+}
+
+/// Schema of the batch estimate-gas body, `BatchInput`
+pub fn batch_input() -> Value {
+    let mut transaction = transaction_input();
+    if let Some(schema) = transaction.as_object_mut() {
+        schema.remove("$schema");
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "BatchInput",
+        "type": "object",
+        "required": ["transactions"],
+        "additionalProperties": false,
+        "properties": {
+            "transactions": { "type": "array", "items": transaction }
+        }
+    })
+}
+
+fn compile(schema: Value) -> Validator {
+    jsonschema::validator_for(&schema).expect("Request schemas are valid")
+}
+
+/// Every way `body` doesn't match the schema of `validator`
+pub fn violations(validator: &Validator, body: &Value) -> Vec<SchemaViolation> {
+    validator
+        .iter_errors(body)
+        .map(|error| SchemaViolation {
+            path: error.instance_path.as_str().to_string(),
+            message: error.to_string(),
+        })
+        .collect()
+}
@@ -1,28 +1,568 @@
-use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::models::{schema, KnownFields};
+use crate::utils::format::parse_hex_or_decimal;
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_rpc_types::AccessList;
+use jsonschema::Validator;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TransactionInput {
     pub from: String,
-    pub to: String,
+    #[serde(default)]
+    pub to: Recipient,
     pub data: Option<String>,
     pub value: Option<String>,
     pub gas_price: Option<String>,
     pub max_fee_per_gas: Option<String>,
     pub max_priority_fee_per_gas: Option<String>,
-    pub _nonce: Option<u64>,
+    /// Sets the nonce of the estimated transaction, e.g. to estimate one queued behind
+    /// pending transactions of the sender
+    #[serde(alias = "_nonce")]
+    pub nonce: Option<u64>,
+    /// Highest gas the node may try while estimating, passed as the estimate's `gas`
+    pub gas_search_cap: Option<String>,
+    /// Unit of `gas_price`, `max_fee_per_gas` and `max_priority_fee_per_gas`
+    #[serde(default)]
+    pub fee_unit: FeeUnit,
+    /// EIP-2930 addresses and storage keys to warm up, `[{"address", "storageKeys"}]`
+    pub access_list: Option<AccessList>,
+    /// Chain the client is assembling the transaction for, compared with the node's
+    pub chain_id: Option<String>,
+    /// Block whose state to estimate against: `latest` (default), `pending` or a number
+    pub block: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "lowercase")]
+pub enum FeeUnit {
+    #[default]
+    Wei,
+    /// Decimal amounts allowed, down to the wei
+    Gwei,
+}
+
+impl TransactionInput {
+    /// Checks every field parses, naming the first one that doesn't. Fees must already
+    /// be in wei.
+    pub fn validate(&self) -> Result<()> {
+        fn invalid(field: &str, expected: &str, value: &str) -> Error {
+            Error::InvalidInput(format!(
+                "Invalid '{}': expected {}, got {:?}",
+                field, expected, value
+            ))
+        }
+        let address = |field: &str, value: &str| {
+            Address::from_str(value)
+                .map(drop)
+                .map_err(|_| invalid(field, "a 20-byte hex address", value))
+        };
+
+        address("from", &self.from)?;
+        if let Recipient::Address(to) = &self.to {
+            if !to.is_empty() {
+                address("to", to)?;
+            }
+        }
+        if let Some(data) = &self.data {
+            Bytes::from_str(data).map_err(|_| invalid("data", "hex bytes", data))?;
+        }
+        if let Some(value) = &self.value {
+            U256::from_str(value).map_err(|_| invalid("value", "a 256-bit integer", value))?;
+        }
+        for (field, fee) in [
+            ("gas_price", &self.gas_price),
+            ("max_fee_per_gas", &self.max_fee_per_gas),
+            ("max_priority_fee_per_gas", &self.max_priority_fee_per_gas),
+        ] {
+            if let Some(fee) = fee {
+                parse_hex_or_decimal::<u128>(fee)
+                    .ok_or_else(|| invalid(field, "an integer amount of wei", fee))?;
+            }
+        }
+        if let Some(cap) = &self.gas_search_cap {
+            parse_hex_or_decimal::<u64>(cap)
+                .ok_or_else(|| invalid("gas_search_cap", "a 64-bit integer", cap))?;
+        }
+        if let Some(chain_id) = &self.chain_id {
+            parse_hex_or_decimal::<u64>(chain_id)
+                .ok_or_else(|| invalid("chain_id", "a 64-bit integer", chain_id))?;
+        }
+        Ok(())
+    }
+
+    /// `"to": null`, or a missing or empty `to` with init code in `data`
+    pub fn is_contract_creation(&self) -> bool {
+        let has_data = self
+            .data
+            .as_deref()
+            .is_some_and(|data| !data.is_empty() && data != "0x");
+        match &self.to {
+            Recipient::Create => true,
+            Recipient::Missing => has_data,
+            Recipient::Address(to) => to.is_empty() && has_data,
+        }
+    }
+}
+
+/// The `to` field as sent by the client, where a missing field and `null` mean different things
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Recipient {
+    /// No `to` field at all, rejected unless `data` is present
+    #[default]
+    Missing,
+    /// `"to": null`, a contract creation
+    Create,
+    Address(String),
+}
+
+impl<'de> Deserialize<'de> for Recipient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(match Option::<String>::deserialize(deserializer)? {
+            Some(address) => Self::Address(address),
+            None => Self::Create,
+        })
+    }
+}
+
+impl From<&str> for Recipient {
+    fn from(address: &str) -> Self {
+        Self::Address(address.to_string())
+    }
+}
+
+impl KnownFields for TransactionInput {
+    const FIELDS: &'static [&'static str] = &[
+        "from",
+        "to",
+        "data",
+        "value",
+        "gas_price",
+        "max_fee_per_gas",
+        "max_priority_fee_per_gas",
+        "nonce",
+        "_nonce",
+        "gas_search_cap",
+        "fee_unit",
+        "access_list",
+        "chain_id",
+        "block",
+    ];
+
+    fn schema() -> Option<&'static Validator> {
+        Some(&schema::TRANSACTION_INPUT)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct GasEstimation {
+    /// Fingerprint of the normalized transaction, the block estimated at, the gas limit
+    /// and the gas price: identical for the same request at the same block
+    #[serde(default)]
+    pub estimate_id: String,
+    /// State the gas limit was estimated against: `latest`, `pending` or a hex block number
+    pub block: String,
+    /// `gas_limit_raw` plus `GAS_LIMIT_BUFFER_PERCENT`, the limit to send the transaction with
     pub gas_limit: String,
+    /// Gas the node estimated, without headroom
+    pub gas_limit_raw: String,
+    /// Part of `gas_limit` charged before execution: 21000, calldata and contract creation
+    #[serde(default)]
+    pub intrinsic_gas: String,
+    /// `gas_limit_raw` minus `intrinsic_gas`
+    #[serde(default)]
+    pub execution_gas: String,
     pub gas_price: String,
+    #[serde(default)]
+    pub gas_price_gwei: String,
     pub estimated_cost_wei: String,
+    #[serde(default)]
+    pub estimated_cost_gwei: String,
     pub estimated_cost_eth: String,
+    /// Only with a `PRICE_FEED_URL` that answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<String>,
     pub estimated_execution_time: Option<String>,
+    /// Set when the fee offered is below the current base fee
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub may_never_be_included: bool,
+    /// Only with `GAS_ESTIMATE_SAMPLES` above 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
     pub type_of_transaction: String,
+    /// How the cost was priced: `eip1559` only for type-2 transactions on chains with a
+    /// base fee, `legacy` otherwise
+    #[serde(default)]
+    pub pricing_mechanism: String,
+    #[serde(default)]
+    pub is_contract_creation: bool,
+    /// `eth_gasPrice` as reported by the node, only set on chains that also have a base fee
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_gas_price: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<String>,
+    /// Fee fields to build a type-2 transaction with, `null` for legacy transactions
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<String>,
+    #[serde(default)]
+    pub max_fee_per_gas: Option<String>,
+    /// RPC calls made for this estimate, only with `?include_rpc_stats=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub rpc_calls: Option<Vec<RpcCallStat>>,
+    /// Likely mistakes in the request, e.g. an excessive priority fee
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Suggested fees by inclusion speed, only for EIP-1559 transactions on nodes
+    /// serving `eth_feeHistory`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tiers: Option<FeeTiers>,
+    /// Only with `?optimize=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub access_list_optimization: Option<AccessListOptimization>,
+    /// Only with `?access_list_impact=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub access_list_impact: Option<AccessListImpact>,
+    /// Gas refunded for clearing storage (EIP-3529), already deducted from `gas_limit`.
+    /// Only with `?include_refund=true` on nodes allowing `debug_traceCall`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_refund: Option<String>,
+    /// Only on OP-stack chains, for transactions with calldata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub calldata_analysis: Option<CalldataAnalysis>,
+    /// Confirmations to wait for before treating a value transfer as settled, from
+    /// `CONFIRMATION_THRESHOLDS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_confirmations: Option<u64>,
+    /// The request's `nonce`, when one was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    /// Set when `max_latency_ms` ran out: the estimate was made without calling the node
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub approximate: bool,
+}
+
+/// Access list from `eth_createAccessList` and what it does to the gas limit. The
+/// estimate uses it only when it saves gas.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccessListOptimization {
+    pub access_list: AccessList,
+    pub gas_without_access_list: String,
+    pub gas_with_access_list: String,
+    /// Zero when the access list doesn't help
+    pub saved_gas: String,
+    /// Whether the estimate includes the access list
+    pub applied: bool,
 }
 
+/// What the request's own access list does to the gas limit. Signed amounts: a slot
+/// warmed for nothing costs more than it saves.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccessListImpact {
+    pub gas_with_access_list: String,
+    pub gas_without_access_list: String,
+    pub saved_gas: String,
+    /// Inferred by estimating without each slot in turn, left out for long lists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots: Option<Vec<SlotImpact>>,
+}
+
+/// Gas saved by warming one storage slot, the difference between estimates with and
+/// without it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SlotImpact {
+    pub address: String,
+    pub storage_key: String,
+    pub saved_gas: String,
+}
+
+/// Byte composition of the calldata and its L1 cost, on OP-stack chains where the L1
+/// data fee usually dominates
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CalldataAnalysis {
+    pub zero_bytes: u64,
+    pub non_zero_bytes: u64,
+    /// L1 gas of the calldata before compression: 4 per zero byte, 16 per other byte
+    pub l1_data_gas: String,
+    /// Bytes in runs of 4 or more identical bytes, mostly ABI padding
+    pub repetitive_bytes: u64,
+    pub compressibility: Compressibility,
+    /// How the calldata could be laid out to lower the L1 data fee
+    pub hint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compressibility {
+    Low,
+    Medium,
+    High,
+}
+
+/// Fees from the 25th, 50th and 90th percentile of recent priority fees
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct FeeTiers {
+    pub slow: FeeTier,
+    pub average: FeeTier,
+    pub fast: FeeTier,
+}
+
+/// Fees of a tier, with the worst-case cost of paying the full `max_fee_per_gas`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct FeeTier {
+    pub max_fee_per_gas: String,
+    pub max_priority_fee_per_gas: String,
+    pub estimated_cost_wei: String,
+    pub estimated_cost_eth: String,
+}
+
+/// A JSON-RPC call made to the node while serving a request
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RpcCallStat {
+    pub method: String,
+    pub duration_ms: u64,
+}
+
+/// Pending transaction to speed up or cancel, identified by its sender and nonce
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReplacementInput {
+    #[serde(flatten)]
+    pub transaction: TransactionInput,
+    pub nonce: u64,
+}
+
+impl KnownFields for ReplacementInput {
+    const FIELDS: &'static [&'static str] = &[];
+    const FLATTENS_TRANSACTION: bool = true;
+}
+
+/// Transaction to check against a maximum total fee
+#[derive(Debug, Deserialize, Clone)]
+pub struct BudgetInput {
+    #[serde(flatten)]
+    pub transaction: TransactionInput,
+    pub max_budget_wei: String,
+}
+
+impl KnownFields for BudgetInput {
+    const FIELDS: &'static [&'static str] = &["max_budget_wei"];
+    const FLATTENS_TRANSACTION: bool = true;
+}
+
+/// Whether a transaction's fees fit in a budget, and the highest fee per gas that does
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetEstimation {
+    pub gas_limit: String,
+    pub gas_price: String,
+    pub estimated_cost_wei: String,
+    pub max_budget_wei: String,
+    pub within_budget: bool,
+    pub max_fee_per_gas_within_budget: String,
+}
+
+/// Transaction to price at every priority fee from `priority_fee_from` to
+/// `priority_fee_to` in increments of `priority_fee_step`, all in wei
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeeSweepInput {
+    #[serde(flatten)]
+    pub transaction: TransactionInput,
+    pub priority_fee_from: String,
+    pub priority_fee_to: String,
+    pub priority_fee_step: String,
+}
+
+impl KnownFields for FeeSweepInput {
+    const FIELDS: &'static [&'static str] =
+        &["priority_fee_from", "priority_fee_to", "priority_fee_step"];
+    const FLATTENS_TRANSACTION: bool = true;
+}
+
+/// Inclusion outlook of a transaction over a range of priority fees
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeeSweep {
+    pub gas_limit: String,
+    /// Base fee of the pending block
+    pub base_fee_per_gas: String,
+    pub points: Vec<FeeSweepPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeeSweepPoint {
+    pub max_priority_fee_per_gas: String,
+    pub max_fee_per_gas: String,
+    /// Share of recent blocks whose median priority fee this fee meets
+    pub inclusion_probability: f64,
+    /// Blocks expected until inclusion, `null` when no recent block would have included it
+    pub expected_blocks: Option<u64>,
+    /// Cost at the pending base fee plus the priority fee
+    pub estimated_cost_wei: String,
+}
+
+/// Read-only call to execute, at `block` (a number or tag, `latest` when omitted)
+#[derive(Debug, Deserialize, Clone)]
+pub struct CallInput {
+    #[serde(flatten)]
+    pub transaction: TransactionInput,
+}
+
+impl KnownFields for CallInput {
+    const FIELDS: &'static [&'static str] = &[];
+    const FLATTENS_TRANSACTION: bool = true;
+}
+
+/// Output of an `eth_call`. `gas_estimate` is the node's `eth_estimateGas` at the same
+/// block, not the gas the call used, and is omitted when the node can't provide it there.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CallResult {
+    pub return_data: String,
+    pub block: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_estimate: Option<String>,
+}
+
+/// Two versions of a transaction to compare
+#[derive(Debug, Deserialize, Clone)]
+pub struct DiffInput {
+    pub baseline: TransactionInput,
+    pub candidate: TransactionInput,
+}
+
+impl KnownFields for DiffInput {
+    const FIELDS: &'static [&'static str] = &["baseline", "candidate"];
+    const NESTED_TRANSACTIONS: &'static [&'static str] = &["baseline", "candidate"];
+}
+
+/// Transactions to estimate independently of each other
+#[derive(Debug, Deserialize, Clone)]
+pub struct BatchInput {
+    pub transactions: Vec<TransactionInput>,
+}
+
+impl KnownFields for BatchInput {
+    const FIELDS: &'static [&'static str] = &["transactions"];
+    const NESTED_TRANSACTIONS: &'static [&'static str] = &["transactions"];
+
+    fn schema() -> Option<&'static Validator> {
+        Some(&schema::BATCH_INPUT)
+    }
+}
+
+/// Estimates of a batch, with what sending them as a single multicall would save
+#[derive(Debug, Serialize, Clone)]
+pub struct BatchEstimation {
+    pub results: Vec<BatchItem>,
+    pub batching_savings: BatchSavings,
+}
+
+/// Base transaction gas saved by bundling the estimated transactions of a batch into
+/// one multicall transaction. Rough: the multicall's own call overhead is not counted.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct BatchSavings {
+    /// Transactions that could be estimated, the ones counted
+    pub transactions: usize,
+    /// 21000 per transaction sent separately
+    pub separate_base_gas: String,
+    /// 21000 for the one multicall transaction
+    pub batched_base_gas: String,
+    pub saved_gas: String,
+}
+
+/// Result for one transaction of a batch, in request order
 #[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum BatchItem {
+    Estimation(Box<GasEstimation>),
+    Error { error: BatchError },
+}
+
+/// Why a transaction of a batch couldn't be estimated, shaped like error responses
+#[derive(Debug, Serialize, Clone)]
+pub struct BatchError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: String,
+}
+
+/// Estimate of one side of a diff, or why it failed (e.g. a revert)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffSide {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimation: Option<GasEstimation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Both estimates, with `candidate - baseline` deltas when both succeeded
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EstimateDiff {
+    pub baseline: DiffSide,
+    pub candidate: DiffSide,
+    pub gas_delta: Option<String>,
+    pub cost_delta_wei: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplacementEstimation {
+    pub nonce: u64,
+    pub base_fee_per_gas: Option<String>,
+    pub speed_up: ReplacementOption,
+    pub cancel: ReplacementOption,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_calls: Option<Vec<RpcCallStat>>,
+}
+
+/// Minimum fees accepted by the mempool for a replacement, with its worst-case cost
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplacementOption {
+    pub gas_limit: String,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub estimated_cost_wei: String,
+    pub estimated_cost_eth: String,
+}
+
+/// How consistent repeated gas estimates of a transaction were
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// Samples within 1% of each other
+    High,
+    /// Samples within 10% of each other
+    Medium,
+    /// Gas use depends heavily on state, a larger buffer is advisable
+    Low,
+}
+
+impl Confidence {
+    /// Classifies the spread of the samples relative to the largest one, `None` for a
+    /// single sample
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.len() < 2 {
+            return None;
+        }
+        let max = *samples.iter().max()?;
+        let min = *samples.iter().min()?;
+        let spread_bps = u128::from(max - min) * 10_000 / u128::from(max.max(1));
+
+        Some(match spread_bps {
+            0..=100 => Self::High,
+            101..=1_000 => Self::Medium,
+            _ => Self::Low,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
 pub enum TransactionType {
     Legacy,
     EIP1559,
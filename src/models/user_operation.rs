@@ -0,0 +1,63 @@
+use crate::error::{Error, Result};
+use crate::models::KnownFields;
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// Fields every UserOperation carries, whatever the EntryPoint version
+const REQUIRED_FIELDS: [&str; 3] = ["sender", "nonce", "callData"];
+
+/// An ERC-4337 UserOperation to estimate through the bundler
+#[derive(Debug, Deserialize, Clone)]
+pub struct UserOperationInput {
+    /// The operation as bundlers take it: camelCase fields of EntryPoint v0.6
+    /// (`initCode`, `paymasterAndData`) or v0.7 (`factory`, `paymaster`...), gas
+    /// fields may be left out
+    pub user_operation: Map<String, Value>,
+    /// EntryPoint the operation is for, `ENTRY_POINT_ADDRESS` by default
+    pub entry_point: Option<String>,
+}
+
+impl UserOperationInput {
+    /// Checks the fields every operation needs are there and the addresses parse
+    pub fn validate(&self) -> Result<Option<Address>> {
+        for field in REQUIRED_FIELDS {
+            if !self.user_operation.contains_key(field) {
+                return Err(Error::InvalidInput(format!(
+                    "Missing '{}' in user_operation",
+                    field
+                )));
+            }
+        }
+        let address = |field: &str, value: Option<&str>| {
+            Address::from_str(value.unwrap_or_default()).map_err(|_| {
+                Error::InvalidInput(format!(
+                    "Invalid '{}': expected a 20-byte hex address",
+                    field
+                ))
+            })
+        };
+        address("sender", self.user_operation["sender"].as_str())?;
+        self.entry_point
+            .as_deref()
+            .map(|entry_point| address("entry_point", Some(entry_point)))
+            .transpose()
+    }
+}
+
+impl KnownFields for UserOperationInput {
+    const FIELDS: &'static [&'static str] = &["user_operation", "entry_point"];
+}
+
+/// Gas limits of a UserOperation as estimated by the bundler
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct UserOperationGasEstimation {
+    /// Gas of the inner call to the account
+    pub call_gas_limit: String,
+    /// Gas of account creation, validation and paymaster validation
+    pub verification_gas_limit: String,
+    /// Gas paid to the bundler for the calldata and overhead of its bundle transaction
+    pub pre_verification_gas: String,
+    pub entry_point: String,
+}
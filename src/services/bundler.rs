@@ -0,0 +1,77 @@
+use alloy_json_rpc::RpcError;
+use alloy_primitives::Address;
+use alloy_provider::{Provider, RootProvider};
+use alloy_rpc_client::ClientBuilder;
+use serde_json::{Map, Value};
+use std::time::Duration;
+use url::Url;
+
+use crate::{
+    error::{Error, Result},
+    models::user_operation::{UserOperationGasEstimation, UserOperationInput},
+    utils::{format::parse_hex_or_decimal, rpc_timeout::RpcTimeoutLayer},
+};
+
+/// ERC-4337 bundler estimating UserOperations with `eth_estimateUserOperationGas`
+#[derive(Clone)]
+pub struct Bundler {
+    provider: RootProvider,
+    entry_point: Address,
+}
+
+impl Bundler {
+    pub fn new(url: Url, entry_point: Address, timeout: Duration) -> Self {
+        let client = ClientBuilder::default()
+            .layer(RpcTimeoutLayer::new(timeout))
+            .http(url);
+        Self::with_provider(RootProvider::new(client), entry_point)
+    }
+
+    /// Bundler behind an already built provider
+    pub fn with_provider(provider: RootProvider, entry_point: Address) -> Self {
+        Self {
+            provider,
+            entry_point,
+        }
+    }
+
+    pub async fn estimate_user_operation(
+        &self,
+        input: UserOperationInput,
+    ) -> Result<UserOperationGasEstimation> {
+        let entry_point = input.validate()?.unwrap_or(self.entry_point);
+
+        let estimate: Map<String, Value> = self
+            .provider
+            .raw_request(
+                "eth_estimateUserOperationGas".into(),
+                (input.user_operation, entry_point),
+            )
+            .await
+            .map_err(|e| match e {
+                // AA-coded failures of the simulation, e.g. `AA21 didn't pay prefund`
+                RpcError::ErrorResp(payload) => Error::GasEstimation(
+                    format!("Bundler rejected the user operation: {}", payload.message).into(),
+                ),
+                e => e.into(),
+            })?;
+
+        let gas = |field: &str| {
+            let value = estimate.get(field);
+            let gas = match value {
+                Some(Value::String(gas)) => parse_hex_or_decimal::<u64>(gas),
+                Some(Value::Number(gas)) => gas.as_u64(),
+                _ => None,
+            };
+            gas.map(|gas| gas.to_string()).ok_or_else(|| {
+                Error::Provider(format!("Bundler returned no valid {}: {:?}", field, value))
+            })
+        };
+        Ok(UserOperationGasEstimation {
+            call_gas_limit: gas("callGasLimit")?,
+            verification_gas_limit: gas("verificationGasLimit")?,
+            pre_verification_gas: gas("preVerificationGas")?,
+            entry_point: entry_point.to_checksum(None),
+        })
+    }
+}
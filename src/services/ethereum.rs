@@ -1,138 +1,1701 @@
-use alloy_primitives::{utils::format_ether, Address, Bytes, U256};
-use alloy_provider::{Provider, RootProvider};
-use alloy_rpc_types::{TransactionInput as TxData, TransactionRequest};
+use alloy_primitives::{keccak256, Address, Bytes, TxKind, U256};
+use alloy_provider::{network::TransactionResponse, Provider, RootProvider};
+use alloy_rpc_client::ClientBuilder;
+use alloy_rpc_types::{
+    AccessList, BlockNumberOrTag, BlockTransactionsKind, FeeHistory, Header,
+    TransactionInput as TxData, TransactionRequest, TransactionTrait,
+};
+use alloy_transport::{utils::guess_local_url, Transport};
+use alloy_transport_http::Http;
+use futures::future::try_join_all;
+use std::collections::HashMap;
 use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::Instant;
+use tower::Layer;
 
 use crate::{
-    config::AppConfig,
+    config::{
+        AppConfig, ConfirmationTable, DefaultTxType, GasEstimationMethod, GasSampleAggregation,
+    },
     error::{Error, Result},
-    models::transaction::{GasEstimation, TransactionInput, TransactionType},
-    utils::cache::cached_gas_price,
+    models::fees::{FeeForBlocks, NetworkFees},
+    models::history::MethodGas,
+    models::transaction::{
+        AccessListImpact, AccessListOptimization, BudgetEstimation, BudgetInput, CallInput,
+        CallResult, CalldataAnalysis, Confidence, DiffInput, DiffSide, EstimateDiff, FeeSweep,
+        FeeSweepInput, FeeSweepPoint, FeeTier, FeeTiers, GasEstimation, Recipient,
+        ReplacementEstimation, ReplacementInput, ReplacementOption, SlotImpact, TransactionInput,
+        TransactionType,
+    },
+    services::l2::{analyze_calldata, is_op_stack},
+    services::price_feed::{format_usd, PriceFeed},
+    utils::{
+        cache::{bypass_cache, GasPriceCache},
+        failover::Failover,
+        format::{format_address, parse_hex_or_decimal, CostFormat},
+        retry::RetryLayer,
+        rpc::{is_execution_failure, is_method_not_found, recover_quantity},
+        rpc_limit::{AdaptiveLimitLayer, AdaptiveLimiter},
+        rpc_stats::RpcStatsLayer,
+        rpc_timeout::RpcTimeoutLayer,
+    },
 };
 
-const DEFAULT_PRIORITY_FEE: u128 = 1_500_000_000;
+/// Gas used by a plain value transfer, which is what a cancellation is. Also the base
+/// cost every transaction pays.
+pub const TRANSFER_GAS: u64 = 21_000;
+/// Minimum fee increase (in percent) nodes require to replace a pending transaction
+const REPLACEMENT_BUMP_PERCENT: u128 = 10;
+/// Blocks of fee history the base fee projection is based on
+const FEE_HISTORY_BLOCKS: u64 = 20;
+/// How long to trust a node's answer that it doesn't implement `eth_feeHistory`, in
+/// case it gets upgraded or a failover node does
+const FEE_HISTORY_REPROBE_INTERVAL: Duration = Duration::from_secs(600);
+/// Reward percentiles behind the slow, average and fast fee tiers
+const TIER_PERCENTILES: [f64; 3] = [25.0, 50.0, 90.0];
+/// Extra intrinsic gas of a contract creation (EIP-2)
+const CREATE_GAS: u64 = 32_000;
+/// Intrinsic gas per zero and non-zero calldata byte (EIP-2028)
+pub(crate) const ZERO_BYTE_GAS: u64 = 4;
+pub(crate) const NON_ZERO_BYTE_GAS: u64 = 16;
+/// Intrinsic gas per 32-byte word of init code (EIP-3860)
+const INIT_CODE_WORD_GAS: u64 = 2;
+/// Longest access list whose slots are each estimated with `?access_list_impact=true`,
+/// one extra `eth_estimateGas` per slot
+const MAX_IMPACT_SLOTS: usize = 16;
+/// Intrinsic gas per access list address and storage key (EIP-2930)
+const ACCESS_LIST_ADDRESS_GAS: u64 = 2_400;
+const ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1_900;
+/// Refunds are capped at this fraction of the gas used (EIP-3529)
+const MAX_REFUND_QUOTIENT: u64 = 5;
+/// Largest base fee change per block is 1/8 (EIP-1559)
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u128 = 8;
+/// Client version of nodes that don't implement `web3_clientVersion`
+const UNKNOWN_CLIENT_VERSION: &str = "unknown";
+
+/// Method gas results of the latest block scanned, keyed by (to, selector, blocks)
+type MethodGasCache = HashMap<(Address, Bytes, u64), (u64, MethodGas)>;
 
 #[derive(Clone)]
 pub struct EthereumService {
     provider: Arc<RootProvider>,
-    cache_duration: Duration,
+    price_cache: GasPriceCache,
+    estimation_method: GasEstimationMethod,
+    gas_estimate_samples: usize,
+    gas_sample_aggregation: GasSampleAggregation,
+    cost_format: CostFormat,
+    max_execution_time: Duration,
+    legacy_execution_time: Duration,
+    eip1559_execution_time: Duration,
+    default_priority_fee: u128,
+    default_tx_type: DefaultTxType,
+    max_calldata_bytes: usize,
+    chain_id: Arc<OnceCell<u64>>,
+    eip1559: Arc<OnceCell<bool>>,
+    client_version: Arc<OnceCell<String>>,
+    /// When the node last answered that it doesn't implement `eth_feeHistory`
+    fee_history_unsupported: Arc<std::sync::Mutex<Option<Instant>>>,
+    method_gas_cache: Arc<Mutex<MethodGasCache>>,
+    priority_fee_warning_multiple: f64,
+    checksum_output: bool,
+    gas_limit_buffer_percent: u64,
+    confirmation_thresholds: ConfirmationTable,
+    price_feed: Option<PriceFeed>,
 }
 
 impl EthereumService {
     pub async fn new(config: &AppConfig) -> Result<Self> {
-        let url = config.ethereum_rpc_url.parse().map_err(|e| {
-            Error::Config(format!(
-                "Not valid URL '{}': {:?}",
-                config.ethereum_rpc_url, e
-            ))
-        })?;
-        let provider = RootProvider::new_http(url);
-
-        provider
-            .get_block_number()
-            .await
-            .map_err(|e| Error::Provider(format!("Failed to connect to Ethereum node: {}", e)))?;
+        let transports = config
+            .ethereum_rpc_urls
+            .iter()
+            .map(|url| {
+                let parsed = url
+                    .parse()
+                    .map_err(|e| Error::Config(format!("Not valid URL '{}': {:?}", url, e)))?;
+                // Per node, so a node that hangs fails over like one that's down
+                Ok(RpcTimeoutLayer::new(config.rpc_timeout)
+                    .layer(Http::new(parsed))
+                    .boxed())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if transports.is_empty() {
+            return Err(Error::Config("No Ethereum RPC URLs provided".into()));
+        }
+        let is_local = config.ethereum_rpc_urls.iter().all(guess_local_url);
+        let provider = RootProvider::new(
+            ClientBuilder::default()
+                .layer(AdaptiveLimitLayer::new(AdaptiveLimiter::new(
+                    config.rpc_max_concurrency,
+                )))
+                .layer(RpcStatsLayer)
+                .layer(RetryLayer::new(
+                    config.rpc_max_retries,
+                    config.rpc_retry_base_delay,
+                ))
+                .transport(Failover::new(transports), is_local),
+        );
+
+        Self::connect(provider, config).await
+    }
+
+    /// Checks a node answers before building the service, retrying with exponential
+    /// backoff while it may still be starting up. Each attempt goes through every
+    /// configured node until one answers. With a `CHAIN_ID`, the node must also be on
+    /// that chain.
+    pub async fn connect(provider: RootProvider, config: &AppConfig) -> Result<Self> {
+        let mut delay = config.startup_retry_delay;
+        let mut attempt = 0;
 
-        Ok(Self {
+        loop {
+            match recover_quantity(provider.get_block_number().await) {
+                Ok(_) => {
+                    let service = Self::with_provider(provider, config);
+                    if let Some(expected) = config.chain_id {
+                        service.check_chain_id(expected).await?;
+                    }
+                    return Ok(service);
+                }
+                Err(e) if attempt < config.startup_retry_attempts => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Ethereum node not reachable ({}), retry {}/{} in {:?}",
+                        e,
+                        attempt,
+                        config.startup_retry_attempts,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                }
+                Err(e) => {
+                    return Err(Error::Provider(format!(
+                        "Failed to connect to Ethereum node: {}",
+                        e
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Builds the service around an already connected provider
+    pub fn with_provider(provider: RootProvider, config: &AppConfig) -> Self {
+        Self {
             provider: Arc::new(provider),
-            cache_duration: config.cache_duration,
-        })
+            price_cache: GasPriceCache::new(config.cache_duration),
+            estimation_method: config.gas_estimation_method,
+            gas_estimate_samples: config.gas_estimate_samples.max(1),
+            gas_sample_aggregation: config.gas_sample_aggregation,
+            cost_format: config.cost_format,
+            max_execution_time: config.max_execution_time,
+            legacy_execution_time: config.legacy_execution_time,
+            eip1559_execution_time: config.eip1559_execution_time,
+            default_priority_fee: config.default_priority_fee,
+            default_tx_type: config.default_tx_type,
+            max_calldata_bytes: config.max_calldata_bytes,
+            chain_id: Arc::new(OnceCell::new()),
+            eip1559: Arc::new(OnceCell::new()),
+            client_version: Arc::new(OnceCell::new()),
+            fee_history_unsupported: Arc::default(),
+            method_gas_cache: Arc::default(),
+            priority_fee_warning_multiple: config.priority_fee_warning_multiple,
+            checksum_output: config.checksum_output,
+            gas_limit_buffer_percent: config.gas_limit_buffer_percent,
+            confirmation_thresholds: config.confirmation_thresholds.clone(),
+            price_feed: config
+                .price_feed_url
+                .clone()
+                .map(|url| PriceFeed::new(url, config.rpc_timeout)),
+        }
+    }
+
+    /// Cache of this service's gas price, not shared with other services
+    pub fn price_cache(&self) -> &GasPriceCache {
+        &self.price_cache
+    }
+
+    /// False from the node answering that it doesn't implement `eth_feeHistory` until
+    /// it is asked again
+    pub fn serves_fee_history(&self) -> bool {
+        self.fee_history_unsupported.lock().unwrap().is_none()
+    }
+
+    /// Drops cached gas prices and method gas, and forgets that the node doesn't
+    /// implement `eth_feeHistory`. Returns how many of those were cleared.
+    pub async fn clear_caches(&self) -> usize {
+        let prices = self.price_cache.clear().await;
+        let method_gas = std::mem::take(&mut *self.method_gas_cache.lock().await).len();
+        let fee_history = self.fee_history_unsupported.lock().unwrap().take();
+        prices + method_gas + usize::from(fee_history.is_some())
+    }
+
+    /// ETH/USD price in millionths of a dollar, `None` without a reachable price feed
+    async fn eth_usd_price(&self) -> Option<u128> {
+        match &self.price_feed {
+            Some(feed) => feed.try_eth_usd_micros(&self.price_cache).await,
+            None => None,
+        }
+    }
+
+    /// Chain id of the connected node, fetched once
+    pub async fn chain_id(&self) -> Result<u64> {
+        self.chain_id
+            .get_or_try_init(|| async {
+                recover_quantity(self.provider.get_chain_id().await).map_err(Error::from)
+            })
+            .await
+            .copied()
+    }
+
+    /// Fails when the node isn't on chain `expected`
+    async fn check_chain_id(&self, expected: u64) -> Result<()> {
+        let chain_id = self.chain_id().await?;
+        if chain_id != expected {
+            return Err(Error::Config(format!(
+                "RPC node is on chain {}, CHAIN_ID is {}",
+                chain_id, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the chain has a base fee, probed once
+    pub async fn supports_eip1559(&self) -> Result<bool> {
+        self.eip1559
+            .get_or_try_init(|| async { Ok(self.get_base_fee().await?.is_some()) })
+            .await
+            .copied()
+    }
+
+    /// Node client and version from `web3_clientVersion`, e.g. `Geth/v1.14.0-stable/...`,
+    /// fetched once. `"unknown"` when the node doesn't expose it; other failures are
+    /// retried on the next call.
+    pub async fn client_version(&self) -> String {
+        let version = self
+            .client_version
+            .get_or_try_init(|| async {
+                match self.provider.get_client_version().await {
+                    Ok(version) => Ok(version),
+                    Err(e) if is_method_not_found(&e) => Ok(UNKNOWN_CLIENT_VERSION.into()),
+                    Err(e) => Err(e),
+                }
+            })
+            .await;
+        match version {
+            Ok(version) => version.clone(),
+            Err(e) => {
+                tracing::warn!("Failed to fetch the node client version: {}", e);
+                UNKNOWN_CLIENT_VERSION.into()
+            }
+        }
     }
 
     pub async fn estimate_gas(&self, tx: TransactionInput) -> Result<GasEstimation> {
         let transaction = self.build_transaction_request(&tx)?;
-        let tx_type = self.determine_transaction_type(&tx);
+        let block = parse_block(tx.block.as_deref())?;
+        let tx_type = self.determine_transaction_type(&tx).await?;
 
-        let (gas_price, gas_limit) = tokio::join!(
-            self.get_gas_price(tx_type.clone(), &tx),
-            self.provider.estimate_gas(&transaction)
+        let tier_history = async {
+            match tx_type {
+                TransactionType::EIP1559 => self.fee_history(&TIER_PERCENTILES).await,
+                TransactionType::Legacy => Ok(None),
+            }
+        };
+        let (gas_limit, header, network_gas_price, eth_usd, chain_id, tier_history) = tokio::join!(
+            self.estimate_gas_limit_with_confidence(&transaction, block),
+            self.get_latest_header(),
+            self.gas_price(self.price_cache.ttl()),
+            self.eth_usd_price(),
+            self.chain_id(),
+            tier_history
         );
 
-        let gas_price = gas_price?;
-        let gas_limit = gas_limit.map_err(Error::from)?;
+        let (raw_gas_limit, confidence) = gas_limit?;
+        let header = header?;
+        check_block_gas_limit(raw_gas_limit, &header)?;
+        let gas_limit = self.buffered_gas_limit(raw_gas_limit, header.gas_limit);
+        let intrinsic_gas = intrinsic_gas(&transaction);
+        let base_fee = header.base_fee_per_gas.map(u128::from);
+        // Pricing only falls back on the node's price without a gas price from the
+        // client or a base fee. Otherwise it's just reported, when the node gave one.
+        let needs_network_price = match tx_type {
+            TransactionType::Legacy => tx.gas_price.is_none(),
+            TransactionType::EIP1559 => base_fee.is_none(),
+        };
+        let network_gas_price = match network_gas_price {
+            Ok(price) => Some(price),
+            Err(e) if needs_network_price => return Err(e),
+            Err(e) => {
+                tracing::warn!("Estimating without the node's gas price: {}", e);
+                None
+            }
+        };
+        // Unused by the pricing below when missing
+        let fallback_price = network_gas_price.unwrap_or_default();
+        // The gas price, and so the cost, derives from the fees reported
+        let eip1559_fees = match tx_type {
+            TransactionType::EIP1559 => Some(self.eip1559_fees(&tx, base_fee, fallback_price)?),
+            TransactionType::Legacy => None,
+        };
+        let gas_price = match eip1559_fees {
+            Some((priority_fee, max_fee)) => {
+                eip1559_gas_price(base_fee, fallback_price, priority_fee, max_fee)
+            }
+            None => self.get_legacy_gas_price(&tx, fallback_price)?,
+        };
 
         let total_cost = gas_price.saturating_mul(gas_limit.into());
+        let estimated_cost_usd = eth_usd.map(|eth_usd| format_usd(U256::from(total_cost), eth_usd));
+
+        // The fee cap the client asked for, or ours when they left it to us
+        let offered_fee = match tx_type {
+            TransactionType::Legacy => tx.gas_price.as_deref(),
+            TransactionType::EIP1559 => tx.max_fee_per_gas.as_deref(),
+        }
+        .map(parse_u128)
+        .transpose()?
+        .unwrap_or(gas_price);
+        let underpriced = base_fee.is_some_and(|base_fee| offered_fee < base_fee);
+
+        let mut warnings = Vec::new();
+        if raw_gas_limit < intrinsic_gas {
+            warnings.push(format!(
+                "Node estimated {} gas, below the {} intrinsic gas of the transaction",
+                raw_gas_limit, intrinsic_gas
+            ));
+        }
+        if let Some(base_fee) = base_fee.filter(|_| underpriced) {
+            warnings.push(format!(
+                "Offered fee of {} wei is below the current base fee of {} wei, \
+                 the transaction can't be included until the base fee drops",
+                offered_fee, base_fee
+            ));
+        }
+        if let Some(requested) = &tx.chain_id {
+            let (requested, chain_id) = (parse_u64(requested)?, self.chain_id().await?);
+            if requested != chain_id {
+                warnings.push(format!(
+                    "Requested chain id {} differs from the node's chain id {}, \
+                     the estimate is for chain {}",
+                    requested, chain_id, chain_id
+                ));
+            }
+        }
+        if let (Some((priority_fee, _)), Some(base_fee)) = (eip1559_fees, base_fee) {
+            warnings.extend(self.priority_fee_warning(priority_fee, base_fee));
+        }
+        // Tiers only add to the estimate, which goes on without them
+        let tiers = match (base_fee, tier_history) {
+            (Some(base_fee), Ok(history)) => {
+                history.map(|history| self.fee_tiers(history, base_fee, gas_limit))
+            }
+            (Some(_), Err(e)) => {
+                warnings.push(format!("Fee tiers unavailable: {}", e));
+                None
+            }
+            (None, _) => None,
+        };
+
+        // Without a base fee there is no fee market, whatever type was asked for
+        let pricing_mechanism = match base_fee {
+            Some(_) => tx_type.clone(),
+            None => TransactionType::Legacy,
+        };
+
+        // On chains with a fee market, `gas_price` alone is ambiguous: report the node's
+        // legacy price and the base fee separately
+        let (legacy_gas_price, base_fee_per_gas) = match base_fee {
+            Some(base_fee) => (
+                network_gas_price.map(|price| price.to_string()),
+                Some(base_fee.to_string()),
+            ),
+            None => (None, None),
+        };
 
         Ok(GasEstimation {
+            estimate_id: estimate_id(
+                &transaction,
+                chain_id.ok(),
+                block,
+                Some(header.number),
+                gas_limit,
+                gas_price,
+            )?,
+            block: block.to_string(),
             gas_limit: gas_limit.to_string(),
+            gas_limit_raw: raw_gas_limit.to_string(),
+            intrinsic_gas: intrinsic_gas.to_string(),
+            execution_gas: raw_gas_limit.saturating_sub(intrinsic_gas).to_string(),
             gas_price: gas_price.to_string(),
+            gas_price_gwei: self.cost_format.format_gwei(U256::from(gas_price)),
             estimated_cost_wei: total_cost.to_string(),
-            estimated_cost_eth: format_ether(total_cost),
-            estimated_execution_time: self.estimate_execution_time(&tx_type),
+            estimated_cost_gwei: self.cost_format.format_gwei(U256::from(total_cost)),
+            estimated_cost_eth: self.cost_format.format_ether(U256::from(total_cost)),
+            estimated_cost_usd,
+            estimated_execution_time: self.estimate_execution_time(&tx_type, underpriced),
+            may_never_be_included: underpriced,
+            confidence,
             type_of_transaction: tx_type.to_string(),
+            pricing_mechanism: pricing_mechanism.to_string(),
+            is_contract_creation: tx.is_contract_creation(),
+            legacy_gas_price,
+            base_fee_per_gas,
+            max_priority_fee_per_gas: eip1559_fees
+                .map(|(priority_fee, _)| priority_fee.to_string()),
+            max_fee_per_gas: eip1559_fees.map(|(_, max_fee)| max_fee.to_string()),
+            rpc_calls: None,
+            warnings,
+            tiers,
+            access_list_optimization: None,
+            access_list_impact: None,
+            gas_refund: None,
+            calldata_analysis: self.calldata_analysis(&transaction).await,
+            suggested_confirmations: transaction
+                .value
+                .filter(|value| !value.is_zero())
+                .and_then(|value| self.confirmation_thresholds.confirmations_for(value)),
+            nonce: tx.nonce,
+            approximate: false,
         })
     }
 
-    fn build_transaction_request(&self, tx: &TransactionInput) -> Result<TransactionRequest> {
-        let mut transaction = TransactionRequest::default();
+    /// Best-effort estimate of `tx` without calling the node, for when a full one takes
+    /// too long: the intrinsic gas, priced at the fee sent or the last cached gas price
+    pub async fn approximate_estimate(&self, tx: &TransactionInput) -> Result<GasEstimation> {
+        let transaction = self.build_transaction_request(tx)?;
+        let block = parse_block(tx.block.as_deref())?;
+        let eip1559 = self.eip1559.get().copied().unwrap_or(false);
+        let tx_type = requested_transaction_type(tx).unwrap_or(match self.default_tx_type {
+            DefaultTxType::Eip1559 => TransactionType::EIP1559,
+            DefaultTxType::Auto if eip1559 => TransactionType::EIP1559,
+            DefaultTxType::Legacy | DefaultTxType::Auto => TransactionType::Legacy,
+        });
+
+        let sent_fee = match tx_type {
+            TransactionType::Legacy => tx.gas_price.as_deref(),
+            TransactionType::EIP1559 => tx.max_fee_per_gas.as_deref(),
+        };
+        let gas_price = match sent_fee {
+            Some(fee) => parse_u128(fee)?,
+            None => {
+                let cached = match self.chain_id.get() {
+                    Some(chain_id) => self.price_cache.last_gas_price(*chain_id).await,
+                    None => None,
+                };
+                cached.ok_or_else(|| {
+                    Error::Timeout(
+                        "Estimation exceeded max_latency_ms and no gas price is cached \
+                         to approximate it"
+                            .into(),
+                    )
+                })?
+            }
+        };
+
+        let gas_limit = intrinsic_gas(&transaction);
+        let total_cost = U256::from(gas_price).saturating_mul(U256::from(gas_limit));
+        let mut warnings = vec![
+            "Approximate estimate: the full estimate exceeded max_latency_ms, the node wasn't \
+             consulted"
+                .to_string(),
+        ];
+        if tx.data.is_some() || tx.is_contract_creation() {
+            warnings.push(
+                "gas_limit is the intrinsic gas only, contract execution comes on top".into(),
+            );
+        }
+
+        Ok(GasEstimation {
+            estimate_id: estimate_id(
+                &transaction,
+                self.chain_id.get().copied(),
+                block,
+                None,
+                gas_limit,
+                gas_price,
+            )?,
+            block: block.to_string(),
+            gas_limit: gas_limit.to_string(),
+            gas_limit_raw: gas_limit.to_string(),
+            intrinsic_gas: gas_limit.to_string(),
+            execution_gas: "0".into(),
+            gas_price: gas_price.to_string(),
+            gas_price_gwei: self.cost_format.format_gwei(U256::from(gas_price)),
+            estimated_cost_wei: total_cost.to_string(),
+            estimated_cost_gwei: self.cost_format.format_gwei(total_cost),
+            estimated_cost_eth: self.cost_format.format_ether(total_cost),
+            estimated_execution_time: self.estimate_execution_time(&tx_type, false),
+            type_of_transaction: tx_type.to_string(),
+            pricing_mechanism: tx_type.to_string(),
+            is_contract_creation: tx.is_contract_creation(),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas.clone(),
+            max_fee_per_gas: tx.max_fee_per_gas.clone(),
+            warnings,
+            nonce: tx.nonce,
+            approximate: true,
+            ..Default::default()
+        })
+    }
+
+    /// Analysis of the calldata's L1 cost, only on OP-stack chains
+    async fn calldata_analysis(
+        &self,
+        transaction: &TransactionRequest,
+    ) -> Option<CalldataAnalysis> {
+        let data = transaction.input.input().filter(|data| !data.is_empty())?;
+        let chain_id = self
+            .chain_id()
+            .await
+            .inspect_err(|e| tracing::debug!("No calldata analysis without a chain id: {}", e))
+            .ok()?;
+        is_op_stack(chain_id).then(|| analyze_calldata(data))
+    }
+
+    /// `gas_limit` raised by `GAS_LIMIT_BUFFER_PERCENT`, rounded up, without reaching
+    /// the block gas limit
+    fn buffered_gas_limit(&self, gas_limit: u64, block_gas_limit: u64) -> u64 {
+        let buffer = gas_limit
+            .saturating_mul(self.gas_limit_buffer_percent)
+            .div_ceil(100);
+        gas_limit
+            .saturating_add(buffer)
+            .min(block_gas_limit.saturating_sub(1))
+    }
+
+    /// Estimates `tx` with and without the access list `eth_createAccessList` suggests
+    /// for it, keeping the cheaper of the two
+    pub async fn estimate_gas_optimized(&self, tx: TransactionInput) -> Result<GasEstimation> {
+        let transaction = self.build_transaction_request(&tx)?;
+        let access_list = self.create_access_list(&transaction).await?;
+
+        let with_access_list = TransactionInput {
+            access_list: Some(access_list.clone()),
+            ..tx.clone()
+        };
+        let (without, with) =
+            tokio::try_join!(self.estimate_gas(tx), self.estimate_gas(with_access_list))?;
+        let gas_without = parse_u64(&without.gas_limit)?;
+        let gas_with = parse_u64(&with.gas_limit)?;
+
+        let applied = gas_with < gas_without;
+        let optimization = AccessListOptimization {
+            access_list,
+            gas_without_access_list: gas_without.to_string(),
+            gas_with_access_list: gas_with.to_string(),
+            saved_gas: gas_without.saturating_sub(gas_with).to_string(),
+            applied,
+        };
+        Ok(GasEstimation {
+            access_list_optimization: Some(optimization),
+            ..if applied { with } else { without }
+        })
+    }
+
+    /// Estimates `tx` with its access list, then without it and without each of its
+    /// storage slots in turn to tell what each one saves. Nodes only report totals, so
+    /// a slot's effect is the difference between two estimates.
+    pub async fn estimate_access_list_impact(&self, tx: TransactionInput) -> Result<GasEstimation> {
+        let access_list = tx
+            .access_list
+            .clone()
+            .filter(|list| !list.0.is_empty())
+            .ok_or_else(|| {
+                Error::InvalidInput("access_list_impact needs a non-empty access_list".into())
+            })?;
+        let mut transaction = self.build_transaction_request(&tx)?;
+        transaction.access_list = None;
+        let block = parse_block(tx.block.as_deref())?;
+
+        let slots: Vec<(usize, usize)> = access_list
+            .0
+            .iter()
+            .enumerate()
+            .flat_map(|(item, entry)| (0..entry.storage_keys.len()).map(move |key| (item, key)))
+            .collect();
+        let per_slot = slots.len() <= MAX_IMPACT_SLOTS;
+        let without_slot = |&(item, key): &(usize, usize)| {
+            let mut list = access_list.clone();
+            list.0[item].storage_keys.remove(key);
+            TransactionRequest {
+                access_list: Some(list),
+                ..transaction.clone()
+            }
+        };
+        let slot_estimates = async {
+            if !per_slot {
+                return Ok(Vec::new());
+            }
+            try_join_all(
+                slots
+                    .iter()
+                    .map(without_slot)
+                    .map(|transaction| async move {
+                        self.estimate_gas_limit(&transaction, block).await
+                    }),
+            )
+            .await
+        };
+
+        let (estimation, gas_without, slot_estimates) = tokio::try_join!(
+            self.estimate_gas(tx),
+            self.estimate_gas_limit(&transaction, block),
+            slot_estimates
+        )?;
+        let gas_with = parse_u64(&estimation.gas_limit_raw)?;
+        let saved = |gas: u64| (i128::from(gas) - i128::from(gas_with)).to_string();
+
+        let slots = per_slot.then(|| {
+            slots
+                .iter()
+                .zip(slot_estimates)
+                .map(|(&(item, key), gas)| {
+                    let entry = &access_list.0[item];
+                    SlotImpact {
+                        address: format_address(&entry.address, self.checksum_output),
+                        storage_key: entry.storage_keys[key].to_string(),
+                        saved_gas: saved(gas),
+                    }
+                })
+                .collect()
+        });
+        Ok(GasEstimation {
+            access_list_impact: Some(AccessListImpact {
+                gas_with_access_list: gas_with.to_string(),
+                gas_without_access_list: gas_without.to_string(),
+                saved_gas: saved(gas_without),
+                slots,
+            }),
+            ..estimation
+        })
+    }
+
+    /// Gas refunded to `tx` for clearing storage, from a `debug_traceCall` of it. `None`
+    /// when the node doesn't allow tracing.
+    pub async fn gas_refund(&self, tx: &TransactionInput) -> Option<u64> {
+        let transaction = self.build_transaction_request(tx).ok()?;
+        // Only the refund counter is needed, leave out everything bulky
+        let options = serde_json::json!({
+            "disableStack": true,
+            "disableStorage": true,
+            "enableMemory": false,
+            "enableReturnData": false,
+        });
+        let trace: CallTrace = self
+            .provider
+            .raw_request(
+                "debug_traceCall".into(),
+                (&transaction, BlockNumberOrTag::Latest, options),
+            )
+            .await
+            .inspect_err(|e| tracing::debug!("Gas refund unavailable, trace failed: {}", e))
+            .ok()?;
+        Some(trace.gas_refund())
+    }
+
+    /// Access list the node generates for `transaction`
+    async fn create_access_list(&self, transaction: &TransactionRequest) -> Result<AccessList> {
+        let result = self
+            .provider
+            .create_access_list(transaction)
+            .await
+            .map_err(|e| {
+                if is_method_not_found(&e) {
+                    Error::Unsupported(
+                        "The RPC node doesn't support eth_createAccessList, \
+                         estimate without ?optimize=true"
+                            .into(),
+                    )
+                } else {
+                    e.into()
+                }
+            })?;
+        if let Some(error) = result.error {
+            return Err(Error::GasEstimation(
+                format!("Transaction would fail: Details: {}", error).into(),
+            ));
+        }
+        Ok(result.access_list)
+    }
+
+    /// Slow, average and fast fees from the median over the blocks of `history` of each
+    /// `TIER_PERCENTILES` priority fee, capped at twice `base_fee` plus the priority fee
+    fn fee_tiers(&self, history: FeeHistory, base_fee: u128, gas_limit: u64) -> FeeTiers {
+        let rewards = history.reward.unwrap_or_default();
+
+        let tier = |index: usize| {
+            let mut fees: Vec<u128> = rewards
+                .iter()
+                .filter_map(|block| block.get(index).copied())
+                .collect();
+            fees.sort_unstable();
+            let priority_fee = fees
+                .get(fees.len() / 2)
+                .copied()
+                .unwrap_or(self.default_priority_fee);
+            let max_fee = base_fee.saturating_mul(2).saturating_add(priority_fee);
+            let cost = U256::from(max_fee).saturating_mul(U256::from(gas_limit));
+            FeeTier {
+                max_fee_per_gas: max_fee.to_string(),
+                max_priority_fee_per_gas: priority_fee.to_string(),
+                estimated_cost_wei: cost.to_string(),
+                estimated_cost_eth: self.cost_format.format_ether(cost),
+            }
+        };
+
+        FeeTiers {
+            slow: tier(0),
+            average: tier(1),
+            fast: tier(2),
+        }
+    }
+
+    /// Flags a priority fee above `PRIORITY_FEE_WARNING_MULTIPLE` times the base fee,
+    /// most likely a mistake that overpays
+    fn priority_fee_warning(&self, priority_fee: u128, base_fee: u128) -> Option<String> {
+        if self.priority_fee_warning_multiple <= 0.0 {
+            return None;
+        }
+        let threshold = (base_fee as f64 * self.priority_fee_warning_multiple) as u128;
+        (priority_fee > threshold).then(|| {
+            format!(
+                "max_priority_fee_per_gas of {} wei is more than {} times the base fee of {} wei, consider at most {} wei",
+                priority_fee, self.priority_fee_warning_multiple, base_fee, threshold
+            )
+        })
+    }
+
+    /// Checks the fees of a transaction at current prices against `max_budget_wei`.
+    /// The transferred value is not part of the budget.
+    pub async fn estimate_budget(&self, input: BudgetInput) -> Result<BudgetEstimation> {
+        let budget = parse_u256(&input.max_budget_wei)?;
+        let estimation = self.estimate_gas(input.transaction).await?;
+
+        let gas_limit = parse_u256(&estimation.gas_limit)?;
+        let cost = parse_u256(&estimation.estimated_cost_wei)?;
+        let max_fee_per_gas = budget.checked_div(gas_limit).unwrap_or_default();
+
+        Ok(BudgetEstimation {
+            gas_limit: estimation.gas_limit,
+            gas_price: estimation.gas_price,
+            estimated_cost_wei: estimation.estimated_cost_wei,
+            max_budget_wei: budget.to_string(),
+            within_budget: cost <= budget,
+            max_fee_per_gas_within_budget: max_fee_per_gas.to_string(),
+        })
+    }
+
+    /// Estimates the gas limit once and, for each priority fee of the sweep, how likely
+    /// the recent fee history says it is to get into a block. At most `max_points` fees.
+    pub async fn fee_sweep(&self, input: FeeSweepInput, max_points: usize) -> Result<FeeSweep> {
+        let from = parse_u128(&input.priority_fee_from)?;
+        let to = parse_u128(&input.priority_fee_to)?;
+        let step = parse_u128(&input.priority_fee_step)?;
+        if step == 0 || from > to {
+            return Err(Error::InvalidInput(
+                "priority_fee_step must be positive and priority_fee_from at most priority_fee_to"
+                    .into(),
+            ));
+        }
+        let points = (to - from) / step + 1;
+        if points > max_points as u128 {
+            return Err(Error::InvalidInput(format!(
+                "Sweep has {} points, at most {} allowed",
+                points, max_points
+            )));
+        }
+
+        let transaction = self.build_transaction_request(&input.transaction)?;
+        let block = parse_block(input.transaction.block.as_deref())?;
+        let (gas_limit, history) = tokio::try_join!(
+            self.estimate_gas_limit(&transaction, block),
+            self.fee_history(&[50.0])
+        )?;
+        let history =
+            history.ok_or_else(|| Error::Provider("Node doesn't serve eth_feeHistory".into()))?;
+        let base_fee = history
+            .next_block_base_fee()
+            .filter(|fee| *fee > 0)
+            .ok_or_else(|| Error::Provider("Node reports no base fee".into()))?;
+        let medians: Vec<u128> = history
+            .reward
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
 
-        transaction.from = Some(parse_address(&tx.from)?);
-        transaction.to = Some(parse_address(&tx.to)?.into());
+        let points = (0..points)
+            .map(|i| {
+                let priority_fee = from + i * step;
+                let included = medians.iter().filter(|fee| **fee <= priority_fee).count();
+                let probability = included as f64 / medians.len().max(1) as f64;
+                let cost = U256::from(base_fee.saturating_add(priority_fee))
+                    .saturating_mul(U256::from(gas_limit));
+                FeeSweepPoint {
+                    max_priority_fee_per_gas: priority_fee.to_string(),
+                    max_fee_per_gas: base_fee
+                        .saturating_mul(2)
+                        .saturating_add(priority_fee)
+                        .to_string(),
+                    inclusion_probability: probability,
+                    expected_blocks: (included > 0).then(|| (1.0 / probability).ceil() as u64),
+                    estimated_cost_wei: cost.to_string(),
+                }
+            })
+            .collect();
+
+        Ok(FeeSweep {
+            gas_limit: gas_limit.to_string(),
+            base_fee_per_gas: base_fee.to_string(),
+            points,
+        })
+    }
+
+    /// Executes a read-only call. Reverts surface as estimation errors, like when estimating.
+    pub async fn call(&self, input: CallInput) -> Result<CallResult> {
+        let transaction = self.build_transaction_request(&input.transaction)?;
+        let block = parse_block(input.transaction.block.as_deref())?;
+
+        let (output, gas_estimate) = tokio::join!(
+            self.provider.call(&transaction).block(block.into()),
+            self.provider.estimate_gas(&transaction).block(block.into())
+        );
+        let output = output?;
+        let gas_estimate = match recover_quantity(gas_estimate) {
+            Ok(gas) => Some(gas.to_string()),
+            Err(e) => {
+                tracing::debug!("No gas estimate for call at {}: {}", block, e);
+                None
+            }
+        };
+
+        Ok(CallResult {
+            return_data: output.to_string(),
+            block: block.to_string(),
+            gas_estimate,
+        })
+    }
+
+    /// Estimates both transactions concurrently. A failing side is reported in place
+    /// rather than failing the whole comparison.
+    pub async fn estimate_diff(&self, input: DiffInput) -> Result<EstimateDiff> {
+        let (baseline, candidate) = tokio::join!(
+            self.estimate_gas(input.baseline),
+            self.estimate_gas(input.candidate)
+        );
+
+        let (gas_delta, cost_delta_wei) = match (&baseline, &candidate) {
+            (Ok(baseline), Ok(candidate)) => (
+                Some(signed_delta(&baseline.gas_limit, &candidate.gas_limit)?),
+                Some(signed_delta(
+                    &baseline.estimated_cost_wei,
+                    &candidate.estimated_cost_wei,
+                )?),
+            ),
+            _ => (None, None),
+        };
+
+        Ok(EstimateDiff {
+            baseline: diff_side(baseline),
+            candidate: diff_side(candidate),
+            gas_delta,
+            cost_delta_wei,
+        })
+    }
+
+    pub async fn estimate_replacement(
+        &self,
+        input: ReplacementInput,
+    ) -> Result<ReplacementEstimation> {
+        let tx = &input.transaction;
+        let original_fees = match requested_transaction_type(tx).unwrap_or(TransactionType::Legacy)
+        {
+            TransactionType::EIP1559 => {
+                let (Some(max_fee), Some(priority_fee)) =
+                    (&tx.max_fee_per_gas, &tx.max_priority_fee_per_gas)
+                else {
+                    return Err(Error::InvalidInput(
+                        "Replacing an EIP-1559 transaction requires both max_fee_per_gas and max_priority_fee_per_gas".into(),
+                    ));
+                };
+                OriginalFees::Eip1559 {
+                    max_fee: parse_u128(max_fee)?,
+                    priority_fee: parse_u128(priority_fee)?,
+                }
+            }
+            TransactionType::Legacy => match &tx.gas_price {
+                Some(gas_price) => OriginalFees::Legacy(parse_u128(gas_price)?),
+                None => {
+                    return Err(Error::InvalidInput(
+                        "The original transaction's gas_price is required".into(),
+                    ))
+                }
+            },
+        };
+
+        let mut transaction = self.build_transaction_request(tx)?;
+        transaction.nonce = Some(input.nonce);
+        let block = parse_block(tx.block.as_deref())?;
+
+        // Only a legacy replacement is priced against the node's gas price
+        let network_gas_price = async {
+            match original_fees {
+                OriginalFees::Legacy(_) => self.gas_price(self.price_cache.ttl()).await.map(Some),
+                OriginalFees::Eip1559 { .. } => Ok(None),
+            }
+        };
+        let (gas_limit, header, network_gas_price) = tokio::join!(
+            self.estimate_gas_limit(&transaction, block),
+            self.get_latest_header(),
+            network_gas_price
+        );
+        let gas_limit = gas_limit?;
+        let header = header?;
+        check_block_gas_limit(gas_limit, &header)?;
+        let base_fee = header.base_fee_per_gas.map(u128::from);
+        let network_gas_price = network_gas_price?;
+
+        let fees = match original_fees {
+            OriginalFees::Legacy(original) => ReplacementFees::Legacy(std::cmp::max(
+                bump_fee(original),
+                network_gas_price.unwrap_or_default(),
+            )),
+            OriginalFees::Eip1559 {
+                max_fee,
+                priority_fee,
+            } => {
+                let priority_fee = bump_fee(priority_fee);
+                // A bump that doesn't cover the current base fee would never be included
+                let floor = base_fee.unwrap_or(0).saturating_add(priority_fee);
+                ReplacementFees::Eip1559 {
+                    max_fee: std::cmp::max(bump_fee(max_fee), floor),
+                    priority_fee,
+                }
+            }
+        };
+
+        Ok(ReplacementEstimation {
+            nonce: input.nonce,
+            base_fee_per_gas: base_fee.map(|fee| fee.to_string()),
+            speed_up: fees.option(gas_limit, self.cost_format),
+            cancel: fees.option(TRANSFER_GAS, self.cost_format),
+            rpc_calls: None,
+        })
+    }
+
+    /// Fee conditions without a transaction: the node's gas price and, on chains with a
+    /// base fee, the latest base fee and the median priority fee of recent blocks. All
+    /// of them are cached like the gas price.
+    pub async fn network_fees(&self) -> Result<NetworkFees> {
+        let gas_price = self.gas_price(self.price_cache.ttl());
+        if !self.supports_eip1559().await? {
+            return Ok(NetworkFees {
+                base_fee: None,
+                suggested_priority_fee: None,
+                suggested_max_fee: None,
+                gas_price_legacy: gas_price.await?.to_string(),
+            });
+        }
+
+        let chain_id = self.chain_id().await?;
+        let base_fee_key = format!("base_fee:{}", chain_id);
+        let priority_fee_key = format!("priority_fee:{}", chain_id);
+        let (gas_price, base_fee, priority_fee) = tokio::join!(
+            gas_price,
+            self.price_cache.cached_value(&base_fee_key, async {
+                self.get_base_fee()
+                    .await?
+                    .ok_or_else(|| eyre::eyre!("Node reports no base fee"))
+            }),
+            self.price_cache.cached_value(&priority_fee_key, async {
+                Ok(self.median_priority_fee().await?)
+            })
+        );
+        let base_fee =
+            base_fee.map_err(|e| Error::Provider(format!("Failed to get base fee: {}", e)))?;
+        let priority_fee = priority_fee
+            .map_err(|e| Error::Provider(format!("Failed to get priority fee: {}", e)))?;
+
+        Ok(NetworkFees {
+            base_fee: Some(base_fee.to_string()),
+            suggested_priority_fee: Some(priority_fee.to_string()),
+            suggested_max_fee: Some(
+                base_fee
+                    .saturating_mul(2)
+                    .saturating_add(priority_fee)
+                    .to_string(),
+            ),
+            gas_price_legacy: gas_price?.to_string(),
+        })
+    }
+
+    /// Median of the median priority fees of the last `FEE_HISTORY_BLOCKS` blocks,
+    /// `DEFAULT_PRIORITY_FEE_WEI` without fee history
+    async fn median_priority_fee(&self) -> Result<u128> {
+        let history = self.fee_history(&[50.0]).await?;
+        let mut fees: Vec<u128> = history
+            .and_then(|history| history.reward)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
+        fees.sort_unstable();
+        Ok(fees
+            .get(fees.len() / 2)
+            .copied()
+            .unwrap_or(self.default_priority_fee))
+    }
+
+    /// Projects the base fee `target` blocks out, assuming blocks stay as full as they
+    /// were over the recent fee history, and suggests fees covering the highest of them
+    pub async fn fee_for_blocks(&self, target: u64) -> Result<FeeForBlocks> {
+        // Without fee history, the latest block stands in for the recent ones
+        let (pending_base_fee, gas_used_ratio, mut rewards) =
+            match self.fee_history(&[50.0]).await? {
+                Some(history) => {
+                    let gas_used_ratio = if history.gas_used_ratio.is_empty() {
+                        0.5
+                    } else {
+                        history.gas_used_ratio.iter().sum::<f64>()
+                            / history.gas_used_ratio.len() as f64
+                    };
+                    let rewards: Vec<u128> = history
+                        .reward
+                        .as_deref()
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|block| block.first().copied())
+                        .collect();
+                    (history.next_block_base_fee(), gas_used_ratio, rewards)
+                }
+                None => {
+                    let header = self.get_latest_header().await?;
+                    let gas_used_ratio = header.gas_used as f64 / header.gas_limit.max(1) as f64;
+                    let pending_base_fee = header
+                        .base_fee_per_gas
+                        .map(|base_fee| next_base_fee(base_fee.into(), gas_used_ratio));
+                    (pending_base_fee, gas_used_ratio, Vec::new())
+                }
+            };
+        let pending_base_fee = pending_base_fee
+            .filter(|fee| *fee > 0)
+            .ok_or_else(|| Error::Provider("Node reports no base fee".into()))?;
+
+        let mut projected = vec![pending_base_fee];
+        while (projected.len() as u64) < target {
+            let last = projected[projected.len() - 1];
+            projected.push(next_base_fee(last, gas_used_ratio));
+        }
+
+        rewards.sort_unstable();
+        let priority_fee = rewards
+            .get(rewards.len() / 2)
+            .copied()
+            .unwrap_or(self.default_priority_fee);
+
+        let max_base_fee = projected.iter().copied().max().unwrap_or(pending_base_fee);
+        let (next_base_fee_min, next_base_fee_max) = next_base_fee_bounds(pending_base_fee);
+
+        Ok(FeeForBlocks {
+            target_blocks: target,
+            max_fee_per_gas: max_base_fee.saturating_add(priority_fee).to_string(),
+            max_priority_fee_per_gas: priority_fee.to_string(),
+            projected_base_fees: projected.iter().map(|fee| fee.to_string()).collect(),
+            next_base_fee_min: next_base_fee_min.to_string(),
+            next_base_fee_max: next_base_fee_max.to_string(),
+        })
+    }
+
+    /// Gas used by the transactions calling `selector` on `to` in the last `blocks` blocks.
+    /// Costs one full block fetch per block plus one receipt per match, so results are
+    /// kept until the next block.
+    pub async fn method_gas(&self, to: &str, selector: &str, blocks: u64) -> Result<MethodGas> {
+        let to = parse_address(to)?;
+        let selector = parse_bytes(selector)?;
+        if selector.len() != 4 {
+            return Err(Error::InvalidInput(
+                "The selector must be 4 bytes long".into(),
+            ));
+        }
+        let latest = recover_quantity(self.provider.get_block_number().await)?;
+
+        let key = (to, selector.clone(), blocks);
+        if let Some((block, method_gas)) = self.method_gas_cache.lock().await.get(&key) {
+            if *block == latest {
+                return Ok(method_gas.clone());
+            }
+        }
+
+        let from_block = latest.saturating_sub(blocks.saturating_sub(1));
+        let scanned = try_join_all((from_block..=latest).map(|number| {
+            self.provider
+                .get_block_by_number(number.into(), BlockTransactionsKind::Full)
+        }))
+        .await?;
+        let hashes: Vec<_> = scanned
+            .into_iter()
+            .flatten()
+            .flat_map(|block| block.transactions.into_transactions())
+            .filter(|tx| tx.to() == Some(to) && tx.input().starts_with(&selector))
+            .map(|tx| tx.tx_hash())
+            .collect();
+        let receipts = try_join_all(
+            hashes
+                .iter()
+                .map(|hash| self.provider.get_transaction_receipt(*hash)),
+        )
+        .await?;
+        let gas_used: Vec<u64> = receipts
+            .into_iter()
+            .flatten()
+            .map(|receipt| receipt.gas_used)
+            .collect();
+
+        let average = (!gas_used.is_empty()).then(|| {
+            gas_used.iter().map(|gas| u128::from(*gas)).sum::<u128>() / gas_used.len() as u128
+        });
+        let method_gas = MethodGas {
+            to: format_address(&to, self.checksum_output),
+            selector: selector.to_string(),
+            from_block,
+            to_block: latest,
+            transactions: gas_used.len(),
+            average_gas_used: average.map(|gas| gas.to_string()),
+            median_gas_used: GasSampleAggregation::Median
+                .aggregate(gas_used)
+                .map(|gas| gas.to_string()),
+        };
+
+        let mut cache = self.method_gas_cache.lock().await;
+        cache.retain(|_, (block, _)| *block == latest);
+        cache.insert(key, (latest, method_gas.clone()));
+        Ok(method_gas)
+    }
+
+    /// Priority fee `percentiles` and gas usage of the last `FEE_HISTORY_BLOCKS` blocks,
+    /// or `None` when the node doesn't serve `eth_feeHistory`. That is remembered for
+    /// `FEE_HISTORY_REPROBE_INTERVAL` so later calls don't keep failing against the node.
+    pub async fn fee_history(&self, percentiles: &[f64]) -> Result<Option<FeeHistory>> {
+        let unsupported_since = *self.fee_history_unsupported.lock().unwrap();
+        if unsupported_since.is_some_and(|since| since.elapsed() < FEE_HISTORY_REPROBE_INTERVAL) {
+            return Ok(None);
+        }
+
+        match self
+            .provider
+            .get_fee_history(FEE_HISTORY_BLOCKS, BlockNumberOrTag::Latest, percentiles)
+            .await
+        {
+            Ok(history) => {
+                *self.fee_history_unsupported.lock().unwrap() = None;
+                Ok(Some(history))
+            }
+            Err(e) if is_method_not_found(&e) => {
+                tracing::warn!("Node doesn't support eth_feeHistory ({}), falling back", e);
+                *self.fee_history_unsupported.lock().unwrap() = Some(Instant::now());
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Base fee of the latest block, `None` on chains without EIP-1559
+    async fn get_base_fee(&self) -> Result<Option<u128>> {
+        Ok(self
+            .get_latest_header()
+            .await?
+            .base_fee_per_gas
+            .map(u128::from))
+    }
+
+    async fn get_latest_header(&self) -> Result<Header> {
+        let block = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Latest, BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or_else(|| Error::Provider("Latest block not found".into()))?;
+
+        Ok(block.header)
+    }
+
+    /// Gas limit for `transaction` against the state at `block`, using the configured
+    /// estimation method
+    async fn estimate_gas_limit(
+        &self,
+        transaction: &TransactionRequest,
+        block: BlockNumberOrTag,
+    ) -> Result<u64> {
+        Ok(self
+            .estimate_gas_limit_with_confidence(transaction, block)
+            .await?
+            .0)
+    }
+
+    /// Gas limit along with the confidence derived from the spread of its samples
+    async fn estimate_gas_limit_with_confidence(
+        &self,
+        transaction: &TransactionRequest,
+        block: BlockNumberOrTag,
+    ) -> Result<(u64, Option<Confidence>)> {
+        match self.estimation_method {
+            GasEstimationMethod::Node => {
+                let samples = try_join_all((0..self.gas_estimate_samples).map(|_| async {
+                    let estimate = self.provider.estimate_gas(transaction).block(block.into());
+                    recover_quantity(estimate.await)
+                        .map_err(|e| search_cap_error(Error::from(e), transaction.gas))
+                }))
+                .await?;
+                let confidence = Confidence::from_samples(&samples);
+                let gas_limit = self
+                    .gas_sample_aggregation
+                    .aggregate(samples)
+                    .ok_or_else(|| Error::GasEstimation("No gas estimate samples".into()))?;
+                Ok((gas_limit, confidence))
+            }
+            GasEstimationMethod::BinarySearch => Ok((
+                self.binary_search_gas_limit(transaction, block).await?,
+                None,
+            )),
+        }
+    }
+
+    /// Finds the lowest gas limit `eth_call` succeeds with, between the intrinsic gas of
+    /// the transaction and the block gas limit (or `gas_search_cap` when lower). Fees are
+    /// left out of the calls so the sender's balance doesn't cap the search.
+    async fn binary_search_gas_limit(
+        &self,
+        transaction: &TransactionRequest,
+        block: BlockNumberOrTag,
+    ) -> Result<u64> {
+        let mut call = TransactionRequest {
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            ..transaction.clone()
+        };
+
+        // Below its intrinsic gas the node rejects the transaction before executing it
+        let mut low = intrinsic_gas(transaction).saturating_sub(1);
+        let mut high = self.get_latest_header().await?.gas_limit;
+        if let Some(cap) = transaction.gas {
+            high = high.min(cap);
+        }
+        high = high.max(low + 1);
+        call.gas = Some(high);
+        // Failing with all the gas in a block is a revert, not an under-estimate
+        self.provider.call(&call).block(block.into()).await?;
+
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            call.gas = Some(mid);
+            match self.provider.call(&call).block(block.into()).await {
+                Ok(_) => high = mid,
+                Err(e) if is_execution_failure(&e) => low = mid,
+                // Not an answer about `mid`, searching on would converge to a wrong limit
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(high)
+    }
+
+    pub async fn block_number(&self) -> Result<u64> {
+        recover_quantity(self.provider.get_block_number().await).map_err(Error::from)
+    }
+
+    /// Latest block number, cached like the gas price
+    pub async fn cached_block_number(&self) -> Result<u64> {
+        if self.price_cache.ttl().is_zero() {
+            return self.block_number().await;
+        }
+        let key = format!("block_number:{}", self.chain_id().await?);
+        let block_number = self
+            .price_cache
+            .cached_value(&key, async { Ok(self.block_number().await?.into()) })
+            .await
+            .map_err(|e| Error::Provider(format!("Failed to get block number: {}", e)))?;
+        Ok(block_number as u64)
+    }
+
+    /// Refetches the cached gas price and block number every `interval`, so requests
+    /// find them fresh instead of waiting on the node once they expire. Runs until
+    /// dropped.
+    pub async fn keep_cache_warm(&self, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let (gas_price, block_number) = bypass_cache(true, async {
+                tokio::join!(
+                    self.gas_price(self.price_cache.ttl()),
+                    self.cached_block_number()
+                )
+            })
+            .await;
+            if let Err(e) = gas_price.and(block_number) {
+                tracing::warn!("Background cache refresh failed: {}", e);
+            }
+        }
+    }
+
+    /// Current gas price, served from the cache when younger than `max_age`
+    pub async fn gas_price(&self, max_age: Duration) -> Result<u128> {
+        let chain_id = async { Ok(self.chain_id().await?) };
+        self.price_cache
+            .cached_gas_price(&self.provider, chain_id, max_age)
+            .await
+            .map_err(|e| Error::Provider(format!("Failed to get gas price: {}", e)))
+    }
+
+    fn build_transaction_request(&self, tx: &TransactionInput) -> Result<TransactionRequest> {
+        let mut transaction = TransactionRequest {
+            from: Some(parse_address(&tx.from)?),
+            to: Some(match &tx.to {
+                _ if tx.is_contract_creation() => TxKind::Create,
+                Recipient::Address(to) => parse_address(to)?.into(),
+                Recipient::Create | Recipient::Missing => return Err(missing_recipient()),
+            }),
+            ..Default::default()
+        };
 
         if let Some(data) = &tx.data {
-            transaction.input = TxData::new(parse_bytes(data)?);
+            let data = parse_bytes(data)?;
+            if self.max_calldata_bytes > 0 && data.len() > self.max_calldata_bytes {
+                return Err(Error::InvalidInput(format!(
+                    "Calldata is {} bytes, at most {} allowed",
+                    data.len(),
+                    self.max_calldata_bytes
+                )));
+            }
+            transaction.input = TxData::new(data);
         }
         if let Some(value) = &tx.value {
             transaction.value = Some(parse_u256(value)?);
         }
+        transaction.nonce = tx.nonce;
         if let Some(max_fee) = &tx.max_fee_per_gas {
             transaction.max_fee_per_gas = Some(parse_u128(max_fee)?);
         }
         if let Some(max_priority_fee) = &tx.max_priority_fee_per_gas {
             transaction.max_priority_fee_per_gas = Some(parse_u128(max_priority_fee)?);
         }
+        if let (Some(max_fee), Some(max_priority_fee)) = (
+            transaction.max_fee_per_gas,
+            transaction.max_priority_fee_per_gas,
+        ) {
+            if max_fee < max_priority_fee {
+                return Err(Error::InvalidInput(
+                    "max_fee_per_gas must be >= max_priority_fee_per_gas".into(),
+                ));
+            }
+        }
         if let Some(gas_price) = &tx.gas_price {
             transaction.gas_price = Some(parse_u128(gas_price)?);
         }
+        if let Some(cap) = &tx.gas_search_cap {
+            transaction.gas = Some(parse_u64(cap)?);
+        }
+        transaction.access_list = tx.access_list.clone();
 
         Ok(transaction)
     }
 
-    fn determine_transaction_type(&self, tx: &TransactionInput) -> TransactionType {
-        if tx.max_fee_per_gas.is_some() || tx.max_priority_fee_per_gas.is_some() {
-            TransactionType::EIP1559
-        } else {
-            TransactionType::Legacy
+    /// Type implied by the fee fields, or the configured default for fee-less requests
+    async fn determine_transaction_type(&self, tx: &TransactionInput) -> Result<TransactionType> {
+        if let Some(tx_type) = requested_transaction_type(tx) {
+            return Ok(tx_type);
         }
+        Ok(match self.default_tx_type {
+            DefaultTxType::Legacy => TransactionType::Legacy,
+            DefaultTxType::Eip1559 => TransactionType::EIP1559,
+            DefaultTxType::Auto if self.supports_eip1559().await? => TransactionType::EIP1559,
+            DefaultTxType::Auto => TransactionType::Legacy,
+        })
     }
 
-    async fn get_gas_price(&self, tx_type: TransactionType, tx: &TransactionInput) -> Result<u128> {
-        match tx_type {
-            TransactionType::Legacy => self.get_legacy_gas_price(tx).await,
-            TransactionType::EIP1559 => self.get_eip1559_gas_price(tx).await,
+    fn get_legacy_gas_price(&self, tx: &TransactionInput, network_gas_price: u128) -> Result<u128> {
+        match &tx.gas_price {
+            Some(gas_price_str) => parse_u128(gas_price_str),
+            None => Ok(network_gas_price),
         }
     }
 
-    async fn get_legacy_gas_price(&self, tx: &TransactionInput) -> Result<u128> {
-        if let Some(gas_price_str) = &tx.gas_price {
-            return Ok(parse_u128(gas_price_str)?);
-        }
-        cached_gas_price(self.provider.clone(), self.cache_duration)
-            .await
-            .map_err(|e| Error::Provider(format!("Failed to get gas price: {}", e)))
+    /// Priority fee and max fee of an EIP-1559 `tx`, ours where the client left them
+    /// out. Our max fee leaves room for the base fee to double: `base_fee * 2 +
+    /// priority_fee`.
+    fn eip1559_fees(
+        &self,
+        tx: &TransactionInput,
+        base_fee: Option<u128>,
+        network_gas_price: u128,
+    ) -> Result<(u128, u128)> {
+        let priority_fee = match &tx.max_priority_fee_per_gas {
+            Some(fee) => parse_u128(fee)?,
+            None => self.default_priority_fee,
+        };
+        let max_fee = match &tx.max_fee_per_gas {
+            Some(fee) => parse_u128(fee)?,
+            None => base_fee
+                .map_or(network_gas_price.max(priority_fee), |base_fee| {
+                    base_fee.saturating_mul(2)
+                })
+                .saturating_add(priority_fee),
+        };
+        Ok((priority_fee, max_fee))
     }
 
-    async fn get_eip1559_gas_price(&self, tx: &TransactionInput) -> Result<u128> {
-        let suggested_priority_fee = tx
-            .max_priority_fee_per_gas
-            .as_ref()
-            .map(|fee| parse_u128(fee))
-            .transpose()?
-            .unwrap_or(DEFAULT_PRIORITY_FEE);
+    /// Rough inclusion time, `None` for an underpriced transaction that can't be
+    /// included at the current base fee
+    fn estimate_execution_time(
+        &self,
+        tx_type: &TransactionType,
+        underpriced: bool,
+    ) -> Option<String> {
+        if underpriced {
+            return None;
+        }
+        let expected = match tx_type {
+            TransactionType::Legacy => self.legacy_execution_time,
+            TransactionType::EIP1559 => self.eip1559_execution_time,
+        };
 
-        let current_gas_price = self.provider.get_gas_price().await?;
-        Ok(std::cmp::max(current_gas_price, suggested_priority_fee))
+        Some(match expected.min(self.max_execution_time).as_secs() {
+            // Sub-second blocks of L2s
+            0 | 1 => "~1 second".into(),
+            secs if secs < 120 => format!("~{} seconds", secs),
+            secs => format!("~{} minutes", secs / 60),
+        })
     }
+}
 
-    fn estimate_execution_time(&self, tx_type: &TransactionType) -> Option<String> {
-        Some(
-            match tx_type {
-                TransactionType::Legacy => "~30 seconds",
-                TransactionType::EIP1559 => "~15 seconds",
-            }
-            .to_string(),
-        )
-    }
+enum OriginalFees {
+    Legacy(u128),
+    Eip1559 { max_fee: u128, priority_fee: u128 },
+}
+
+enum ReplacementFees {
+    Legacy(u128),
+    Eip1559 { max_fee: u128, priority_fee: u128 },
+}
+
+impl ReplacementFees {
+    fn option(&self, gas_limit: u64, cost_format: CostFormat) -> ReplacementOption {
+        let (gas_price, max_fee, priority_fee, fee_cap) = match *self {
+            ReplacementFees::Legacy(gas_price) => (Some(gas_price), None, None, gas_price),
+            ReplacementFees::Eip1559 {
+                max_fee,
+                priority_fee,
+            } => (None, Some(max_fee), Some(priority_fee), max_fee),
+        };
+        let cost = U256::from(fee_cap).saturating_mul(U256::from(gas_limit));
+
+        ReplacementOption {
+            gas_limit: gas_limit.to_string(),
+            gas_price: gas_price.map(|fee| fee.to_string()),
+            max_fee_per_gas: max_fee.map(|fee| fee.to_string()),
+            max_priority_fee_per_gas: priority_fee.map(|fee| fee.to_string()),
+            estimated_cost_wei: cost.to_string(),
+            estimated_cost_eth: cost_format.format_ether(cost),
+        }
+    }
+}
+
+/// Keccak-256 of the JSON of a fixed-order tuple, so equal inputs always hash the same.
+/// Covers the chain, the block requested and the latest block when it was estimated,
+/// which an approximate estimate doesn't know.
+fn estimate_id(
+    transaction: &TransactionRequest,
+    chain_id: Option<u64>,
+    requested_block: BlockNumberOrTag,
+    latest_block: Option<u64>,
+    gas_limit: u64,
+    gas_price: u128,
+) -> Result<String> {
+    let fingerprint = (
+        (
+            transaction.from,
+            transaction.to.and_then(|kind| kind.to().copied()),
+            transaction.input.input(),
+            transaction.value,
+            transaction.nonce,
+            &transaction.access_list,
+            transaction.gas_price,
+            transaction.max_fee_per_gas,
+            transaction.max_priority_fee_per_gas,
+            transaction.gas,
+        ),
+        chain_id,
+        requested_block.to_string(),
+        latest_block,
+        gas_limit,
+        gas_price,
+    );
+    let bytes = serde_json::to_vec(&fingerprint).map_err(|e| Error::Server(e.to_string()))?;
+    Ok(keccak256(bytes).to_string())
+}
+
+/// Struct log trace from `debug_traceCall`, reduced to what the refund needs
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CallTrace {
+    /// Gas used, refund already deducted
+    gas: u64,
+    #[serde(default)]
+    struct_logs: Vec<StructLog>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StructLog {
+    /// Refund counter before this opcode, left out while zero
+    #[serde(default)]
+    refund: u64,
+}
+
+impl CallTrace {
+    /// The refund counter at the last opcode, capped at a fifth of the gas used before
+    /// the refund, i.e. a quarter of the gas used after it
+    fn gas_refund(&self) -> u64 {
+        let counter = self.struct_logs.last().map_or(0, |log| log.refund);
+        counter.min(self.gas / (MAX_REFUND_QUOTIENT - 1))
+    }
+}
+
+/// What an EIP-1559 transaction pays per gas once included: the base fee plus the
+/// priority fee, capped by the max fee. Without a base fee there is no fee market, so
+/// the node's price is all there is.
+fn eip1559_gas_price(
+    base_fee: Option<u128>,
+    network_gas_price: u128,
+    priority_fee: u128,
+    max_fee: u128,
+) -> u128 {
+    match base_fee {
+        Some(base_fee) => base_fee.saturating_add(priority_fee),
+        None => network_gas_price.max(priority_fee),
+    }
+    .min(max_fee)
+}
+
+/// Gas charged before any execution: the base cost, calldata, the access list and, for
+/// contract creations, the creation cost and init code words
+fn intrinsic_gas(transaction: &TransactionRequest) -> u64 {
+    let data = transaction.input.input().map_or(&[][..], |data| &data[..]);
+    let zero_bytes = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zero_bytes = data.len() as u64 - zero_bytes;
+    let mut gas = TRANSFER_GAS + zero_bytes * ZERO_BYTE_GAS + non_zero_bytes * NON_ZERO_BYTE_GAS;
+    if matches!(transaction.to, Some(TxKind::Create)) {
+        gas += CREATE_GAS + (data.len() as u64).div_ceil(32) * INIT_CODE_WORD_GAS;
+    }
+    for item in transaction.access_list.iter().flat_map(|list| list.iter()) {
+        gas +=
+            ACCESS_LIST_ADDRESS_GAS + item.storage_keys.len() as u64 * ACCESS_LIST_STORAGE_KEY_GAS;
+    }
+    gas
+}
+
+/// EIP-1559 base fee update for a parent block filled to `gas_used_ratio` of its limit
+fn next_base_fee(base_fee: u128, gas_used_ratio: f64) -> u128 {
+    // Work in parts per million of the gas limit, the target being half of it
+    let gas_used = (gas_used_ratio.clamp(0.0, 1.0) * 1_000_000.0).round() as u128;
+    let gas_target = 500_000;
+
+    if gas_used > gas_target {
+        let delta =
+            base_fee * (gas_used - gas_target) / gas_target / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+        base_fee.saturating_add(delta.max(1))
+    } else {
+        let delta =
+            base_fee * (gas_target - gas_used) / gas_target / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+        base_fee.saturating_sub(delta)
+    }
+}
+
+/// The base fee moves by at most 12.5% per block, down after an empty block and up
+/// after a full one
+fn next_base_fee_bounds(base_fee: u128) -> (u128, u128) {
+    (next_base_fee(base_fee, 0.0), next_base_fee(base_fee, 1.0))
+}
+
+/// Type implied by the fee fields the client sent, if any
+fn requested_transaction_type(tx: &TransactionInput) -> Option<TransactionType> {
+    if tx.max_fee_per_gas.is_some() || tx.max_priority_fee_per_gas.is_some() {
+        Some(TransactionType::EIP1559)
+    } else if tx.gas_price.is_some() {
+        Some(TransactionType::Legacy)
+    } else {
+        None
+    }
+}
+
+/// Applies the replacement bump, rounding up so the node never sees less than the minimum
+fn bump_fee(fee: u128) -> u128 {
+    fee.saturating_add(fee.saturating_mul(REPLACEMENT_BUMP_PERCENT).div_ceil(100))
+}
+
+/// With a search cap, the node's out-of-gas answer means the cap was too low rather than
+/// the transaction failing outright
+fn search_cap_error(error: Error, cap: Option<u64>) -> Error {
+    match (error, cap) {
+        (Error::GasEstimation(failure), Some(cap))
+            if failure.message.contains("exceeds allowance") =>
+        {
+            Error::GasEstimation(
+                format!("Transaction needs more gas than gas_search_cap of {}", cap).into(),
+            )
+        }
+        (error, _) => error,
+    }
+}
+
+/// Nodes that can't find a sufficient gas limit may answer with the block gas limit
+/// itself instead of an error
+fn check_block_gas_limit(gas_limit: u64, header: &Header) -> Result<()> {
+    if gas_limit >= header.gas_limit {
+        return Err(Error::GasEstimation(
+            "estimate hit block gas limit; transaction likely cannot be executed".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn diff_side(result: Result<GasEstimation>) -> DiffSide {
+    match result {
+        Ok(estimation) => DiffSide {
+            estimation: Some(estimation),
+            error: None,
+        },
+        Err(e) => DiffSide {
+            estimation: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// `to - from` of two decimal amounts, with a leading `-` when negative
+fn signed_delta(from: &str, to: &str) -> Result<String> {
+    let (from, to) = (parse_u256(from)?, parse_u256(to)?);
+    Ok(if to >= from {
+        (to - from).to_string()
+    } else {
+        format!("-{}", from - to)
+    })
+}
+
+/// Rejection of a request without a `to` field
+pub fn missing_recipient() -> Error {
+    Error::InvalidInput("to is required; use null for contract creation".into())
+}
+
+/// Accepts a tag (`latest`, `pending`, ...), a hex number or a decimal number
+fn parse_block(input: Option<&str>) -> Result<BlockNumberOrTag> {
+    let Some(input) = input else {
+        return Ok(BlockNumberOrTag::Latest);
+    };
+    match input.parse::<u64>() {
+        Ok(number) => Ok(BlockNumberOrTag::Number(number)),
+        Err(_) => BlockNumberOrTag::from_str(input)
+            .map_err(|e| Error::InvalidInput(format!("Invalid block '{}': {}", input, e))),
+    }
 }
 
 fn parse_address(input: &str) -> Result<Address> {
@@ -143,31 +1706,1699 @@ fn parse_bytes(input: &str) -> Result<Bytes> {
     Bytes::from_str(input).map_err(|_| Error::InvalidInput("Invalid transaction data".into()))
 }
 
+/// Numbers are accepted as `0x`-prefixed hex or decimal
 fn parse_u256(input: &str) -> Result<U256> {
     U256::from_str(input).map_err(|_| Error::InvalidInput("Invalid U256 value".into()))
 }
 
+fn parse_u64(input: &str) -> Result<u64> {
+    parse_hex_or_decimal(input).ok_or_else(|| Error::InvalidInput("Invalid u64 value".into()))
+}
+
 fn parse_u128(input: &str) -> Result<u128> {
-    input
-        .parse::<u128>()
-        .map_err(|_| Error::InvalidInput("Invalid u128 value".into()))
+    parse_hex_or_decimal(input).ok_or_else(|| Error::InvalidInput("Invalid u128 value".into()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::transaction::{Compressibility, FeeUnit};
+    use crate::test_utils::{
+        fee_history, fee_history_rewards, mock_block, quantity, MockTransport,
+    };
+    use serde_json::{json, Value};
     use std::net::IpAddr;
     use std::str::FromStr;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
     use std::time::Duration;
 
+    const GWEI: u128 = 1_000_000_000;
+
+    fn mock_service(transport: &MockTransport) -> EthereumService {
+        EthereumService::with_provider(transport.provider(), &AppConfig::default())
+    }
+
+    fn mock_tx() -> TransactionInput {
+        TransactionInput {
+            from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string(),
+            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".into(),
+            data: None,
+            value: None,
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
+        }
+    }
+
+    /// Chain with a 30 gwei base fee, 31 gwei gas price and a 50k gas estimate, recent
+    /// blocks paying 1, 2 and 5 gwei priority fees at the tier percentiles
+    fn fee_market_transport() -> MockTransport {
+        MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(50_000)),
+            "eth_gasPrice" => Ok(quantity(31 * GWEI)),
+            "eth_feeHistory" => Ok(fee_history_rewards(
+                &[30 * GWEI; 3],
+                &[0.5; 2],
+                &[GWEI, 2 * GWEI, 5 * GWEI],
+            )),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_estimate_reports_legacy_price_and_base_fee() {
+        let service = mock_service(&fee_market_transport());
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+
+        assert_eq!(estimation.legacy_gas_price, Some((31 * GWEI).to_string()));
+        assert_eq!(estimation.base_fee_per_gas, Some((30 * GWEI).to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_price_omitted_when_node_fails_to_give_one() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(50_000)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            "eth_feeHistory" => Err("eth_feeHistory is not supported".into()),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let tx = TransactionInput {
+            max_priority_fee_per_gas: Some((2 * GWEI).to_string()),
+            ..mock_tx()
+        };
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.gas_price, (32 * GWEI).to_string());
+        assert_eq!(estimation.legacy_gas_price, None);
+        assert_eq!(estimation.base_fee_per_gas, Some((30 * GWEI).to_string()));
+
+        // Pricing a legacy transaction needs it
+        let result = service.estimate_gas(mock_tx()).await;
+        assert!(matches!(result, Err(Error::Provider(_))));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_reports_gwei_amounts() {
+        // 31 gwei for 50k gas
+        let service = mock_service(&fee_market_transport());
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.gas_price, (31 * GWEI).to_string());
+        assert_eq!(estimation.gas_price_gwei, "31.000000000");
+        assert_eq!(estimation.estimated_cost_gwei, "1550000.000000000");
+        assert_eq!(estimation.estimated_cost_eth, "0.001550000000000000");
+    }
+
+    #[tokio::test]
+    async fn test_estimate_id_is_deterministic_per_block() {
+        let block = Arc::new(AtomicU64::new(100));
+        let current = block.clone();
+        let chain = Arc::new(AtomicU64::new(1));
+        let chain_id = chain.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_chainId" => Ok(quantity(chain_id.load(Ordering::SeqCst).into())),
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(
+                current.load(Ordering::SeqCst),
+                None,
+                30_000_000,
+                15_000_000,
+            )),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let first = service.estimate_gas(mock_tx()).await.unwrap();
+        let second = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(first.estimate_id.len(), 66);
+        assert_eq!(first.estimate_id, second.estimate_id);
+
+        let mut other = mock_tx();
+        other.value = Some("1".into());
+        let other = service.estimate_gas(other).await.unwrap();
+        assert_ne!(first.estimate_id, other.estimate_id);
+
+        let mut other = mock_tx();
+        other.nonce = Some(7);
+        let other = service.estimate_gas(other).await.unwrap();
+        assert_ne!(first.estimate_id, other.estimate_id);
+
+        let mut other = mock_tx();
+        other.block = Some("pending".into());
+        let other = service.estimate_gas(other).await.unwrap();
+        assert_ne!(first.estimate_id, other.estimate_id);
+
+        block.store(101, Ordering::SeqCst);
+        let next_block = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_ne!(first.estimate_id, next_block.estimate_id);
+
+        // Same transaction and block on another chain
+        block.store(100, Ordering::SeqCst);
+        chain.store(10, Ordering::SeqCst);
+        let other_chain = mock_service(&transport)
+            .estimate_gas(mock_tx())
+            .await
+            .unwrap();
+        assert_ne!(first.estimate_id, other_chain.estimate_id);
+    }
+
+    #[tokio::test]
+    async fn test_underpriced_transaction_may_never_be_included() {
+        let config = AppConfig {
+            max_execution_time: Duration::from_secs(600),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(fee_market_transport().provider(), &config);
+        let tx = TransactionInput {
+            max_fee_per_gas: Some((10 * GWEI).to_string()),
+            max_priority_fee_per_gas: Some(GWEI.to_string()),
+            ..mock_tx()
+        };
+
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert!(estimation.may_never_be_included);
+        assert_eq!(estimation.estimated_execution_time, None);
+        assert_eq!(
+            estimation.warnings,
+            vec![
+                "Offered fee of 10000000000 wei is below the current base fee of 30000000000 wei, \
+                 the transaction can't be included until the base fee drops"
+            ]
+        );
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert!(!estimation.may_never_be_included);
+        assert_eq!(
+            estimation.estimated_execution_time.as_deref(),
+            Some("~30 seconds")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_background_refresh_keeps_cache_current() {
+        let block = Arc::new(AtomicU64::new(100));
+        let latest = block.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_blockNumber" => Ok(quantity(latest.load(Ordering::SeqCst).into())),
+            "eth_gasPrice" => Ok(quantity(latest.load(Ordering::SeqCst) as u128 * GWEI)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            cache_duration: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let refresher = service.clone();
+        let refresh =
+            tokio::spawn(async move { refresher.keep_cache_warm(Duration::from_millis(20)).await });
+
+        // The first refresh runs right away
+        tokio::task::yield_now().await;
+        assert_eq!(service.cached_block_number().await.unwrap(), 100);
+        // Well within the TTL, the cache still follows the node
+        block.store(101, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_millis(20)).await;
+        assert_eq!(service.cached_block_number().await.unwrap(), 101);
+        assert_eq!(
+            service.gas_price(config.cache_duration).await.unwrap(),
+            101 * GWEI
+        );
+
+        refresh.abort();
+    }
+
+    #[tokio::test]
+    async fn test_configured_execution_time_and_priority_fee() {
+        // An L2 with quarter-second blocks and cheap inclusion
+        let config = AppConfig {
+            eip1559_execution_time: Duration::from_millis(250),
+            default_priority_fee: 10_000_000,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(fee_market_transport().provider(), &config);
+        let tx = TransactionInput {
+            max_fee_per_gas: Some((100 * GWEI).to_string()),
+            ..mock_tx()
+        };
+
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(
+            estimation.max_priority_fee_per_gas.as_deref(),
+            Some("10000000")
+        );
+        assert_eq!(
+            estimation.estimated_execution_time.as_deref(),
+            Some("~1 second")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_retries_until_node_is_up() {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_blockNumber" if counter.fetch_add(1, Ordering::SeqCst) < 2 => {
+                Err("connection refused".into())
+            }
+            "eth_blockNumber" => Ok(quantity(100)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let mut config = AppConfig {
+            startup_retry_attempts: 2,
+            startup_retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        assert!(EthereumService::connect(transport.provider(), &config)
+            .await
+            .is_ok());
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+
+        checks.store(0, Ordering::SeqCst);
+        config.startup_retry_attempts = 1;
+        let result = EthereumService::connect(transport.provider(), &config).await;
+        assert!(matches!(result, Err(Error::Provider(_))));
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_checks_chain_id() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_blockNumber" => Ok(quantity(100)),
+            "eth_chainId" => Ok(quantity(1)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let connect = |chain_id| {
+            let config = AppConfig {
+                chain_id,
+                ..Default::default()
+            };
+            let provider = transport.provider();
+            async move { EthereumService::connect(provider, &config).await }
+        };
+
+        assert!(connect(None).await.is_ok());
+        assert_eq!(connect(Some(1)).await.unwrap().chain_id().await.unwrap(), 1);
+        let error = connect(Some(11155111)).await.err().unwrap();
+        assert!(
+            matches!(&error, Error::Config(msg) if msg == "RPC node is on chain 1, CHAIN_ID is 11155111"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_at_block_gas_limit_is_rejected() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(30_000_000)),
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+
+        let result = mock_service(&transport).estimate_gas(mock_tx()).await;
+        match result {
+            Err(Error::GasEstimation(failure)) => assert_eq!(
+                failure.message,
+                "estimate hit block gas limit; transaction likely cannot be executed"
+            ),
+            other => panic!("expected a gas estimation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gas_limit_buffer() {
+        let estimate = |gas: u64, buffer_percent: u64| async move {
+            let transport = MockTransport::new(move |method, _| match method {
+                "eth_estimateGas" => Ok(quantity(gas.into())),
+                "eth_gasPrice" => Ok(quantity(GWEI)),
+                "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+                _ => Err(format!("unexpected method {}", method)),
+            });
+            let config = AppConfig {
+                gas_limit_buffer_percent: buffer_percent,
+                ..Default::default()
+            };
+            EthereumService::with_provider(transport.provider(), &config)
+                .estimate_gas(mock_tx())
+                .await
+                .unwrap()
+        };
+
+        let estimation = estimate(21_000, 0).await;
+        assert_eq!(estimation.gas_limit, "21000");
+        assert_eq!(estimation.gas_limit_raw, "21000");
+
+        let estimation = estimate(50_001, 10).await;
+        assert_eq!(estimation.gas_limit, "55002");
+        assert_eq!(estimation.gas_limit_raw, "50001");
+        assert_eq!(estimation.execution_gas, "29001");
+        assert_eq!(estimation.estimated_cost_wei, (55_002 * GWEI).to_string());
+
+        // The buffer stops short of the block gas limit
+        let estimation = estimate(29_000_000, 10).await;
+        assert_eq!(estimation.gas_limit, "29999999");
+        assert_eq!(estimation.gas_limit_raw, "29000000");
+    }
+
+    #[tokio::test]
+    async fn test_estimate_with_nonce() {
+        // A node estimating differently once earlier transactions of the sender are pending
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_estimateGas" => match params[0]["nonce"].as_str() {
+                Some("0x5") => Ok(quantity(30_000)),
+                None => Ok(quantity(21_000)),
+                nonce => Err(format!("unexpected nonce {:?}", nonce)),
+            },
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = EthereumService::with_provider(transport.provider(), &Default::default());
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(
+            (estimation.gas_limit.as_str(), estimation.nonce),
+            ("21000", None)
+        );
+
+        let tx: TransactionInput = serde_json::from_value(json!({
+            "from": "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+            "to": "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5",
+            "nonce": 5
+        }))
+        .unwrap();
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(
+            (estimation.gas_limit.as_str(), estimation.nonce),
+            ("30000", Some(5))
+        );
+
+        // The former field name still works
+        let tx: TransactionInput = serde_json::from_value(json!({
+            "from": "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+            "to": "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5",
+            "_nonce": 5
+        }))
+        .unwrap();
+        assert_eq!(tx.nonce, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_confirmations_grow_with_value() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            confirmation_thresholds: "0:1,1:3,10:12,100:64".parse().unwrap(),
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let confirmations = |value: Option<&str>| {
+            let tx = TransactionInput {
+                value: value.map(String::from),
+                ..mock_tx()
+            };
+            let service = &service;
+            async move {
+                service
+                    .estimate_gas(tx)
+                    .await
+                    .unwrap()
+                    .suggested_confirmations
+            }
+        };
+
+        // 0.01 ETH and 500 ETH
+        let small = confirmations(Some("10000000000000000")).await.unwrap();
+        let large = confirmations(Some("500000000000000000000")).await.unwrap();
+        assert_eq!((small, large), (1, 64));
+        assert!(large > small);
+        // Not a value transfer
+        assert_eq!(confirmations(None).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_chain_id_mismatch_warns() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_chainId" => Ok(quantity(1)),
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+        let estimate = |chain_id: &str| {
+            service.estimate_gas(TransactionInput {
+                chain_id: Some(chain_id.into()),
+                ..mock_tx()
+            })
+        };
+
+        let estimation = estimate("0x5").await.unwrap();
+        assert_eq!(
+            estimation.warnings,
+            vec![
+                "Requested chain id 5 differs from the node's chain id 1, \
+                 the estimate is for chain 1"
+            ]
+        );
+        assert!(estimate("1").await.unwrap().warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_calldata_analysis_on_op_stack() {
+        let estimate = |chain_id: u64, data: Option<&str>| {
+            let transport = MockTransport::new(move |method, _| match method {
+                "eth_chainId" => Ok(quantity(chain_id.into())),
+                "eth_estimateGas" => Ok(quantity(50_000)),
+                "eth_gasPrice" => Ok(quantity(GWEI)),
+                "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+                _ => Err(format!("unexpected method {}", method)),
+            });
+            let tx = TransactionInput {
+                data: data.map(str::to_string),
+                ..mock_tx()
+            };
+            async move { mock_service(&transport).estimate_gas(tx).await.unwrap() }
+        };
+        let data = format!("0xa9059cbb{:0>64}{:0>64}", "11".repeat(20), "64");
+
+        let analysis = estimate(10, Some(&data)).await.calldata_analysis.unwrap();
+        assert_eq!(analysis.zero_bytes, 43);
+        assert_eq!(analysis.non_zero_bytes, 25);
+        assert_eq!(analysis.l1_data_gas, "572");
+        assert_eq!(analysis.compressibility, Compressibility::High);
+
+        assert_eq!(estimate(1, Some(&data)).await.calldata_analysis, None);
+        assert_eq!(estimate(10, None).await.calldata_analysis, None);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_against_block() {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_estimateGas" => match params[1].as_str() {
+                Some("pending") => Ok(quantity(30_000)),
+                Some("latest") => Ok(quantity(21_000)),
+                Some("0x64") => Ok(quantity(25_000)),
+                other => Err(format!("unexpected block {:?}", other)),
+            },
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+        let estimate = |block: Option<&str>| {
+            service.estimate_gas(TransactionInput {
+                block: block.map(str::to_string),
+                ..mock_tx()
+            })
+        };
+
+        let estimation = estimate(None).await.unwrap();
+        assert_eq!(
+            (estimation.block.as_str(), estimation.gas_limit.as_str()),
+            ("latest", "21000")
+        );
+        let estimation = estimate(Some("pending")).await.unwrap();
+        assert_eq!(
+            (estimation.block.as_str(), estimation.gas_limit.as_str()),
+            ("pending", "30000")
+        );
+        let estimation = estimate(Some("100")).await.unwrap();
+        assert_eq!(
+            (estimation.block.as_str(), estimation.gas_limit.as_str()),
+            ("0x64", "25000")
+        );
+        assert!(matches!(
+            estimate(Some("yesterday")).await,
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_diff() {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_estimateGas" => match params[0]["input"].as_str() {
+                Some("0x01") => Ok(quantity(30_000)),
+                Some("0x02") => Ok(quantity(25_000)),
+                _ => Err("execution reverted".into()),
+            },
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+        let with_data = |data: &str| TransactionInput {
+            data: Some(data.to_string()),
+            ..mock_tx()
+        };
+
+        let diff = service
+            .estimate_diff(DiffInput {
+                baseline: with_data("0x01"),
+                candidate: with_data("0x02"),
+            })
+            .await
+            .unwrap();
+        assert_eq!(diff.gas_delta.as_deref(), Some("-5000"));
+        assert_eq!(diff.cost_delta_wei.as_deref(), Some("-5000000000000"));
+
+        let diff = service
+            .estimate_diff(DiffInput {
+                baseline: with_data("0x01"),
+                candidate: with_data("0xff"),
+            })
+            .await
+            .unwrap();
+        assert!(diff.baseline.estimation.is_some());
+        assert!(diff.candidate.error.unwrap().contains("execution reverted"));
+        assert_eq!(diff.gas_delta, None);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_omits_base_fee_on_legacy_chain() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+
+        assert_eq!(estimation.gas_price, (20 * GWEI).to_string());
+        assert_eq!(estimation.legacy_gas_price, None);
+        assert_eq!(estimation.base_fee_per_gas, None);
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_request_on_legacy_chain_priced_as_legacy() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let tx = TransactionInput {
+            max_priority_fee_per_gas: Some((2 * GWEI).to_string()),
+            ..mock_tx()
+        };
+        let estimation = service.estimate_gas(tx).await.unwrap();
+
+        assert_eq!(estimation.type_of_transaction, "eip1559");
+        assert_eq!(estimation.pricing_mechanism, "legacy");
+        assert_eq!(estimation.gas_price, (20 * GWEI).to_string());
+    }
+
+    #[tokio::test]
+    async fn test_call_returns_data_and_gas_estimate() {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_call" if params[1] == "0x64" => Ok(json!(format!("0x{:064x}", 42))),
+            "eth_estimateGas" if params[1] == "0x64" => Ok(quantity(24_000)),
+            _ => Err(format!("unexpected call {} {}", method, params)),
+        });
+        let service = mock_service(&transport);
+
+        let result = service
+            .call(CallInput {
+                transaction: TransactionInput {
+                    block: Some("100".into()),
+                    ..mock_tx()
+                },
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result.return_data, format!("0x{:064x}", 42));
+        assert_eq!(result.gas_estimate.as_deref(), Some("24000"));
+        assert_eq!(result.block, "0x64");
+    }
+
+    #[tokio::test]
+    async fn test_call_without_gas_estimate_or_reverting() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_call" => Ok(json!("0x")),
+            _ => Err("method not supported".into()),
+        });
+        let service = mock_service(&transport);
+        let result = service
+            .call(CallInput {
+                transaction: TransactionInput {
+                    block: None,
+                    ..mock_tx()
+                },
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.return_data, "0x");
+        assert_eq!(result.gas_estimate, None);
+
+        let transport = MockTransport::new(|_, _| Err("execution reverted: paused".into()));
+        let service = mock_service(&transport);
+        let result = service
+            .call(CallInput {
+                transaction: TransactionInput {
+                    block: Some("latest".into()),
+                    ..mock_tx()
+                },
+            })
+            .await;
+        assert!(
+            matches!(result, Err(Error::GasEstimation(failure)) if failure.message.contains("paused"))
+        );
+
+        let result = service
+            .call(CallInput {
+                transaction: TransactionInput {
+                    block: Some("yesterday".into()),
+                    ..mock_tx()
+                },
+            })
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_fee_components() {
+        // 30 gwei base fee
+        let service = mock_service(&fee_market_transport());
+
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "eip1559");
+        assert_eq!(estimation.pricing_mechanism, "eip1559");
+        assert_eq!(estimation.base_fee_per_gas, Some((30 * GWEI).to_string()));
+        assert_eq!(
+            estimation.max_priority_fee_per_gas,
+            Some((2 * GWEI).to_string())
+        );
+        assert_eq!(estimation.max_fee_per_gas, Some((62 * GWEI).to_string()));
+
+        // A cap sent by the client is kept as is
+        tx.max_fee_per_gas = Some((40 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.max_fee_per_gas, Some((40 * GWEI).to_string()));
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "legacy");
+        assert_eq!(estimation.pricing_mechanism, "legacy");
+        assert_eq!(estimation.max_priority_fee_per_gas, None);
+        assert_eq!(estimation.max_fee_per_gas, None);
+    }
+
+    #[tokio::test]
+    async fn test_gas_price_agrees_with_fee_components() {
+        let service = mock_service(&fee_market_transport());
+        let field = |value: &Option<String>| value.as_deref().unwrap().parse::<u128>().unwrap();
+
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+        for max_fee in [None, Some(40 * GWEI), Some(31 * GWEI)] {
+            tx.max_fee_per_gas = max_fee.map(|fee| fee.to_string());
+            let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+
+            let base_fee = field(&estimation.base_fee_per_gas);
+            let priority_fee = field(&estimation.max_priority_fee_per_gas);
+            let max_fee = field(&estimation.max_fee_per_gas);
+            let gas_price: u128 = estimation.gas_price.parse().unwrap();
+            let gas_limit: u128 = estimation.gas_limit.parse().unwrap();
+            assert_eq!(gas_price, (base_fee + priority_fee).min(max_fee));
+            assert_eq!(
+                estimation.estimated_cost_wei,
+                (gas_price * gas_limit).to_string()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_gas_price_follows_base_fee() {
+        // 30 gwei base fee, the node suggests 31 gwei
+        let service = mock_service(&fee_market_transport());
+
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((5 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+        assert_eq!(estimation.gas_price, (35 * GWEI).to_string());
+
+        // Never more than the max fee
+        tx.max_fee_per_gas = Some((33 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.gas_price, (33 * GWEI).to_string());
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_fee_tiers() {
+        // 30 gwei base fee
+        let service = mock_service(&fee_market_transport());
+
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+        let tiers = service.estimate_gas(tx).await.unwrap().tiers.unwrap();
+        let fees = |tier: &FeeTier| {
+            (
+                tier.max_priority_fee_per_gas.clone(),
+                tier.max_fee_per_gas.clone(),
+                tier.estimated_cost_wei.clone(),
+            )
+        };
+        assert_eq!(
+            fees(&tiers.slow),
+            (
+                GWEI.to_string(),
+                (61 * GWEI).to_string(),
+                (61 * GWEI * 50_000).to_string()
+            )
+        );
+        assert_eq!(
+            fees(&tiers.average),
+            (
+                (2 * GWEI).to_string(),
+                (62 * GWEI).to_string(),
+                (62 * GWEI * 50_000).to_string()
+            )
+        );
+        assert_eq!(
+            fees(&tiers.fast),
+            (
+                (5 * GWEI).to_string(),
+                (65 * GWEI).to_string(),
+                (65 * GWEI * 50_000).to_string()
+            )
+        );
+
+        // Legacy estimates carry no tiers
+        assert!(service
+            .estimate_gas(mock_tx())
+            .await
+            .unwrap()
+            .tiers
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fee_tiers_omitted_without_fee_history() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(50_000)),
+            "eth_gasPrice" => Ok(quantity(31 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            "eth_feeHistory" => Err("eth_feeHistory is not supported".into()),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+
+        let estimation = mock_service(&transport).estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.max_fee_per_gas, Some((62 * GWEI).to_string()));
+        assert!(estimation.tiers.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fee_history_failure_only_drops_tiers() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(50_000)),
+            "eth_gasPrice" => Ok(quantity(31 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            "eth_feeHistory" => Err("internal error".into()),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let mut tx = mock_tx();
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+
+        let estimation = mock_service(&transport).estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.gas_price, (32 * GWEI).to_string());
+        assert!(estimation.tiers.is_none());
+        assert_eq!(estimation.warnings.len(), 1);
+        assert!(estimation.warnings[0].starts_with("Fee tiers unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_excessive_priority_fee_warns() {
+        // 30 gwei base fee
+        let service = mock_service(&fee_market_transport());
+
+        let mut tx = mock_tx();
+        tx.max_fee_per_gas = Some((200 * GWEI).to_string());
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+        assert!(estimation.warnings.is_empty());
+
+        tx.max_priority_fee_per_gas = Some((100 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(
+            estimation.warnings,
+            vec!["max_priority_fee_per_gas of 100000000000 wei is more than 1 times the base fee of 30000000000 wei, consider at most 30000000000 wei"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_fee_below_priority_fee_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let transport = MockTransport::new(move |method, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(format!("unexpected method {}", method))
+        });
+        let service = mock_service(&transport);
+
+        let mut tx = mock_tx();
+        tx.max_fee_per_gas = Some(GWEI.to_string());
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+        let error = service.estimate_gas(tx.clone()).await.unwrap_err();
+        assert!(
+            matches!(&error, Error::InvalidInput(msg) if msg == "max_fee_per_gas must be >= max_priority_fee_per_gas"),
+            "{:?}",
+            error
+        );
+        // Caught before reaching the node
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Equal fees are valid
+        tx.max_fee_per_gas = Some((2 * GWEI).to_string());
+        let service = mock_service(&fee_market_transport());
+        service.estimate_gas(tx).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gas_search_cap_is_forwarded() {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_estimateGas" => match params[0]["gas"].as_str() {
+                Some("0xea60") => Ok(quantity(50_000)),
+                Some("0x9c40") => Err("gas required exceeds allowance (40000)".into()),
+                other => Err(format!("unexpected gas {:?}", other)),
+            },
+            "eth_gasPrice" => Ok(quantity(31 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let mut tx = mock_tx();
+        tx.gas_search_cap = Some("60000".into());
+        let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+        assert_eq!(estimation.gas_limit, "50000");
+
+        tx.gas_search_cap = Some("40000".into());
+        let result = service.estimate_gas(tx).await;
+        assert!(
+            matches!(result, Err(Error::GasEstimation(failure)) if failure.message == "Transaction needs more gas than gas_search_cap of 40000")
+        );
+    }
+
+    fn service_with_default_tx_type(
+        transport: &MockTransport,
+        default_tx_type: DefaultTxType,
+    ) -> EthereumService {
+        let config = AppConfig {
+            default_tx_type,
+            ..Default::default()
+        };
+        EthereumService::with_provider(transport.provider(), &config)
+    }
+
+    #[tokio::test]
+    async fn test_default_tx_type_legacy() {
+        let service = service_with_default_tx_type(&fee_market_transport(), DefaultTxType::Legacy);
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "legacy");
+    }
+
+    #[tokio::test]
+    async fn test_default_tx_type_eip1559() {
+        let service = service_with_default_tx_type(&fee_market_transport(), DefaultTxType::Eip1559);
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "eip1559");
+
+        // Explicit fee fields still decide
+        let mut tx = mock_tx();
+        tx.gas_price = Some((40 * GWEI).to_string());
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "legacy");
+    }
+
+    #[tokio::test]
+    async fn test_default_tx_type_auto_follows_base_fee() {
+        let service = service_with_default_tx_type(&fee_market_transport(), DefaultTxType::Auto);
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "eip1559");
+
+        let legacy_chain = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(21_000)),
+            "eth_gasPrice" => Ok(quantity(20 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = service_with_default_tx_type(&legacy_chain, DefaultTxType::Auto);
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.type_of_transaction, "legacy");
+    }
+
+    #[tokio::test]
+    async fn test_gas_estimate_samples_are_aggregated() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_estimateGas" => {
+                let samples = [40_000, 52_000, 45_000];
+                Ok(quantity(
+                    samples[counter.fetch_add(1, Ordering::SeqCst) % 3],
+                ))
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let transaction = TransactionRequest::default();
+
+        for (aggregation, expected) in [
+            (GasSampleAggregation::Max, 52_000),
+            (GasSampleAggregation::Median, 45_000),
+        ] {
+            let config = AppConfig {
+                gas_estimate_samples: 3,
+                gas_sample_aggregation: aggregation,
+                ..Default::default()
+            };
+            let service = EthereumService::with_provider(transport.provider(), &config);
+            assert_eq!(
+                service
+                    .estimate_gas_limit(&transaction, BlockNumberOrTag::Latest)
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn test_confidence_from_sample_spread() {
+        assert_eq!(Confidence::from_samples(&[50_000]), None);
+        assert_eq!(
+            Confidence::from_samples(&[50_000, 50_000, 50_300]),
+            Some(Confidence::High)
+        );
+        assert_eq!(
+            Confidence::from_samples(&[46_000, 50_000, 48_000]),
+            Some(Confidence::Medium)
+        );
+        assert_eq!(
+            Confidence::from_samples(&[30_000, 50_000, 45_000]),
+            Some(Confidence::Low)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_reports_sample_confidence() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_estimateGas" => {
+                let samples = [40_000, 52_000, 45_000];
+                Ok(quantity(samples[calls.fetch_add(1, Ordering::SeqCst) % 3]))
+            }
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            gas_estimate_samples: 3,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+
+        let estimation = service.estimate_gas(mock_tx()).await.unwrap();
+        assert_eq!(estimation.gas_limit, "52000");
+        assert_eq!(estimation.confidence, Some(Confidence::Low));
+
+        let estimation = mock_service(&transport)
+            .estimate_gas(mock_tx())
+            .await
+            .unwrap();
+        assert_eq!(estimation.confidence, None);
+    }
+
+    #[tokio::test]
+    async fn test_binary_search_converges_to_minimum_gas() {
+        let transport = MockTransport::new(|method, params| match method {
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            "eth_call" => {
+                let gas = params[0]["gas"].as_str().unwrap().trim_start_matches("0x");
+                if u64::from_str_radix(gas, 16).unwrap() >= 46_875 {
+                    Ok(serde_json::json!("0x"))
+                } else {
+                    Err("out of gas".into())
+                }
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            gas_estimation_method: GasEstimationMethod::BinarySearch,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let transaction = service.build_transaction_request(&mock_tx()).unwrap();
+
+        assert_eq!(
+            service
+                .estimate_gas_limit(&transaction, BlockNumberOrTag::Latest)
+                .await
+                .unwrap(),
+            46_875
+        );
+    }
+
+    #[tokio::test]
+    async fn test_binary_search_starts_at_intrinsic_gas() {
+        // Selector and one 32-byte argument: 4 + 31 non-zero bytes, 1 zero byte
+        let data = format!("0xa9059cbb{}00", "11".repeat(31));
+        let intrinsic = 21_000 + 35 * 16 + 4;
+        let transport = MockTransport::new(move |method, params| match method {
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            "eth_call" => {
+                let gas = params[0]["gas"].as_str().unwrap().trim_start_matches("0x");
+                match u64::from_str_radix(gas, 16).unwrap() {
+                    gas if gas < intrinsic => Err("intrinsic gas too low".into()),
+                    gas if gas < 51_234 => Err("out of gas".into()),
+                    _ => Ok(serde_json::json!("0x")),
+                }
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let config = AppConfig {
+            gas_estimation_method: GasEstimationMethod::BinarySearch,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let mut tx = mock_tx();
+        tx.data = Some(data);
+        let transaction = service.build_transaction_request(&tx).unwrap();
+
+        assert_eq!(
+            service
+                .estimate_gas_limit(&transaction, BlockNumberOrTag::Latest)
+                .await
+                .unwrap(),
+            51_234
+        );
+
+        // A cap below the intrinsic gas leaves the node to reject the transaction
+        let capped = TransactionRequest {
+            gas: Some(20_000),
+            ..transaction
+        };
+        let result = service
+            .estimate_gas_limit(&capped, BlockNumberOrTag::Latest)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_binary_search_reports_revert() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err("execution reverted".into()),
+        });
+        let config = AppConfig {
+            gas_estimation_method: GasEstimationMethod::BinarySearch,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let transaction = service.build_transaction_request(&mock_tx()).unwrap();
+
+        let result = service
+            .estimate_gas_limit(&transaction, BlockNumberOrTag::Latest)
+            .await;
+        assert!(matches!(result, Err(Error::GasEstimation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_binary_search_fails_when_node_drops_out() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            "eth_call" => Ok(serde_json::json!("0x")),
+            _ => Err(format!("unexpected method {}", method)),
+        })
+        .disconnect_after("eth_call", 3);
+        let config = AppConfig {
+            gas_estimation_method: GasEstimationMethod::BinarySearch,
+            ..Default::default()
+        };
+        let service = EthereumService::with_provider(transport.provider(), &config);
+        let transaction = service.build_transaction_request(&mock_tx()).unwrap();
+
+        let result = service
+            .estimate_gas_limit(&transaction, BlockNumberOrTag::Latest)
+            .await;
+        assert!(matches!(result, Err(Error::Provider(_))));
+    }
+
+    #[tokio::test]
+    async fn test_gas_split_into_intrinsic_and_execution() {
+        // 50k gas estimated
+        let service = mock_service(&fee_market_transport());
+
+        let mut tx = mock_tx();
+        // Selector and one 32-byte argument: 4 + 31 non-zero bytes, 1 zero byte
+        tx.data = Some(format!("0xa9059cbb{}00", "11".repeat(31)));
+        let estimation = service.estimate_gas(tx.clone()).await.unwrap();
+        let intrinsic = 21_000 + 35 * 16 + 4;
+        assert_eq!(estimation.intrinsic_gas, intrinsic.to_string());
+        assert_eq!(estimation.execution_gas, (50_000 - intrinsic).to_string());
+        assert!(estimation.warnings.is_empty());
+
+        // Creations add 32000 plus 2 per init code word
+        tx.to = Recipient::Create;
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        let intrinsic = intrinsic + 32_000 + 2 * 2;
+        assert_eq!(estimation.intrinsic_gas, intrinsic.to_string());
+        assert_eq!(estimation.execution_gas, "0");
+        assert_eq!(
+            estimation.warnings,
+            vec![format!(
+                "Node estimated 50000 gas, below the {} intrinsic gas of the transaction",
+                intrinsic
+            )]
+        );
+    }
+
+    /// Node suggesting one warm slot of the recipient, which brings a 60k estimate down
+    /// to 55k
+    fn access_list_transport() -> MockTransport {
+        MockTransport::new(|method, params| match method {
+            "eth_createAccessList" => Ok(json!({
+                "accessList": [{
+                    "address": "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+                    "storageKeys": [format!("0x{:064x}", 1)]
+                }],
+                "gasUsed": quantity(55_000)
+            })),
+            "eth_estimateGas" if params[0].get("accessList").is_some() => Ok(quantity(55_000)),
+            "eth_estimateGas" => Ok(quantity(60_000)),
+            "eth_gasPrice" => Ok(quantity(20 * GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_optimize_applies_cheaper_access_list() {
+        let service = mock_service(&access_list_transport());
+
+        let estimation = service.estimate_gas_optimized(mock_tx()).await.unwrap();
+        let optimization = estimation.access_list_optimization.unwrap();
+        assert!(optimization.applied);
+        assert_eq!(optimization.gas_without_access_list, "60000");
+        assert_eq!(optimization.gas_with_access_list, "55000");
+        assert_eq!(optimization.saved_gas, "5000");
+        assert_eq!(optimization.access_list.len(), 1);
+        assert_eq!(estimation.gas_limit, "55000");
+        // The access list is part of the intrinsic gas
+        assert_eq!(
+            estimation.intrinsic_gas,
+            (21_000 + 2_400 + 1_900).to_string()
+        );
+
+        // A sent access list is used as is
+        let tx = TransactionInput {
+            access_list: Some(optimization.access_list),
+            ..mock_tx()
+        };
+        let estimation = service.estimate_gas(tx).await.unwrap();
+        assert_eq!(estimation.gas_limit, "55000");
+        assert_eq!(estimation.access_list_optimization, None);
+    }
+
+    #[tokio::test]
+    async fn test_access_list_impact_per_slot() {
+        let (hot, cold) = (format!("0x{:064x}", 1), format!("0x{:064x}", 2));
+        let slot = hot.clone();
+        // Each listed key costs 1900 gas up front, and saves 2000 when read. The
+        // contract's account saves 2500 of the 2400 it costs.
+        let transport = MockTransport::new(move |method, params| match method {
+            "eth_estimateGas" => {
+                let mut gas = 30_000;
+                if let Some(entry) = params[0]["accessList"].get(0) {
+                    let keys = entry["storageKeys"].as_array().unwrap();
+                    gas = gas + 2_400 + 1_900 * keys.len() as u128 - 2_500;
+                    if keys.iter().any(|key| *key == slot) {
+                        gas -= 2_000;
+                    }
+                }
+                Ok(quantity(gas))
+            }
+            "eth_gasPrice" => Ok(quantity(GWEI)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, None, 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+        let contract = "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5";
+        let tx = TransactionInput {
+            access_list: Some(
+                serde_json::from_value(
+                    json!([{ "address": contract, "storageKeys": [hot, cold] }]),
+                )
+                .unwrap(),
+            ),
+            ..mock_tx()
+        };
+
+        let estimation = service.estimate_access_list_impact(tx).await.unwrap();
+        assert_eq!(estimation.gas_limit, "31700");
+        let impact = estimation.access_list_impact.unwrap();
+        assert_eq!(impact.gas_without_access_list, "30000");
+        assert_eq!(impact.gas_with_access_list, "31700");
+        assert_eq!(impact.saved_gas, "-1700");
+        let slots = impact.slots.unwrap();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].address, contract);
+        // The warm slot read pays for itself, the unread one is overhead
+        assert_eq!(
+            (slots[0].storage_key.as_str(), slots[0].saved_gas.as_str()),
+            (hot.as_str(), "100")
+        );
+        assert_eq!(slots[1].saved_gas, "-1900");
+
+        let error = service
+            .estimate_access_list_impact(mock_tx())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidInput(_)), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_optimize_without_create_access_list() {
+        let service = mock_service(&MockTransport::new(|method, _| match method {
+            "eth_createAccessList" => {
+                Err("the method eth_createAccessList does not exist/is not available".into())
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        }));
+
+        let error = service.estimate_gas_optimized(mock_tx()).await.unwrap_err();
+        assert!(
+            matches!(&error, Error::Unsupported(msg) if msg.contains("eth_createAccessList")),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_gas_refund_capped_at_a_fifth() {
+        let trace = |gas: u64, refund: u64| CallTrace {
+            gas,
+            struct_logs: vec![StructLog { refund: 0 }, StructLog { refund }],
+        };
+        assert_eq!(trace(26_000, 4_800).gas_refund(), 4_800);
+        // 40000 used after a 10000 refund, the most 50000 used before it allows
+        assert_eq!(trace(40_000, 20_000).gas_refund(), 10_000);
+        assert_eq!(
+            CallTrace {
+                gas: 21_000,
+                struct_logs: Vec::new()
+            }
+            .gas_refund(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_numbers_parse_as_hex_or_decimal() {
+        assert_eq!(
+            parse_u256("0x16345785d8a0000").unwrap(),
+            parse_u256("100000000000000000").unwrap()
+        );
+        assert_eq!(parse_u128("0x77359400").unwrap(), 2_000_000_000);
+        assert_eq!(parse_u128("2000000000").unwrap(), 2_000_000_000);
+        assert_eq!(parse_u64("0x5208").unwrap(), 21_000);
+        assert!(parse_u128("0xZZ").is_err());
+    }
+
+    #[test]
+    fn test_next_base_fee() {
+        assert_eq!(next_base_fee(8 * GWEI, 1.0), 9 * GWEI);
+        assert_eq!(next_base_fee(8 * GWEI, 0.0), 7 * GWEI);
+        assert_eq!(next_base_fee(8 * GWEI, 0.5), 8 * GWEI);
+        // Any block over target raises the base fee
+        assert_eq!(next_base_fee(7, 0.500001), 8);
+    }
+
+    #[test]
+    fn test_next_base_fee_bounds() {
+        assert_eq!(next_base_fee_bounds(8 * GWEI), (7 * GWEI, 9 * GWEI));
+        // The current block's fullness sets the base fee the bounds are taken around
+        let base_fee = next_base_fee(8 * GWEI, 0.75);
+        assert_eq!(base_fee, 8_500_000_000);
+        assert_eq!(
+            next_base_fee_bounds(base_fee),
+            (7_437_500_000, 9_562_500_000)
+        );
+        assert_eq!(next_base_fee_bounds(0), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_fee_for_blocks_projects_full_blocks() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_feeHistory" => Ok(fee_history(
+                &[8 * GWEI, 8 * GWEI, 8 * GWEI, 8 * GWEI],
+                &[1.0, 1.0, 1.0],
+                2 * GWEI,
+            )),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let fees = mock_service(&transport).fee_for_blocks(3).await.unwrap();
+
+        assert_eq!(
+            fees.projected_base_fees,
+            vec!["8000000000", "9000000000", "10125000000"]
+        );
+        assert_eq!(fees.next_base_fee_min, (7 * GWEI).to_string());
+        assert_eq!(fees.next_base_fee_max, (9 * GWEI).to_string());
+        assert_eq!(fees.max_priority_fee_per_gas, (2 * GWEI).to_string());
+        assert_eq!(
+            fees.max_fee_per_gas,
+            (10_125_000_000 + 2 * GWEI).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fee_for_blocks_without_fee_history() {
+        let fee_history_calls = Arc::new(AtomicUsize::new(0));
+        let counter = fee_history_calls.clone();
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_feeHistory" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err("the method eth_feeHistory does not exist/is not available".into())
+            }
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(8 * GWEI), 30_000_000, 30_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        for _ in 0..2 {
+            let fees = service.fee_for_blocks(2).await.unwrap();
+            // A full latest block, projected to stay full, and the default priority fee
+            assert_eq!(fees.projected_base_fees, vec!["9000000000", "10125000000"]);
+            assert_eq!(
+                fees.max_priority_fee_per_gas,
+                AppConfig::default().default_priority_fee.to_string()
+            );
+        }
+        assert_eq!(fee_history_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fee_history_support_probed_again() {
+        let supported = Arc::new(AtomicUsize::new(0));
+        let fee_history_calls = Arc::new(AtomicUsize::new(0));
+        let (node_supports, counter) = (supported.clone(), fee_history_calls.clone());
+        let transport = MockTransport::new(move |method, _| match method {
+            "eth_feeHistory" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                if node_supports.load(Ordering::SeqCst) == 0 {
+                    return Err("the method eth_feeHistory does not exist/is not available".into());
+                }
+                Ok(fee_history_rewards(&[GWEI; 2], &[0.5], &[GWEI]))
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_none());
+        supported.store(1, Ordering::SeqCst);
+        tokio::time::advance(FEE_HISTORY_REPROBE_INTERVAL / 2).await;
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_none());
+        assert_eq!(fee_history_calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(FEE_HISTORY_REPROBE_INTERVAL / 2).await;
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_some());
+        assert_eq!(fee_history_calls.load(Ordering::SeqCst), 2);
+
+        // Also asked again right away once told to
+        supported.store(0, Ordering::SeqCst);
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_none());
+        supported.store(1, Ordering::SeqCst);
+        assert_eq!(service.clear_caches().await, 1);
+        assert!(service.fee_history(&[50.0]).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fee_history_errors_other_than_unsupported_propagate() {
+        let transport = MockTransport::new(|_, _| Err("header not found".into()));
+        let service = mock_service(&transport);
+
+        assert!(service.fee_for_blocks(2).await.is_err());
+        assert!(service.fee_history_unsupported.lock().unwrap().is_none());
+    }
+
+    /// Signed legacy transaction as found in a full block
+    fn mock_block_tx(block: u64, nonce: u64, to: &str, input: &str) -> Value {
+        json!({
+            "type": "0x0",
+            "chainId": "0x1",
+            "nonce": format!("0x{:x}", nonce),
+            "gasPrice": "0x1",
+            "gas": "0x30000",
+            "to": to,
+            "value": "0x0",
+            "input": input,
+            "r": "0x1",
+            "s": "0x1",
+            "v": "0x25",
+            "hash": format!("0x{:064x}", nonce),
+            "blockHash": format!("0x{:064x}", block),
+            "blockNumber": format!("0x{:x}", block),
+            "transactionIndex": "0x0",
+            "from": "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+        })
+    }
+
+    fn mock_receipt(hash: &str, gas_used: u64) -> Value {
+        json!({
+            "type": "0x0",
+            "status": "0x1",
+            "cumulativeGasUsed": format!("0x{:x}", gas_used),
+            "logs": [],
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "transactionHash": hash,
+            "transactionIndex": "0x0",
+            "blockHash": format!("0x{:064x}", 0),
+            "blockNumber": "0x1",
+            "gasUsed": format!("0x{:x}", gas_used),
+            "effectiveGasPrice": "0x1",
+            "from": "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+            "to": "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+            "contractAddress": null,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_method_gas_averages_matching_receipts() {
+        const TOKEN: &str = "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5";
+        const OTHER: &str = "0x742d35cc6634c0532925a3b844bc454e4438f44e";
+        const TRANSFER: &str =
+            "0xa9059cbb0000000000000000000000000000000000000000000000000000000000000001";
+        const APPROVE: &str =
+            "0x095ea7b30000000000000000000000000000000000000000000000000000000000000001";
+
+        // Two transfers to the token, plus an approve and a transfer elsewhere to skip
+        let txs = [
+            (99, mock_block_tx(99, 0, TOKEN, TRANSFER), 50_000),
+            (99, mock_block_tx(99, 1, TOKEN, APPROVE), 40_000),
+            (100, mock_block_tx(100, 2, TOKEN, TRANSFER), 36_000),
+            (100, mock_block_tx(100, 3, OTHER, TRANSFER), 21_000),
+        ];
+        let gas_by_hash: HashMap<String, u64> = txs
+            .iter()
+            .map(|(_, tx, gas)| {
+                let tx: alloy_rpc_types::Transaction = serde_json::from_value(tx.clone()).unwrap();
+                (tx.tx_hash().to_string(), *gas)
+            })
+            .collect();
+        let block_calls = Arc::new(AtomicUsize::new(0));
+        let counter = block_calls.clone();
+        let transport = MockTransport::new(move |method, params| match method {
+            "eth_blockNumber" => Ok(quantity(100)),
+            "eth_getBlockByNumber" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let number = params[0].as_str().unwrap().trim_start_matches("0x");
+                let number = u64::from_str_radix(number, 16).unwrap();
+                let mut block = mock_block(number, Some(GWEI), 30_000_000, 15_000_000);
+                block["transactions"] = txs
+                    .iter()
+                    .filter(|(block, _, _)| *block == number)
+                    .map(|(_, tx, _)| tx.clone())
+                    .collect();
+                Ok(block)
+            }
+            "eth_getTransactionReceipt" => {
+                let hash = params[0].as_str().unwrap();
+                Ok(mock_receipt(hash, gas_by_hash[hash]))
+            }
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+
+        let method_gas = service.method_gas(TOKEN, "0xa9059cbb", 2).await.unwrap();
+        assert_eq!(method_gas.from_block, 99);
+        assert_eq!(method_gas.to_block, 100);
+        assert_eq!(method_gas.transactions, 2);
+        assert_eq!(method_gas.average_gas_used.as_deref(), Some("43000"));
+        assert_eq!(method_gas.median_gas_used.as_deref(), Some("50000"));
+        assert_eq!(block_calls.load(Ordering::SeqCst), 2);
+
+        // Same head block: served from the cache
+        service.method_gas(TOKEN, "0xa9059cbb", 2).await.unwrap();
+        assert_eq!(block_calls.load(Ordering::SeqCst), 2);
+
+        let result = service.method_gas(TOKEN, "0xa9059c", 2).await;
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_bump_fee_rounds_up() {
+        assert_eq!(bump_fee(100), 110);
+        assert_eq!(bump_fee(15), 17);
+        assert_eq!(bump_fee(0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_replacement_eip1559_bumps_fees() {
+        let service = mock_service(&fee_market_transport());
+        let mut tx = mock_tx();
+        tx.max_fee_per_gas = Some((40 * GWEI).to_string());
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+
+        let estimation = service
+            .estimate_replacement(ReplacementInput {
+                transaction: tx,
+                nonce: 7,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(estimation.nonce, 7);
+        assert_eq!(estimation.base_fee_per_gas, Some((30 * GWEI).to_string()));
+        assert_eq!(estimation.speed_up.gas_limit, "50000");
+        assert_eq!(
+            estimation.speed_up.max_fee_per_gas,
+            Some((44 * GWEI).to_string())
+        );
+        assert_eq!(
+            estimation.speed_up.max_priority_fee_per_gas,
+            Some((2_200_000_000u128).to_string())
+        );
+        assert_eq!(estimation.cancel.gas_limit, "21000");
+        assert_eq!(
+            estimation.cancel.estimated_cost_wei,
+            (44 * GWEI * 21_000).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replacement_eip1559_without_gas_price() {
+        let transport = MockTransport::new(|method, _| match method {
+            "eth_estimateGas" => Ok(quantity(50_000)),
+            "eth_getBlockByNumber" => Ok(mock_block(100, Some(30 * GWEI), 30_000_000, 15_000_000)),
+            _ => Err(format!("unexpected method {}", method)),
+        });
+        let service = mock_service(&transport);
+        let mut tx = mock_tx();
+        tx.max_fee_per_gas = Some((40 * GWEI).to_string());
+        tx.max_priority_fee_per_gas = Some((2 * GWEI).to_string());
+
+        let estimation = service
+            .estimate_replacement(ReplacementInput {
+                transaction: tx,
+                nonce: 7,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            estimation.speed_up.max_fee_per_gas,
+            Some((44 * GWEI).to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replacement_raised_to_current_base_fee() {
+        let service = mock_service(&fee_market_transport());
+        let mut tx = mock_tx();
+        tx.max_fee_per_gas = Some((20 * GWEI).to_string());
+        tx.max_priority_fee_per_gas = Some(GWEI.to_string());
+
+        let estimation = service
+            .estimate_replacement(ReplacementInput {
+                transaction: tx,
+                nonce: 7,
+            })
+            .await
+            .unwrap();
+
+        // 30 gwei base fee + 1.1 gwei bumped tip beats the 22 gwei bumped cap
+        assert_eq!(
+            estimation.speed_up.max_fee_per_gas,
+            Some((31_100_000_000u128).to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replacement_legacy_uses_current_gas_price() {
+        let service = mock_service(&fee_market_transport());
+        let mut tx = mock_tx();
+        tx.gas_price = Some((10 * GWEI).to_string());
+
+        let estimation = service
+            .estimate_replacement(ReplacementInput {
+                transaction: tx,
+                nonce: 3,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(estimation.speed_up.gas_price, Some((31 * GWEI).to_string()));
+        assert_eq!(estimation.speed_up.max_fee_per_gas, None);
+    }
+
+    #[tokio::test]
+    async fn test_replacement_requires_original_fees() {
+        let service = mock_service(&fee_market_transport());
+        let result = service
+            .estimate_replacement(ReplacementInput {
+                transaction: mock_tx(),
+                nonce: 3,
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
     /// Crée une configuration de test.
     fn create_test_config() -> AppConfig {
         AppConfig {
-            ethereum_rpc_url: "https://eth.llamarpc.com".to_string(),
+            ethereum_rpc_urls: vec!["https://eth.llamarpc.com".to_string()],
             cache_duration: Duration::from_secs(15),
             host: IpAddr::from_str("127.0.0.1").unwrap(),
             port: 8080,
             log_level: "debug".to_string(),
+            ..Default::default()
         }
     }
 
@@ -178,13 +3409,18 @@ mod tests {
 
         let tx = TransactionInput {
             from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string(),
-            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".to_string(),
+            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".into(),
             value: Some("1000000000000000".to_string()), // 0.001 ETH
             data: Some("0x".to_string()),
             gas_price: None,
             max_fee_per_gas: None,
             max_priority_fee_per_gas: None,
-            _nonce: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
         };
 
         let result = service.estimate_gas(tx).await;
@@ -198,19 +3434,24 @@ mod tests {
 
         let tx = TransactionInput {
             from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string(),
-            to: "0xdAC17F958D2ee523a2206206994597C13D831ec7".to_string(), // USDT contract
+            to: "0xdAC17F958D2ee523a2206206994597C13D831ec7".into(), // USDT contract
             value: Some("1000000000000000".to_string()),
             data: Some("0x".to_string()),
             gas_price: None,
             max_fee_per_gas: None,
             max_priority_fee_per_gas: None,
-            _nonce: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
         };
 
         let result = service.estimate_gas(tx).await;
         assert!(result.is_err());
-        if let Err(Error::GasEstimation(msg)) = result {
-            assert!(msg.contains("execution reverted"));
+        if let Err(Error::GasEstimation(failure)) = result {
+            assert!(failure.message.contains("execution reverted"));
         } else {
             panic!("Expected GasEstimation error");
         }
@@ -223,13 +3464,18 @@ mod tests {
 
         let tx = TransactionInput {
             from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string(),
-            to: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".to_string(), // USDC contract
+            to: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".into(), // USDC contract
             value: Some("0".to_string()),
             data: Some("0x095ea7b3000000000000000000000000dac17f958d2ee523a2206206994597c13d831ec700000000000000000000000000000000000000000000000000000000000003e8".to_string()),
             gas_price: None,
             max_fee_per_gas: None,
             max_priority_fee_per_gas: None,
-            _nonce: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
         };
 
         let result = service.estimate_gas(tx).await;
@@ -243,13 +3489,18 @@ mod tests {
 
         let tx = TransactionInput {
             from: "invalid_address".to_string(),
-            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".to_string(),
+            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".into(),
             value: Some("1000000000000000".to_string()),
             data: Some("0x".to_string()),
             gas_price: None,
             max_fee_per_gas: None,
             max_priority_fee_per_gas: None,
-            _nonce: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
         };
 
         let result = service.estimate_gas(tx).await;
@@ -268,13 +3519,18 @@ mod tests {
 
         let tx = TransactionInput {
             from: "0x742d35Cc6634C0532925a3b844Bc454e4438f44e".to_string(),
-            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".to_string(),
+            to: "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5".into(),
             value: Some("1000000000000000".to_string()),
             data: Some("0x".to_string()),
             gas_price: None,
             max_fee_per_gas: Some("50000000000".to_string()), // 50 Gwei
             max_priority_fee_per_gas: Some("2000000000".to_string()), // 2 Gwei
-            _nonce: None,
+            nonce: None,
+            gas_search_cap: None,
+            fee_unit: FeeUnit::Wei,
+            access_list: None,
+            chain_id: None,
+            block: None,
         };
 
         let result = service.estimate_gas(tx).await;
@@ -0,0 +1,81 @@
+use crate::models::transaction::{CalldataAnalysis, Compressibility};
+use crate::services::ethereum::{NON_ZERO_BYTE_GAS, ZERO_BYTE_GAS};
+
+/// OP Mainnet, Base, Zora, Mode and the OP and Base Sepolia testnets
+const OP_STACK_CHAIN_IDS: [u64; 6] = [10, 8453, 7_777_777, 34443, 11_155_420, 84532];
+/// Shortest run of identical bytes counted as repetitive
+const MIN_RUN: usize = 4;
+
+/// Whether the chain charges an L1 data fee on calldata the OP-stack way
+pub fn is_op_stack(chain_id: u64) -> bool {
+    OP_STACK_CHAIN_IDS.contains(&chain_id)
+}
+
+/// Zero and non-zero bytes of `data`, its uncompressed L1 gas, and how well it is
+/// likely to compress. Repeated bytes stand in for what the batch compressor removes.
+pub fn analyze_calldata(data: &[u8]) -> CalldataAnalysis {
+    let zero_bytes = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zero_bytes = data.len() as u64 - zero_bytes;
+    let repetitive_bytes = data
+        .chunk_by(|a, b| a == b)
+        .filter(|run| run.len() >= MIN_RUN)
+        .map(|run| run.len() as u64)
+        .sum::<u64>();
+
+    let share = repetitive_bytes * 100 / (data.len() as u64).max(1);
+    let (compressibility, hint) = match share {
+        50.. => (
+            Compressibility::High,
+            "Mostly repeated bytes such as ABI padding, which compress well: the L1 data \
+             fee is well below the uncompressed cost",
+        ),
+        20.. => (
+            Compressibility::Medium,
+            "Partly compressible: keeping zero bytes together, e.g. small values next to \
+             each other, lets them compress better",
+        ),
+        _ => (
+            Compressibility::Low,
+            "Dense calldata that compresses poorly: only sending fewer bytes, e.g. packed \
+             arguments, lowers the L1 data fee",
+        ),
+    };
+    CalldataAnalysis {
+        zero_bytes,
+        non_zero_bytes,
+        l1_data_gas: (zero_bytes * ZERO_BYTE_GAS + non_zero_bytes * NON_ZERO_BYTE_GAS).to_string(),
+        repetitive_bytes,
+        compressibility,
+        hint: hint.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_calldata() {
+        // transfer(address,uint256): selector, padded address, padded small amount
+        let mut data = vec![0xa9, 0x05, 0x9c, 0xbb];
+        data.extend([0; 12]);
+        data.extend([0x11; 20]);
+        data.extend([0; 31]);
+        data.push(0x64);
+
+        let analysis = analyze_calldata(&data);
+        assert_eq!(analysis.zero_bytes, 43);
+        assert_eq!(analysis.non_zero_bytes, 25);
+        assert_eq!(analysis.l1_data_gas, (43 * 4 + 25 * 16).to_string());
+        assert_eq!(analysis.repetitive_bytes, 63);
+        assert_eq!(analysis.compressibility, Compressibility::High);
+
+        let dense: Vec<u8> = (1..=64).collect();
+        assert_eq!(
+            analyze_calldata(&dense).compressibility,
+            Compressibility::Low
+        );
+        assert!(is_op_stack(8453));
+        assert!(!is_op_stack(1));
+    }
+}
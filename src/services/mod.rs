@@ -1 +1,4 @@
+pub mod bundler;
 pub mod ethereum;
+pub mod l2;
+pub mod price_feed;
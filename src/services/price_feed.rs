@@ -0,0 +1,117 @@
+use alloy_primitives::U256;
+use alloy_transport_http::reqwest::Client;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::utils::cache::GasPriceCache;
+
+/// Cache key of the ETH/USD price, kept in millionths of a dollar
+const CACHE_KEY: &str = "eth_usd_micros";
+const MICROS_PER_USD: f64 = 1_000_000.0;
+
+/// ETH/USD price from a REST endpoint answering with a JSON number, or an object
+/// with a `price` field holding one (as a number or a string)
+#[derive(Debug, Clone)]
+pub struct PriceFeed {
+    client: Client,
+    url: String,
+}
+
+impl PriceFeed {
+    pub fn new(url: String, timeout: Duration) -> Self {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        Self { client, url }
+    }
+
+    /// Price of one ether in millionths of a dollar, cached in `cache`
+    pub async fn eth_usd_micros(&self, cache: &GasPriceCache) -> eyre::Result<u128> {
+        cache
+            .cached_value(CACHE_KEY, async {
+                let body: Value = self
+                    .client
+                    .get(&self.url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                parse_price(&body).ok_or_else(|| eyre::eyre!("No ETH/USD price in {}", body))
+            })
+            .await
+    }
+
+    /// Like `eth_usd_micros`, `None` when the feed can't be read
+    pub async fn try_eth_usd_micros(&self, cache: &GasPriceCache) -> Option<u128> {
+        self.eth_usd_micros(cache)
+            .await
+            .inspect_err(|e| tracing::warn!("ETH/USD price unavailable: {}", e))
+            .ok()
+    }
+}
+
+/// A positive price in dollars, in millionths of a dollar
+fn parse_price(body: &Value) -> Option<u128> {
+    let price = match body {
+        Value::Object(fields) => fields.get("price")?,
+        price => price,
+    };
+    let dollars = match price {
+        Value::Number(number) => number.as_f64()?,
+        Value::String(text) => text.trim().parse().ok()?,
+        _ => return None,
+    };
+    (dollars.is_finite() && dollars > 0.0).then(|| (dollars * MICROS_PER_USD).round() as u128)
+}
+
+/// Dollars with cents, rounded to the nearest cent
+pub fn format_usd(cost_wei: U256, eth_usd_micros: u128) -> String {
+    // wei per ether times millionths per cent
+    let divisor = U256::from(10u128.pow(22));
+    let cents =
+        (cost_wei.saturating_mul(U256::from(eth_usd_micros)) + divisor / U256::from(2)) / divisor;
+    let hundred = U256::from(100);
+    format!("{}.{:02}", cents / hundred, (cents % hundred).to::<u64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price(&json!(3000.5)), Some(3_000_500_000));
+        assert_eq!(parse_price(&json!({"price": 3000})), Some(3_000_000_000));
+        assert_eq!(
+            parse_price(&json!({"price": "2999.99"})),
+            Some(2_999_990_000)
+        );
+        assert_eq!(parse_price(&json!({"usd": 3000})), None);
+        assert_eq!(parse_price(&json!({"price": -1})), None);
+        assert_eq!(parse_price(&json!({"price": "n/a"})), None);
+    }
+
+    #[test]
+    fn test_format_usd() {
+        // 0.0021 ETH at 3000 USD
+        let cost = U256::from(2_100_000_000_000_000u128);
+        assert_eq!(format_usd(cost, 3_000_000_000), "6.30");
+        assert_eq!(format_usd(U256::from(1u128), 3_000_000_000), "0.00");
+        // 0.001 ETH at 1234.567 USD rounds up to the cent
+        let cost = U256::from(1_000_000_000_000_000u128);
+        assert_eq!(format_usd(cost, 1_234_567_000), "1.23");
+        assert_eq!(format_usd(cost, 1_235_000_000), "1.24");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_feed_has_no_price() {
+        let feed = PriceFeed::new("http://127.0.0.1:1".into(), Duration::from_secs(1));
+        assert_eq!(
+            feed.try_eth_usd_micros(&GasPriceCache::default()).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Mock JSON-RPC transport used by unit tests
+use alloy_json_rpc::{RequestPacket, ResponsePacket};
+use alloy_provider::RootProvider;
+use alloy_rpc_client::RpcClient;
+use alloy_transport::{TransportError, TransportErrorKind, TransportFut};
+use axum::body::{to_bytes, Body, Bytes};
+use axum::http::{Request, Response};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::Service;
+
+use crate::utils::rpc_stats::RpcStatsLayer;
+
+type Handler = dyn Fn(&str, &Value) -> Result<Value, String> + Send + Sync;
+
+/// Transport answering each JSON-RPC call through `handler(method, params)`.
+/// An `Err(message)` is returned to the client as a JSON-RPC error response.
+#[derive(Clone)]
+pub struct MockTransport {
+    handler: Arc<Handler>,
+    delays: Vec<(&'static str, Duration)>,
+    disconnect: Option<(&'static str, usize, Arc<AtomicUsize>)>,
+}
+
+impl MockTransport {
+    pub fn new(
+        handler: impl Fn(&str, &Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            delays: Vec::new(),
+            disconnect: None,
+        }
+    }
+
+    /// Answers calls to `method` only after `delay`, like a slow node
+    pub fn delayed(mut self, method: &'static str, delay: Duration) -> Self {
+        self.delays.push((method, delay));
+        self
+    }
+
+    /// Fails calls to `method` at the transport level once `calls` of them got through,
+    /// like a node dropping the connection
+    pub fn disconnect_after(mut self, method: &'static str, calls: usize) -> Self {
+        self.disconnect = Some((method, calls, Arc::new(AtomicUsize::new(0))));
+        self
+    }
+
+    pub fn provider(&self) -> RootProvider {
+        RootProvider::new(
+            RpcClient::builder()
+                .layer(RpcStatsLayer)
+                .transport(self.clone(), true),
+        )
+    }
+
+    fn respond(&self, request: &Value) -> Value {
+        let method = request["method"].as_str().unwrap_or_default();
+
+        match (self.handler)(method, &request["params"]) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32000, "message": message }
+            }),
+        }
+    }
+}
+
+impl Service<RequestPacket> for MockTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let this = self.clone();
+        Box::pin(async move {
+            let raw = req.serialize().map_err(TransportError::ser_err)?;
+            let request: Value =
+                serde_json::from_str(raw.get()).map_err(TransportError::ser_err)?;
+            let requests = match &request {
+                Value::Array(batch) => batch.iter().collect(),
+                single => vec![single],
+            };
+            let delay = this
+                .delays
+                .iter()
+                .filter(|(method, _)| requests.iter().any(|r| r["method"] == *method))
+                .map(|(_, delay)| *delay)
+                .max();
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            if let Some((method, calls, made)) = &this.disconnect {
+                let matching = requests.iter().filter(|r| r["method"] == *method).count();
+                if matching > 0 && made.fetch_add(matching, Ordering::SeqCst) >= *calls {
+                    return Err(TransportErrorKind::custom_str("connection reset"));
+                }
+            }
+
+            let response = match request {
+                Value::Array(batch) => {
+                    Value::Array(batch.iter().map(|r| this.respond(r)).collect())
+                }
+                single => this.respond(&single),
+            };
+            let text = response.to_string();
+            serde_json::from_str(&text).map_err(|e| TransportError::deser_err(e, text))
+        })
+    }
+}
+
+/// Minimal `eth_getBlockByNumber` result
+pub fn mock_block(number: u64, base_fee: Option<u128>, gas_limit: u64, gas_used: u64) -> Value {
+    let mut block = json!({
+        "hash": format!("0x{:064x}", number),
+        "parentHash": format!("0x{:064x}", number.saturating_sub(1)),
+        "sha3Uncles": format!("0x{:064x}", 0),
+        "miner": format!("0x{:040x}", 0),
+        "stateRoot": format!("0x{:064x}", 0),
+        "transactionsRoot": format!("0x{:064x}", 0),
+        "receiptsRoot": format!("0x{:064x}", 0),
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "difficulty": "0x0",
+        "number": format!("0x{:x}", number),
+        "gasLimit": format!("0x{:x}", gas_limit),
+        "gasUsed": format!("0x{:x}", gas_used),
+        "timestamp": format!("0x{:x}", 1_700_000_000 + number * 12),
+        "extraData": "0x",
+        "mixHash": format!("0x{:064x}", 0),
+        "nonce": "0x0000000000000000",
+        "size": "0x0",
+        "uncles": [],
+        "transactions": []
+    });
+    if let Some(base_fee) = base_fee {
+        block["baseFeePerGas"] = json!(format!("0x{:x}", base_fee));
+    }
+    block
+}
+
+/// `eth_feeHistory` result with a single reward percentile. `base_fees` holds one more
+/// entry than `gas_used_ratios`, the base fee of the pending block.
+pub fn fee_history(base_fees: &[u128], gas_used_ratios: &[f64], reward: u128) -> Value {
+    fee_history_rewards(base_fees, gas_used_ratios, &[reward])
+}
+
+/// Same as [`fee_history`], with every block paying `rewards` at the requested percentiles
+pub fn fee_history_rewards(base_fees: &[u128], gas_used_ratios: &[f64], rewards: &[u128]) -> Value {
+    let rewards: Vec<_> = rewards.iter().map(|fee| quantity(*fee)).collect();
+    json!({
+        "oldestBlock": quantity(100),
+        "baseFeePerGas": base_fees.iter().map(|fee| quantity(*fee)).collect::<Vec<_>>(),
+        "gasUsedRatio": gas_used_ratios,
+        "reward": gas_used_ratios.iter().map(|_| rewards.clone()).collect::<Vec<_>>(),
+    })
+}
+
+/// Encodes a quantity the way nodes do
+pub fn quantity(value: u128) -> Value {
+    json!(format!("0x{:x}", value))
+}
+
+/// POST request with a JSON body
+pub fn json_request(uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+pub async fn body_bytes(response: Response<Body>) -> Bytes {
+    to_bytes(response.into_body(), usize::MAX).await.unwrap()
+}
+
+/// Transaction body accepted by the estimation endpoints
+pub fn transfer_body() -> Value {
+    json!({
+        "from": "0x742d35Cc6634C0532925a3b844Bc454e4438f44e",
+        "to": "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5",
+        "value": "1000000000000000"
+    })
+}
+
+/// Node answering the calls a plain legacy transfer estimation makes
+pub fn transfer_transport() -> MockTransport {
+    MockTransport::new(|method, _| match method {
+        "eth_chainId" => Ok(quantity(1)),
+        "eth_estimateGas" => Ok(quantity(21_000)),
+        "eth_gasPrice" => Ok(quantity(20_000_000_000)),
+        "eth_getBlockByNumber" => Ok(mock_block(
+            100,
+            Some(10_000_000_000),
+            30_000_000,
+            15_000_000,
+        )),
+        _ => Err(format!("unexpected method {}", method)),
+    })
+}
@@ -1,43 +1,242 @@
+use crate::utils::metrics;
+use crate::utils::rpc::recover_quantity;
 use alloy_provider::{Provider, RootProvider};
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::Mutex;
 
-lazy_static::lazy_static! {
-    static ref PRICE_CACHE: Mutex<HashMap<String, (u128, Instant)>> = Mutex::new(HashMap::new());
+type Entries = Arc<Mutex<HashMap<String, (u128, CachedAt)>>>;
+const GAS_PRICE_KEY: &str = "gas_price";
+
+/// Key of a chain's gas price, e.g. `gas_price:1` on mainnet
+fn gas_price_key(chain_id: u64) -> String {
+    format!("{}:{}", GAS_PRICE_KEY, chain_id)
+}
+
+/// One lock per key, held while that key is being fetched
+type Fetches = Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>;
+
+/// When a value was cached, on both clocks. The monotonic clock stops while the
+/// process is suspended on some platforms, so an entry is only fresh while the wall
+/// clock agrees it is younger than the TTL.
+#[derive(Debug, Clone, Copy)]
+struct CachedAt {
+    monotonic: Instant,
+    wall: SystemTime,
 }
 
-pub async fn cached_gas_price(provider: Arc<RootProvider>, ttl: Duration) -> eyre::Result<u128> {
-    if ttl == Duration::from_secs(0) {
-        tracing::debug!("TTL is 0: bypassing cache");
-        return Ok(provider.get_gas_price().await?);
+impl CachedAt {
+    fn now() -> Self {
+        Self {
+            monotonic: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.monotonic.elapsed()
+    }
+
+    /// A wall clock set back since caching also counts as expired
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        let wall_elapsed = SystemTime::now().duration_since(self.wall);
+        self.elapsed() < ttl && wall_elapsed.is_ok_and(|elapsed| elapsed < ttl)
     }
+}
 
-    const CACHE_KEY: &str = "gas_price";
-    let mut cache = PRICE_CACHE.lock().await;
+tokio::task_local! {
+    static BYPASS_CACHE: bool;
+}
 
-    if let Some((price, timestamp)) = cache.get(CACHE_KEY) {
-        if timestamp.elapsed() < ttl {
-            tracing::debug!("Gas price cache hit");
-            return Ok(*price);
+/// Runs `future` with cached values ignored when `bypass` is set. Fresh values are
+/// still stored for later calls.
+pub async fn bypass_cache<F: Future>(bypass: bool, future: F) -> F::Output {
+    if bypass {
+        BYPASS_CACHE.scope(true, future).await
+    } else {
+        future.await
+    }
+}
+
+/// Gas price, and other values priced in with it, cached for `ttl`. Clones share
+/// their entries; separate caches, e.g. one per chain, don't.
+#[derive(Debug, Clone, Default)]
+pub struct GasPriceCache {
+    entries: Entries,
+    fetches: Fetches,
+    ttl: Duration,
+}
+
+impl GasPriceCache {
+    /// A zero `ttl` disables caching: every lookup fetches
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Entries::default(),
+            fetches: Fetches::default(),
+            ttl,
         }
-        tracing::debug!("Gas price cache expired");
     }
 
-    tracing::debug!("Fetching fresh gas price from provider");
-    let gas_price = provider.get_gas_price().await?;
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
 
-    cache.insert(CACHE_KEY.to_string(), (gas_price, Instant::now()));
+    /// Gas price cached while younger than both the TTL and `max_age`, keyed by
+    /// `chain_id` so providers of different chains can share the cache. The chain id
+    /// is only awaited when the price may be cached.
+    pub async fn cached_gas_price<C>(
+        &self,
+        provider: &RootProvider,
+        chain_id: C,
+        max_age: Duration,
+    ) -> eyre::Result<u128>
+    where
+        C: Future<Output = eyre::Result<u64>>,
+    {
+        let fetch = async {
+            metrics::GAS_PRICE_FETCHES.inc();
+            Ok(recover_quantity(provider.get_gas_price().await)?)
+        };
+        let ttl = std::cmp::min(self.ttl, max_age);
+        let key = if ttl.is_zero() {
+            GAS_PRICE_KEY.to_string()
+        } else {
+            gas_price_key(chain_id.await?)
+        };
+        let (gas_price, lookup) = self.lookup(&key, ttl, fetch).await?;
+        match lookup {
+            Lookup::Hit => metrics::GAS_PRICE_CACHE_HITS.inc(),
+            Lookup::Miss => metrics::GAS_PRICE_CACHE_MISSES.inc(),
+            Lookup::Bypassed => {}
+        }
+        Ok(gas_price)
+    }
 
-    Ok(gas_price)
+    /// Last gas price cached for `chain_id` however old, without fetching
+    pub async fn last_gas_price(&self, chain_id: u64) -> Option<u128> {
+        let cache = self.entries.lock().await;
+        cache.get(&gas_price_key(chain_id)).map(|(price, _)| *price)
+    }
+
+    /// The value cached under `key` while younger than the TTL, otherwise the result
+    /// of `fetch`, cached when it succeeds
+    pub async fn cached_value<F>(&self, key: &str, fetch: F) -> eyre::Result<u128>
+    where
+        F: Future<Output = eyre::Result<u128>>,
+    {
+        Ok(self.lookup(key, self.ttl, fetch).await?.0)
+    }
+
+    async fn lookup<F>(&self, key: &str, ttl: Duration, fetch: F) -> eyre::Result<(u128, Lookup)>
+    where
+        F: Future<Output = eyre::Result<u128>>,
+    {
+        if ttl == Duration::from_secs(0) {
+            tracing::debug!("TTL is 0: bypassing cache");
+            return Ok((fetch.await?, Lookup::Bypassed));
+        }
+
+        if BYPASS_CACHE.try_with(|bypass| *bypass).unwrap_or(false) {
+            tracing::debug!("Cache bypass requested");
+            let value = fetch.await?;
+            self.store(key, value).await;
+            return Ok((value, Lookup::Bypassed));
+        }
+        if let Some(value) = self.fresh(key, ttl).await {
+            return Ok((value, Lookup::Hit));
+        }
+
+        // Single flight: concurrent misses on a key wait for the first one's fetch
+        // instead of fetching too. Hits above never wait on a fetch.
+        let fetch_lock = self
+            .fetches
+            .lock()
+            .await
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let _fetching = fetch_lock.lock().await;
+        if let Some(value) = self.fresh(key, ttl).await {
+            return Ok((value, Lookup::Hit));
+        }
+
+        tracing::debug!("Fetching fresh {}", key);
+        let value = fetch.await?;
+        self.store(key, value).await;
+
+        Ok((value, Lookup::Miss))
+    }
+
+    /// The value cached under `key`, if younger than `ttl`
+    async fn fresh(&self, key: &str, ttl: Duration) -> Option<u128> {
+        let cache = self.entries.lock().await;
+        let (value, cached_at) = cache.get(key)?;
+        if cached_at.is_fresh(ttl) {
+            tracing::debug!("{} cache hit", key);
+            return Some(*value);
+        }
+        tracing::debug!("{} cache expired", key);
+        None
+    }
+
+    async fn store(&self, key: &str, value: u128) {
+        let mut cache = self.entries.lock().await;
+        cache.insert(key.to_string(), (value, CachedAt::now()));
+    }
+
+    /// Current contents of the cache, expired entries included
+    pub async fn entries(&self) -> Vec<CacheEntry> {
+        let cache = self.entries.lock().await;
+        let mut entries: Vec<_> = cache
+            .iter()
+            .map(|(key, (price, cached_at))| CacheEntry {
+                key: key.clone(),
+                value: price.to_string(),
+                age_ms: cached_at.elapsed().as_millis() as u64,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    /// Empties the cache, returning how many entries were dropped
+    pub async fn clear(&self) -> usize {
+        let mut cache = self.entries.lock().await;
+        let cleared = cache.len();
+        cache.clear();
+        cleared
+    }
 }
 
+/// How a cached value was obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lookup {
+    Hit,
+    /// No entry, or an expired one
+    Miss,
+    /// Zero TTL or bypass requested, the cache wasn't looked at
+    Bypassed,
+}
+
+/// A cached value as reported to operators
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub value: String,
+    pub age_ms: u64,
+}
+
+type CacheLookupFuture = Pin<Box<dyn Future<Output = Option<(u128, CachedAt)>> + Send>>;
+type ProviderFuture = Pin<Box<dyn Future<Output = eyre::Result<u128>> + Send>>;
+
 pub struct CachedGasPriceFuture {
+    cache: GasPriceCache,
     provider: Arc<RootProvider>,
+    chain_id: u64,
     ttl: Duration,
     state: CacheState,
 }
@@ -46,10 +245,10 @@ enum CacheState {
     #[allow(dead_code)]
     Init,
     CheckingCache {
-        cache_future: Pin<Box<dyn Future<Output = Option<(u128, Instant)>> + Send>>,
+        cache_future: CacheLookupFuture,
     },
     FetchingFromProvider {
-        provider_future: Pin<Box<dyn Future<Output = eyre::Result<u128>> + Send>>,
+        provider_future: ProviderFuture,
     },
     UpdatingCache {
         gas_price: u128,
@@ -67,9 +266,11 @@ impl Future for CachedGasPriceFuture {
             match &mut this.state {
                 CacheState::Init => {
                     // Start by checking the cache
-                    let cache_future = Box::pin(async {
-                        let cache = PRICE_CACHE.lock().await;
-                        cache.get("gas_price").map(|(price, time)| (*price, *time))
+                    let entries = this.cache.entries.clone();
+                    let key = gas_price_key(this.chain_id);
+                    let cache_future = Box::pin(async move {
+                        let cache = entries.lock().await;
+                        cache.get(&key).map(|(price, time)| (*price, *time))
                     });
 
                     this.state = CacheState::CheckingCache { cache_future };
@@ -79,8 +280,8 @@ impl Future for CachedGasPriceFuture {
                     match Pin::new(cache_future).poll(cx) {
                         Poll::Ready(cache_result) => {
                             // Check if we got a valid cached value
-                            if let Some((price, timestamp)) = cache_result {
-                                if timestamp.elapsed() < this.ttl {
+                            if let Some((price, cached_at)) = cache_result {
+                                if cached_at.is_fresh(this.ttl) {
                                     tracing::debug!("Gas price cache hit (future)");
                                     return Poll::Ready(Ok(price));
                                 }
@@ -90,7 +291,8 @@ impl Future for CachedGasPriceFuture {
                             // Cache miss or expired, need to fetch from provider
                             let provider_clone = this.provider.clone();
                             let provider_future = Box::pin(async move {
-                                provider_clone.get_gas_price().await.map_err(Into::into)
+                                recover_quantity(provider_clone.get_gas_price().await)
+                                    .map_err(Into::into)
                             });
 
                             this.state = CacheState::FetchingFromProvider { provider_future };
@@ -105,12 +307,11 @@ impl Future for CachedGasPriceFuture {
                             match result {
                                 Ok(gas_price) => {
                                     // Got price, now update cache
+                                    let entries = this.cache.entries.clone();
+                                    let key = gas_price_key(this.chain_id);
                                     let update_future = Box::pin(async move {
-                                        let mut cache = PRICE_CACHE.lock().await;
-                                        cache.insert(
-                                            "gas_price".to_string(),
-                                            (gas_price, Instant::now()),
-                                        );
+                                        let mut cache = entries.lock().await;
+                                        cache.insert(key, (gas_price, CachedAt::now()));
                                     });
 
                                     this.state = CacheState::UpdatingCache {
@@ -143,3 +344,292 @@ impl Future for CachedGasPriceFuture {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quantity, MockTransport};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn counting_provider() -> (RootProvider, Arc<AtomicU64>) {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        let transport = MockTransport::new(move |_, _| {
+            Ok(quantity(
+                u128::from(counter.fetch_add(1, Ordering::SeqCst)) + 1,
+            ))
+        });
+        (transport.provider(), calls)
+    }
+
+    fn chain(id: u64) -> std::future::Ready<eyre::Result<u64>> {
+        std::future::ready(Ok(id))
+    }
+
+    #[tokio::test]
+    async fn test_bypass_refreshes_warm_cache() {
+        let (provider, calls) = counting_provider();
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+        let max_age = Duration::MAX;
+
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), max_age)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), max_age)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let fresh = bypass_cache(true, cache.cached_gas_price(&provider, chain(1), max_age)).await;
+        assert_eq!(fresh.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The fresh value replaced the cached one
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), max_age)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_always_fetches() {
+        let (provider, calls) = counting_provider();
+        let cache = GasPriceCache::new(Duration::ZERO);
+
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), Duration::MAX)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), Duration::MAX)
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(cache.entries().await.is_empty());
+
+        // A zero max age also skips a cache that has a TTL
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+        cache
+            .cached_gas_price(&provider, chain(1), Duration::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            cache
+                .cached_gas_price(&provider, chain(1), Duration::ZERO)
+                .await
+                .unwrap(),
+            4
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_caches_are_independent() {
+        let (mainnet, _) = counting_provider();
+        let l2 = MockTransport::new(|_, _| Ok(quantity(100))).provider();
+        let ttl = Duration::from_secs(60);
+        let (mainnet_cache, l2_cache) = (GasPriceCache::new(ttl), GasPriceCache::new(ttl));
+
+        assert_eq!(
+            mainnet_cache
+                .cached_gas_price(&mainnet, chain(1), ttl)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            l2_cache
+                .cached_gas_price(&l2, chain(10), ttl)
+                .await
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            mainnet_cache
+                .cached_gas_price(&mainnet, chain(1), ttl)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Clones share their entries
+        assert_eq!(l2_cache.clone().clear().await, 1);
+        assert!(l2_cache.entries().await.is_empty());
+        assert_eq!(mainnet_cache.entries().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chains_keyed_apart() {
+        let mainnet = MockTransport::new(|_, _| Ok(quantity(20))).provider();
+        let l2 = MockTransport::new(|_, _| Ok(quantity(1))).provider();
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+
+        let max_age = Duration::MAX;
+        assert_eq!(
+            cache
+                .cached_gas_price(&mainnet, chain(1), max_age)
+                .await
+                .unwrap(),
+            20
+        );
+        assert_eq!(
+            cache
+                .cached_gas_price(&l2, chain(10), max_age)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            cache
+                .cached_gas_price(&l2, chain(1), max_age)
+                .await
+                .unwrap(),
+            20
+        );
+
+        let keys: Vec<_> = cache.entries().await.into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, ["gas_price:1", "gas_price:10"]);
+    }
+
+    #[tokio::test]
+    async fn test_hits_not_blocked_by_slow_fetch() {
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+        cache
+            .cached_value("gas_price", async { Ok(1) })
+            .await
+            .unwrap();
+
+        // Fetches stuck until released: a miss on another key, and a bypass on this one
+        let (release, released) = tokio::sync::watch::channel(false);
+        let slow_fetch = |key: &'static str, bypass: bool| {
+            let (cache, mut released) = (cache.clone(), released.clone());
+            tokio::spawn(bypass_cache(bypass, async move {
+                cache
+                    .cached_value(key, async move {
+                        released.wait_for(|released| *released).await?;
+                        Ok(2)
+                    })
+                    .await
+            }))
+        };
+        let slow_miss = slow_fetch("eth_usd_micros", false);
+        let slow_bypass = slow_fetch("gas_price", true);
+        tokio::task::yield_now().await;
+
+        let hit = cache.cached_value("gas_price", async { panic!("cache hit expected") });
+        let hit = tokio::time::timeout(Duration::from_secs(1), hit).await;
+        assert_eq!(hit.expect("hit waited on a fetch").unwrap(), 1);
+
+        release.send(true).unwrap();
+        assert_eq!(slow_miss.await.unwrap().unwrap(), 2);
+        assert_eq!(slow_bypass.await.unwrap().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_fetch_once() {
+        let (provider, calls) = counting_provider();
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+
+        let prices = futures::future::join_all(
+            (0..5).map(|_| cache.cached_gas_price(&provider, chain(1), Duration::MAX)),
+        )
+        .await;
+        assert!(prices.into_iter().all(|price| price.unwrap() == 1));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lookups_counted() {
+        let provider = MockTransport::new(|_, _| Ok(quantity(1))).provider();
+        let counts = || {
+            (
+                metrics::GAS_PRICE_CACHE_HITS.get(),
+                metrics::GAS_PRICE_CACHE_MISSES.get(),
+                metrics::GAS_PRICE_FETCHES.get(),
+            )
+        };
+        let (hits, misses, fetches) = counts();
+
+        let ttl = Duration::from_secs(60);
+        let cache = GasPriceCache::new(ttl);
+        cache
+            .cached_gas_price(&provider, chain(1), ttl)
+            .await
+            .unwrap();
+        cache
+            .cached_gas_price(&provider, chain(1), ttl)
+            .await
+            .unwrap();
+        cache
+            .cached_gas_price(&provider, chain(1), ttl)
+            .await
+            .unwrap();
+        bypass_cache(true, cache.cached_gas_price(&provider, chain(1), ttl))
+            .await
+            .unwrap();
+
+        // The counters are process wide: other tests may add to them concurrently
+        let (new_hits, new_misses, new_fetches) = counts();
+        assert!(new_hits - hits >= 2);
+        assert!(new_misses - misses >= 1);
+        assert!(new_fetches - fetches >= 2);
+    }
+
+    #[test]
+    fn test_wall_clock_bounds_freshness() {
+        let ttl = Duration::from_secs(60);
+        assert!(CachedAt::now().is_fresh(ttl));
+
+        // As after a suspend the monotonic clock didn't see
+        let suspended = CachedAt {
+            monotonic: Instant::now(),
+            wall: SystemTime::now() - Duration::from_secs(3600),
+        };
+        assert!(!suspended.is_fresh(ttl));
+
+        // Wall clock set back since the value was cached
+        let clock_set_back = CachedAt {
+            monotonic: Instant::now(),
+            wall: SystemTime::now() + Duration::from_secs(3600),
+        };
+        assert!(!clock_set_back.is_fresh(ttl));
+    }
+
+    #[tokio::test]
+    async fn test_suspended_entry_refetched() {
+        let provider = MockTransport::new(|_, _| Ok(quantity(2))).provider();
+        let cache = GasPriceCache::new(Duration::from_secs(60));
+        cache.entries.lock().await.insert(
+            gas_price_key(1),
+            (
+                1,
+                CachedAt {
+                    monotonic: Instant::now(),
+                    wall: SystemTime::now() - Duration::from_secs(3600),
+                },
+            ),
+        );
+
+        let price = cache
+            .cached_gas_price(&provider, chain(1), Duration::MAX)
+            .await;
+        assert_eq!(price.unwrap(), 2);
+    }
+}
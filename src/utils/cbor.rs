@@ -0,0 +1,30 @@
+use axum::{
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+pub const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+pub fn accepts_cbor(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(CBOR_CONTENT_TYPE))
+}
+
+/// CBOR counterpart of `axum::Json`, for clients that find JSON too heavy to parse
+pub struct Cbor<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Cbor<T> {
+    fn into_response(self) -> Response {
+        let mut body = Vec::new();
+        match ciborium::into_writer(&self.0, &mut body) {
+            Ok(()) => ([(CONTENT_TYPE, CBOR_CONTENT_TYPE)], body).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+}
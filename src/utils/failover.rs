@@ -0,0 +1,161 @@
+use alloy_json_rpc::{RequestPacket, ResponsePacket, RpcError};
+use alloy_transport::{BoxTransport, TransportError, TransportFut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Service, ServiceExt};
+
+/// Transport over several nodes. Calls go to the active node; when it fails at the
+/// transport level (unreachable, HTTP error) the next ones are tried in order and the
+/// first that answers becomes active. JSON-RPC errors are answers and don't fail over.
+#[derive(Debug, Clone)]
+pub struct Failover {
+    transports: Arc<[BoxTransport]>,
+    active: Arc<AtomicUsize>,
+}
+
+impl Failover {
+    pub fn new(transports: Vec<BoxTransport>) -> Self {
+        assert!(
+            !transports.is_empty(),
+            "failover needs at least one transport"
+        );
+        Self {
+            transports: transports.into(),
+            active: Arc::default(),
+        }
+    }
+
+    /// Index of the node calls currently go to
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+impl Service<RequestPacket> for Failover {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let this = self.clone();
+        Box::pin(async move {
+            let start = this.active();
+            let mut last_error = None;
+
+            for offset in 0..this.transports.len() {
+                let index = (start + offset) % this.transports.len();
+                match this.transports[index].clone().oneshot(req.clone()).await {
+                    Err(RpcError::Transport(kind)) => {
+                        tracing::warn!("RPC node #{} failed: {}", index + 1, kind);
+                        last_error = Some(RpcError::Transport(kind));
+                    }
+                    result => {
+                        if index != start {
+                            tracing::warn!("Failing over to RPC node #{}", index + 1);
+                            this.active.store(index, Ordering::Relaxed);
+                        }
+                        return result;
+                    }
+                }
+            }
+
+            Err(last_error.expect("failover has at least one transport"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quantity, MockTransport};
+    use crate::utils::rpc_timeout::RpcTimeoutLayer;
+    use alloy_provider::{Provider, RootProvider};
+    use alloy_rpc_client::RpcClient;
+    use alloy_transport::{Transport, TransportErrorKind};
+    use std::time::Duration;
+    use tower::Layer;
+
+    /// Node that can't be reached, counting the attempts
+    #[derive(Clone, Default)]
+    struct Down {
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl Service<RequestPacket> for Down {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: RequestPacket) -> Self::Future {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Err(TransportErrorKind::custom_str("connection refused")) })
+        }
+    }
+
+    fn boxed(transport: impl Transport + Clone) -> BoxTransport {
+        Transport::boxed(transport)
+    }
+
+    fn provider(failover: &Failover) -> RootProvider {
+        RootProvider::new(RpcClient::builder().transport(failover.clone(), true))
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_next_node() {
+        let down = Down::default();
+        let failover = Failover::new(vec![
+            boxed(down.clone()),
+            boxed(MockTransport::new(|_, _| Ok(quantity(100)))),
+        ]);
+        let provider = provider(&failover);
+
+        assert_eq!(provider.get_block_number().await.unwrap(), 100);
+        assert_eq!(failover.active(), 1);
+
+        // The healthy node stays active
+        assert_eq!(provider.get_block_number().await.unwrap(), 100);
+        assert_eq!(down.attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fails_over_from_stalled_node() {
+        let stalled = MockTransport::new(|_, _| Ok(quantity(99)))
+            .delayed("eth_blockNumber", Duration::from_secs(60));
+        let timeout = RpcTimeoutLayer::new(Duration::from_millis(100));
+        let failover = Failover::new(vec![
+            boxed(timeout.layer(stalled)),
+            boxed(timeout.layer(MockTransport::new(|_, _| Ok(quantity(100))))),
+        ]);
+
+        assert_eq!(provider(&failover).get_block_number().await.unwrap(), 100);
+        assert_eq!(failover.active(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors_and_all_down() {
+        let failover = Failover::new(vec![
+            boxed(MockTransport::new(|_, _| Err("execution reverted".into()))),
+            boxed(MockTransport::new(|_, _| Ok(quantity(100)))),
+        ]);
+        assert!(matches!(
+            provider(&failover).get_block_number().await,
+            Err(RpcError::ErrorResp(_))
+        ));
+        assert_eq!(failover.active(), 0);
+
+        let failover = Failover::new(vec![boxed(Down::default()), boxed(Down::default())]);
+        assert!(matches!(
+            provider(&failover).get_block_number().await,
+            Err(RpcError::Transport(_))
+        ));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::error::{Error, Result};
+use alloy_primitives::{Address, U256};
+use std::str::FromStr;
+
+const ETHER_DECIMALS: usize = 18;
+const GWEI_DECIMALS: usize = 9;
+const SIGNIFICANT_DIGITS: usize = 6;
+
+/// Rendering of wei amounts converted to a larger unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CostFormat {
+    /// Every decimal of the unit, e.g. `0.000420000000000000`
+    #[default]
+    Fixed18,
+    /// Trailing zeros dropped, e.g. `0.00042`
+    Trimmed,
+    /// Rounded half up to 6 significant digits, then trimmed
+    Significant6,
+}
+
+impl CostFormat {
+    pub fn format_ether(self, wei: U256) -> String {
+        self.format_units(wei, ETHER_DECIMALS)
+    }
+
+    pub fn format_gwei(self, wei: U256) -> String {
+        self.format_units(wei, GWEI_DECIMALS)
+    }
+
+    fn format_units(self, wei: U256, decimals: usize) -> String {
+        match self {
+            Self::Fixed18 => fixed(wei, decimals),
+            Self::Trimmed => trim(fixed(wei, decimals)),
+            Self::Significant6 => trim(fixed(round_significant(wei), decimals)),
+        }
+    }
+}
+
+impl FromStr for CostFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fixed18" => Ok(Self::Fixed18),
+            "trimmed" => Ok(Self::Trimmed),
+            "significant6" => Ok(Self::Significant6),
+            _ => Err(Error::Config(format!("Unknown cost format: {}", s))),
+        }
+    }
+}
+
+fn fixed(wei: U256, decimals: usize) -> String {
+    let digits = format!("{:0>width$}", wei.to_string(), width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    format!("{}.{}", integer, fraction)
+}
+
+fn trim(formatted: String) -> String {
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn round_significant(wei: U256) -> U256 {
+    let digits = wei.to_string();
+    if digits.len() <= SIGNIFICANT_DIGITS {
+        return wei;
+    }
+
+    let scale = U256::from(10).pow(U256::from(digits.len() - SIGNIFICANT_DIGITS));
+    let half = scale / U256::from(2);
+    (wei.saturating_add(half) / scale).saturating_mul(scale)
+}
+
+/// Integer from a request field, `0x`-prefixed hex or decimal
+pub fn parse_hex_or_decimal<T>(input: &str) -> Option<T>
+where
+    T: TryFrom<u128>,
+{
+    let value = match input.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16).ok()?,
+        None => input.parse().ok()?,
+    };
+    T::try_from(value).ok()
+}
+
+/// Wei in a decimal gwei amount such as `50` or `1.5`
+pub fn parse_gwei(amount: &str) -> Result<u128> {
+    let invalid = || Error::InvalidInput(format!("Invalid gwei amount: {}", amount));
+    let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = integer.len() + fraction.len();
+    if digits == 0
+        || fraction.len() > GWEI_DECIMALS
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    format!("{}{:0<width$}", integer, fraction, width = GWEI_DECIMALS)
+        .parse()
+        .map_err(|_| invalid())
+}
+
+/// EIP-55 mixed-case form of an address
+pub fn to_checksum(address: &Address) -> String {
+    address.to_checksum(None)
+}
+
+/// Address as returned in responses: checksummed, or lowercase with `CHECKSUM_OUTPUT=false`
+pub fn format_address(address: &Address, checksum: bool) -> String {
+    if checksum {
+        to_checksum(address)
+    } else {
+        format!("{:#x}", address)
+    }
+}
+
+/// `text` with every 20-byte hex address replaced, for logs. Longer hex strings such as
+/// hashes and calldata are left alone, addresses inside them included.
+pub fn redact_addresses(text: &str) -> String {
+    const ADDRESS_DIGITS: usize = 40;
+    let bytes = text.as_bytes();
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        let starts_hex = bytes[i] == b'0'
+            && (bytes[i + 1] == b'x' || bytes[i + 1] == b'X')
+            && (i == 0 || !bytes[i - 1].is_ascii_alphanumeric());
+        if !starts_hex {
+            i += 1;
+            continue;
+        }
+        let digits = bytes[i + 2..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_hexdigit())
+            .count();
+        if digits == ADDRESS_DIGITS {
+            redacted.push_str(&text[copied..i]);
+            redacted.push_str("0x[redacted]");
+            copied = i + 2 + digits;
+        }
+        i += 2 + digits;
+    }
+    redacted.push_str(&text[copied..]);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_addresses() {
+        let address = "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5";
+        assert_eq!(
+            redact_addresses(&format!(
+                r#"from: "{}", to: Address("{}")"#,
+                address, address
+            )),
+            r#"from: "0x[redacted]", to: Address("0x[redacted]")"#
+        );
+        // Hashes and calldata are longer than an address
+        let hash = format!("0x{}", "ab".repeat(32));
+        assert_eq!(redact_addresses(&hash), hash);
+        let calldata = format!("0xa9059cbb{}", &address[2..]);
+        assert_eq!(redact_addresses(&calldata), calldata);
+        assert_eq!(redact_addresses("value: 0x1bc16d"), "value: 0x1bc16d");
+    }
+
+    #[test]
+    fn test_parse_hex_or_decimal() {
+        assert_eq!(parse_hex_or_decimal::<u128>("0x1a"), Some(26));
+        assert_eq!(parse_hex_or_decimal::<u128>("26"), Some(26));
+        assert_eq!(parse_hex_or_decimal::<u64>("0xffffffffffffffffff"), None);
+        assert_eq!(parse_hex_or_decimal::<u128>("0x"), None);
+        assert_eq!(parse_hex_or_decimal::<u128>("1a"), None);
+        assert_eq!(parse_hex_or_decimal::<u128>("-1"), None);
+    }
+
+    #[test]
+    fn test_parse_gwei() {
+        assert_eq!(parse_gwei("50").unwrap(), 50_000_000_000);
+        assert_eq!(parse_gwei("1.5").unwrap(), 1_500_000_000);
+        assert_eq!(parse_gwei("0.000000001").unwrap(), 1);
+        assert!(parse_gwei("0.0000000001").is_err());
+        assert!(parse_gwei("-1").is_err());
+        assert!(parse_gwei("fifty").is_err());
+        assert!(parse_gwei(".").is_err());
+    }
+
+    #[test]
+    fn test_address_formats() {
+        let address = Address::from_str("0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5").unwrap();
+        assert_eq!(
+            format_address(&address, true),
+            "0x95222290DD7278Aa3Ddd389Cc1E1d165CC4BAfe5"
+        );
+        assert_eq!(
+            format_address(&address, false),
+            "0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5"
+        );
+    }
+
+    #[test]
+    fn test_fixed18_matches_format_ether() {
+        let wei = U256::from(420_000_000_000_000u64);
+        assert_eq!(
+            CostFormat::Fixed18.format_ether(wei),
+            "0.000420000000000000"
+        );
+        assert_eq!(
+            CostFormat::Fixed18.format_ether(wei),
+            alloy_primitives::utils::format_ether(wei)
+        );
+        assert_eq!(
+            CostFormat::Fixed18.format_ether(U256::ZERO),
+            "0.000000000000000000"
+        );
+        assert_eq!(
+            CostFormat::Fixed18.format_gwei(U256::from(1_500_000_000u64)),
+            "1.500000000"
+        );
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let format = CostFormat::Trimmed;
+        assert_eq!(
+            format.format_ether(U256::from(420_000_000_000_000u64)),
+            "0.00042"
+        );
+        assert_eq!(format.format_ether(U256::from(10).pow(U256::from(18))), "1");
+        assert_eq!(format.format_ether(U256::ZERO), "0");
+        assert_eq!(format.format_ether(U256::from(1)), "0.000000000000000001");
+        assert_eq!(format.format_gwei(U256::from(1_500_000_000u64)), "1.5");
+    }
+
+    #[test]
+    fn test_significant6() {
+        let format = CostFormat::Significant6;
+        assert_eq!(
+            format.format_ether(U256::from(123_456_789_000_000u64)),
+            "0.000123457"
+        );
+        assert_eq!(
+            format.format_ether(U256::from(420_000_000_000_000u64)),
+            "0.00042"
+        );
+        assert_eq!(
+            format.format_ether(U256::from(999_999_500_000_000_000u64)),
+            "1"
+        );
+        assert_eq!(format.format_ether(U256::from(1)), "0.000000000000000001");
+        assert_eq!(format.format_gwei(U256::from(1_234_567_890u64)), "1.23457");
+    }
+
+    #[test]
+    fn test_very_large_amounts() {
+        assert_eq!(
+            CostFormat::Trimmed.format_ether(U256::MAX),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+        assert_eq!(
+            CostFormat::Significant6.format_ether(U256::MAX),
+            "115792000000000000000000000000000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "trimmed".parse::<CostFormat>().unwrap(),
+            CostFormat::Trimmed
+        );
+        assert!("scientific".parse::<CostFormat>().is_err());
+    }
+}
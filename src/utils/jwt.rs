@@ -0,0 +1,85 @@
+use crate::error::{Error, Result};
+use crate::models::transaction::GasEstimation;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const JWT_CONTENT_TYPE: &str = "application/jwt";
+
+/// Claims of a signed estimate, letting downstream services check integrity and freshness
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EstimationClaims {
+    pub estimation: GasEstimation,
+    pub chain_id: u64,
+    pub estimated_at: u64,
+    pub valid_until: u64,
+}
+
+/// Signs gas estimations as HS256 JWTs
+pub struct ResponseSigner {
+    key: EncodingKey,
+    validity: Duration,
+}
+
+impl ResponseSigner {
+    pub fn new(secret: &str, validity: Duration) -> Self {
+        Self {
+            key: EncodingKey::from_secret(secret.as_bytes()),
+            validity,
+        }
+    }
+
+    pub fn sign(&self, estimation: &GasEstimation, chain_id: u64) -> Result<String> {
+        let estimated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::Server(e.to_string()))?
+            .as_secs();
+
+        let claims = EstimationClaims {
+            estimation: estimation.clone(),
+            chain_id,
+            estimated_at,
+            valid_until: estimated_at + self.validity.as_secs(),
+        };
+
+        encode(&Header::default(), &claims, &self.key)
+            .map_err(|e| Error::Server(format!("Failed to sign response: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, DecodingKey, Validation};
+
+    #[test]
+    fn test_signed_estimation_roundtrip() {
+        let estimation = GasEstimation {
+            gas_limit: "21000".into(),
+            gas_price: "20000000000".into(),
+            estimated_cost_wei: "420000000000000".into(),
+            estimated_cost_eth: "0.000420000000000000".into(),
+            estimated_execution_time: Some("~30 seconds".into()),
+            type_of_transaction: "legacy".into(),
+            ..Default::default()
+        };
+        let signer = ResponseSigner::new("secret", Duration::from_secs(30));
+        let token = signer.sign(&estimation, 1).unwrap();
+
+        let mut validation = Validation::default();
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        let claims =
+            decode::<EstimationClaims>(&token, &DecodingKey::from_secret(b"secret"), &validation)
+                .unwrap()
+                .claims;
+
+        assert_eq!(claims.chain_id, 1);
+        assert_eq!(claims.valid_until - claims.estimated_at, 30);
+        assert_eq!(claims.estimation.gas_limit, "21000");
+
+        let tampered =
+            decode::<EstimationClaims>(&token, &DecodingKey::from_secret(b"other"), &validation);
+        assert!(tampered.is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+
+    /// Responses by method, route and status code
+    pub static ref HTTP_REQUESTS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests served"),
+        &["method", "path", "status"],
+    ));
+    /// Time spent producing `/api/v1/estimate-gas` estimates, failed ones included
+    pub static ref GAS_ESTIMATION_DURATION: Histogram = register(Histogram::with_opts(
+        HistogramOpts::new(
+            "gas_estimation_duration_seconds",
+            "Duration of gas estimations",
+        ),
+    ));
+    /// Calls that reached the node, by JSON-RPC method
+    pub static ref RPC_CALLS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("rpc_calls_total", "JSON-RPC calls made to the node"),
+        &["method"],
+    ));
+    pub static ref GAS_PRICE_CACHE_HITS: IntCounter = register(IntCounter::new(
+        "gas_price_cache_hits_total",
+        "Gas prices served from the cache",
+    ));
+    /// Lookups finding no entry, or an expired one
+    pub static ref GAS_PRICE_CACHE_MISSES: IntCounter = register(IntCounter::new(
+        "gas_price_cache_misses_total",
+        "Gas price cache lookups without a fresh entry",
+    ));
+    /// Every gas price fetched from the node: misses, bypasses and a zero TTL
+    pub static ref GAS_PRICE_FETCHES: IntCounter = register(IntCounter::new(
+        "gas_price_fetches_total",
+        "Gas prices fetched from the node",
+    ));
+}
+
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric registered once");
+    metric
+}
+
+/// Current value of every metric
+pub fn gather() -> Vec<prometheus::proto::MetricFamily> {
+    // Registered on first use otherwise, and missing from the output until then
+    lazy_static::initialize(&HTTP_REQUESTS);
+    lazy_static::initialize(&GAS_ESTIMATION_DURATION);
+    lazy_static::initialize(&RPC_CALLS);
+    lazy_static::initialize(&GAS_PRICE_CACHE_HITS);
+    lazy_static::initialize(&GAS_PRICE_CACHE_MISSES);
+    lazy_static::initialize(&GAS_PRICE_FETCHES);
+    REGISTRY.gather()
+}
+
+/// Every metric in the Prometheus text exposition format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&gather(), &mut buffer) {
+        tracing::warn!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_lists_metrics() {
+        RPC_CALLS.with_label_values(&["eth_chainId"]).inc();
+
+        let output = render();
+        assert!(output.contains("# TYPE rpc_calls_total counter"));
+        assert!(output.contains("rpc_calls_total{method=\"eth_chainId\"}"));
+        assert!(output.contains("gas_price_cache_hits_total"));
+        assert!(output.contains("gas_estimation_duration_seconds_bucket"));
+    }
+}
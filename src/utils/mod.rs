@@ -1,2 +1,14 @@
 pub mod cache;
+pub mod cbor;
+pub mod failover;
+pub mod format;
+pub mod jwt;
+pub mod metrics;
+pub mod retry;
+pub mod rpc;
+pub mod rpc_limit;
+pub mod rpc_stats;
+pub mod rpc_timeout;
 pub mod shutdown;
+#[cfg(feature = "statsd")]
+pub mod statsd;
@@ -0,0 +1,174 @@
+use crate::utils::rpc_timeout::as_rpc_timeout;
+use alloy_json_rpc::{RequestPacket, ResponsePacket, RpcError};
+use alloy_transport::{TransportError, TransportFut};
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service, ServiceExt};
+
+/// Runs `operation` until it succeeds, fails with an error `retryable` rejects, or
+/// `max_retries` retries were made. The delay before retry `n` is `base_delay * 2^n`.
+pub async fn with_backoff<T, E, F, Fut>(
+    max_retries: u32,
+    base_delay: Duration,
+    retryable: impl Fn(&E) -> bool,
+    mut operation: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut delay = base_delay;
+    let mut retries = 0;
+
+    loop {
+        match operation().await {
+            Err(e) if retries < max_retries && retryable(&e) => {
+                retries += 1;
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Transport failures worth another attempt. JSON-RPC errors are the node's answer
+/// (e.g. a revert) and timeouts already waited long enough.
+fn is_transient(error: &TransportError) -> bool {
+    matches!(error, RpcError::Transport(kind) if as_rpc_timeout(kind).is_none())
+}
+
+/// Transport layer retrying calls that failed with a transient error
+#[derive(Debug, Clone, Copy)]
+pub struct RetryLayer {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryLayer {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            max_retries: self.max_retries,
+            base_delay: self.base_delay,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl<S> Service<RequestPacket> for Retry<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let (max_retries, base_delay) = (self.max_retries, self.base_delay);
+        let inner = self.inner.clone();
+        let mut attempt = 0;
+        let operation = move || {
+            if attempt > 0 {
+                tracing::warn!("Retrying RPC call, attempt {}", attempt + 1);
+            }
+            attempt += 1;
+            inner.clone().oneshot(req.clone())
+        };
+
+        Box::pin(with_backoff(
+            max_retries,
+            base_delay,
+            is_transient,
+            operation,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with `error` the first `failures` calls, then succeeds
+    async fn run(failures: u32, max_retries: u32, error: &str) -> (Result<u32, String>, u32) {
+        let calls = AtomicU32::new(0);
+        let result = with_backoff(
+            max_retries,
+            Duration::from_millis(1),
+            |e: &String| e == "transient",
+            || async {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                if call <= failures {
+                    Err(error.to_string())
+                } else {
+                    Ok(call)
+                }
+            },
+        )
+        .await;
+        (result, calls.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_succeeds_after_transient_failures() {
+        assert_eq!(run(2, 3, "transient").await, (Ok(3), 3));
+        assert_eq!(run(0, 3, "transient").await, (Ok(1), 1));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        assert_eq!(run(5, 3, "transient").await, (Err("transient".into()), 4));
+        assert_eq!(run(5, 0, "transient").await, (Err("transient".into()), 1));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        assert_eq!(run(2, 3, "reverted").await, (Err("reverted".into()), 1));
+    }
+
+    #[tokio::test]
+    async fn test_delay_doubles() {
+        let started = tokio::time::Instant::now();
+        let calls = AtomicU32::new(0);
+        let _: Result<(), ()> = with_backoff(
+            3,
+            Duration::from_millis(20),
+            |_| true,
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(())
+            },
+        )
+        .await;
+        // 20 + 40 + 80 ms
+        assert!(started.elapsed() >= Duration::from_millis(140));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,231 @@
+use alloy_json_rpc::RpcError;
+use alloy_primitives::{hex, U256};
+use alloy_transport::TransportErrorKind;
+use std::any::Any;
+
+/// Largest integer an `f64` represents exactly
+const MAX_EXACT_FLOAT: f64 = 9_007_199_254_740_992.0;
+/// Selectors of the revert data Solidity emits for `require`/`revert` messages and
+/// for panics (failed asserts, overflows...)
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Recovers a numeric RPC result that alloy rejected because the node (or a proxy
+/// in front of it) encoded the quantity slightly off, e.g. `" 0x1a"` or `"26.0"`.
+/// Any other error is returned unchanged.
+pub fn recover_quantity<T, E>(result: Result<T, RpcError<E>>) -> Result<T, RpcError<E>>
+where
+    T: TryFrom<u128>,
+{
+    match result {
+        Err(RpcError::DeserError { err, text }) => {
+            match lenient_quantity(&text).and_then(|value| T::try_from(value).ok()) {
+                Some(value) => {
+                    tracing::warn!("Recovered malformed numeric RPC result: {}", text);
+                    Ok(value)
+                }
+                None => Err(RpcError::DeserError { err, text }),
+            }
+        }
+        other => other,
+    }
+}
+
+/// Whether the node doesn't implement the called method. Nodes disagree on the code,
+/// so the message is checked too.
+pub fn is_method_not_found<E>(error: &RpcError<E>) -> bool {
+    let RpcError::ErrorResp(payload) = error else {
+        return false;
+    };
+    let message = payload.message.to_lowercase();
+    payload.code == -32601
+        || message.contains("method not found")
+        || message.contains("not supported")
+        || (message.contains("method") && message.contains("does not exist"))
+}
+
+/// Whether the call ran and failed, by reverting or running out of gas, as opposed to
+/// the node failing to answer. Geth reports reverts with data under code 3.
+pub fn is_execution_failure<E>(error: &RpcError<E>) -> bool {
+    let RpcError::ErrorResp(payload) = error else {
+        return false;
+    };
+    let message = payload.message.to_lowercase();
+    payload.code == 3
+        || message.contains("execution reverted")
+        || message.contains("out of gas")
+        || message.contains("gas required exceeds allowance")
+        || message.contains("invalid opcode")
+}
+
+/// Whether the node rejected the call for exceeding its rate limit, either with an
+/// HTTP 429 or a JSON-RPC error saying so
+pub fn is_rate_limited<E: Any>(error: &RpcError<E>) -> bool {
+    match error {
+        RpcError::ErrorResp(payload) => {
+            let message = payload.message.to_lowercase();
+            payload.code == 429
+                || message.contains("too many requests")
+                || message.contains("rate limit")
+        }
+        RpcError::Transport(kind) => matches!(
+            (kind as &dyn Any).downcast_ref::<TransportErrorKind>(),
+            Some(TransportErrorKind::HttpError(e)) if e.status == 429
+        ),
+        _ => false,
+    }
+}
+
+/// Human-readable reason of a revert: the message of an `Error(string)`, the code of a
+/// `Panic(uint256)`, otherwise the selector of the custom error. `None` without data.
+pub fn revert_reason(data: &[u8]) -> Option<String> {
+    let (selector, args) = data.split_first_chunk::<4>()?;
+    let word = |offset: usize| {
+        let bytes = args.get(offset..offset.checked_add(32)?)?;
+        usize::try_from(U256::from_be_slice(bytes)).ok()
+    };
+    let decoded = match *selector {
+        ERROR_SELECTOR => word(0).and_then(|offset| {
+            let start = offset.checked_add(32)?;
+            let message = args.get(start..start.checked_add(word(offset)?)?)?;
+            String::from_utf8(message.to_vec()).ok()
+        }),
+        PANIC_SELECTOR => args
+            .get(..32)
+            .map(|code| format!("Panic({:#x})", U256::from_be_slice(code))),
+        _ => None,
+    };
+    Some(decoded.unwrap_or_else(|| hex::encode_prefixed(selector)))
+}
+
+/// Parses a JSON quantity leniently: hex or decimal, quoted or not, surrounding
+/// whitespace allowed, and floats as long as they hold an exact integer
+pub fn lenient_quantity(text: &str) -> Option<u128> {
+    let value = text.trim().trim_matches('"').trim();
+
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return u128::from_str_radix(hex, 16).ok();
+    }
+    if let Ok(value) = value.parse::<u128>() {
+        return Some(value);
+    }
+
+    let value = value.parse::<f64>().ok()?;
+    if value.is_finite() && value >= 0.0 && value.fract() == 0.0 && value <= MAX_EXACT_FLOAT {
+        Some(value as u128)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::test_utils::MockTransport;
+    use alloy_provider::Provider;
+    use axum::{http::StatusCode, response::IntoResponse};
+    use serde_json::json;
+
+    #[test]
+    fn test_revert_reason() {
+        let word = |value: u64| format!("{:064x}", value);
+        let error = |message: &str, length: u64| {
+            let data = format!(
+                "08c379a0{}{}{:0<64}",
+                word(32),
+                word(length),
+                hex::encode(message)
+            );
+            revert_reason(&hex::decode(data).unwrap())
+        };
+        assert_eq!(
+            error("Ownable: caller is not the owner", 32).as_deref(),
+            Some("Ownable: caller is not the owner")
+        );
+        // A length past the data falls back to the selector
+        assert_eq!(error("short", 64).as_deref(), Some("0x08c379a0"));
+
+        let panic = hex::decode(format!("4e487b71{}", word(0x11))).unwrap();
+        assert_eq!(revert_reason(&panic).as_deref(), Some("Panic(0x11)"));
+        assert_eq!(
+            revert_reason(&[0xde, 0xad, 0xbe, 0xef]).as_deref(),
+            Some("0xdeadbeef")
+        );
+        assert_eq!(revert_reason(&[0xde, 0xad]), None);
+        assert_eq!(revert_reason(&[]), None);
+    }
+
+    #[test]
+    fn test_lenient_quantity() {
+        assert_eq!(lenient_quantity("\" 0x1a\""), Some(26));
+        assert_eq!(lenient_quantity("\"0x1a \""), Some(26));
+        assert_eq!(lenient_quantity("\"26.0\""), Some(26));
+        assert_eq!(lenient_quantity("1.5e9"), Some(1_500_000_000));
+        assert_eq!(lenient_quantity("\"26\""), Some(26));
+        assert_eq!(lenient_quantity("26.5"), None);
+        assert_eq!(lenient_quantity("-1"), None);
+        assert_eq!(lenient_quantity("\"0xZZ\""), None);
+        assert_eq!(lenient_quantity("null"), None);
+    }
+
+    #[tokio::test]
+    async fn test_recovers_slightly_off_gas_price() {
+        let transport = MockTransport::new(|_, _| Ok(json!(" 0x4a817c800 ")));
+        let raw = transport.provider().get_gas_price().await;
+        assert!(matches!(raw, Err(RpcError::DeserError { .. })));
+
+        let recovered = recover_quantity(transport.provider().get_gas_price().await);
+        assert_eq!(recovered.unwrap(), 20_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_unrecoverable_result_keeps_error() {
+        let transport = MockTransport::new(|_, _| Ok(json!("not a number")));
+        let result = recover_quantity(transport.provider().get_gas_price().await);
+        assert!(matches!(result, Err(RpcError::DeserError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_method_not_found_detection() {
+        for (message, expected) in [
+            (
+                "the method eth_feeHistory does not exist/is not available",
+                true,
+            ),
+            ("Method not found", true),
+            ("eth_feeHistory is not supported", true),
+            ("header not found", false),
+            ("execution reverted", false),
+        ] {
+            let transport = MockTransport::new(move |_, _| Err(message.into()));
+            let error = transport.provider().get_gas_price().await.unwrap_err();
+            assert_eq!(is_method_not_found(&error), expected, "{}", message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_maps_to_429() {
+        for (message, expected) in [
+            ("Too Many Requests", true),
+            ("daily request rate limit exceeded", true),
+            ("header not found", false),
+        ] {
+            let transport = MockTransport::new(move |_, _| Err(message.into()));
+            let error = transport.provider().get_gas_price().await.unwrap_err();
+            assert_eq!(is_rate_limited(&error), expected, "{}", message);
+        }
+
+        let error = TransportErrorKind::http_error(429, "slow down".into());
+        assert!(is_rate_limited(&error));
+        let response = Error::from(error).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(!is_rate_limited(&TransportErrorKind::http_error(
+            500,
+            String::new()
+        )));
+    }
+}
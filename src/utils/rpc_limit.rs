@@ -0,0 +1,224 @@
+use alloy_json_rpc::{RequestPacket, ResponsePacket, RpcError};
+use alloy_transport::{TransportError, TransportFut};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::Notify;
+use tower::{Layer, Service};
+
+/// AIMD concurrency limit for calls to the node: the limit is halved whenever the
+/// node answers with a rate-limit error and grows back by one per limit's worth of
+/// successful calls
+#[derive(Debug)]
+pub struct AdaptiveLimiter {
+    max: usize,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(max: usize) -> Arc<Self> {
+        let max = max.max(1);
+        Arc::new(Self {
+            max,
+            state: Mutex::new(LimiterState {
+                limit: max as f64,
+                in_flight: 0,
+            }),
+            released: Notify::new(),
+        })
+    }
+
+    /// Number of calls currently allowed in flight
+    pub fn limit(&self) -> usize {
+        self.state.lock().expect("rpc limiter poisoned").limit as usize
+    }
+
+    async fn acquire(&self) {
+        loop {
+            // Registered before checking so a release in between isn't missed
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().expect("rpc limiter poisoned");
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return;
+                }
+            }
+            released.await;
+        }
+    }
+
+    fn release(&self, rate_limited: bool) {
+        let mut state = self.state.lock().expect("rpc limiter poisoned");
+        state.in_flight -= 1;
+        if rate_limited {
+            let limit = (state.limit / 2.0).max(1.0);
+            if limit < state.limit {
+                tracing::warn!(
+                    "Node is rate limiting, RPC concurrency lowered to {}",
+                    limit
+                );
+            }
+            state.limit = limit;
+        } else {
+            state.limit = (state.limit + 1.0 / state.limit).min(self.max as f64);
+        }
+        drop(state);
+        self.released.notify_waiters();
+    }
+}
+
+fn is_rate_limited(result: &Result<ResponsePacket, TransportError>) -> bool {
+    match result {
+        Ok(response) => response.iter_errors().any(|e| e.is_retry_err()),
+        Err(RpcError::Transport(kind)) => kind.is_retry_err(),
+        Err(_) => false,
+    }
+}
+
+/// Transport layer applying an [`AdaptiveLimiter`]
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimitLayer {
+    limiter: Arc<AdaptiveLimiter>,
+}
+
+impl AdaptiveLimitLayer {
+    pub fn new(limiter: Arc<AdaptiveLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for AdaptiveLimitLayer {
+    type Service = AdaptiveLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AdaptiveLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimit<S> {
+    inner: S,
+    limiter: Arc<AdaptiveLimiter>,
+}
+
+impl<S> Service<RequestPacket> for AdaptiveLimit<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let limiter = self.limiter.clone();
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            limiter.acquire().await;
+            let result = inner.call(req).await;
+            limiter.release(is_rate_limited(&result));
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_provider::{Provider, RootProvider};
+    use alloy_rpc_client::RpcClient;
+    use alloy_transport::TransportErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Node serving at most `capacity` concurrent calls, answering 429 above that
+    #[derive(Clone)]
+    struct CappedNode {
+        capacity: usize,
+        in_flight: Arc<AtomicUsize>,
+        rejected: Arc<AtomicUsize>,
+    }
+
+    impl Service<RequestPacket> for CappedNode {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: RequestPacket) -> Self::Future {
+            let node = self.clone();
+            Box::pin(async move {
+                let in_flight = node.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                node.in_flight.fetch_sub(1, Ordering::SeqCst);
+                if in_flight > node.capacity {
+                    node.rejected.fetch_add(1, Ordering::SeqCst);
+                    return Err(TransportErrorKind::http_error(429, String::new()));
+                }
+
+                let RequestPacket::Single(req) = req else {
+                    unreachable!("no batches in this test")
+                };
+                let text = format!(r#"{{"jsonrpc":"2.0","id":{},"result":"0x1"}}"#, req.id());
+                serde_json::from_str(&text).map_err(|e| TransportError::deser_err(e, text))
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_adapts_to_rate_limits() {
+        let node = CappedNode {
+            capacity: 4,
+            in_flight: Arc::default(),
+            rejected: Arc::default(),
+        };
+        let limiter = AdaptiveLimiter::new(32);
+        let provider: RootProvider = RootProvider::new(
+            RpcClient::builder()
+                .layer(AdaptiveLimitLayer::new(limiter.clone()))
+                .transport(node.clone(), true),
+        );
+
+        let wave = || async {
+            let calls = (0..32).map(|_| provider.get_block_number());
+            futures::future::join_all(calls).await
+        };
+
+        wave().await;
+        let rejected_before = node.rejected.swap(0, Ordering::SeqCst);
+        assert!(rejected_before > 0);
+        assert!(limiter.limit() <= 4, "limit is {}", limiter.limit());
+
+        // Once adapted, only the additive probing above capacity gets rejected
+        let results = wave().await;
+        let rejected_after = node.rejected.load(Ordering::SeqCst);
+        assert!(
+            rejected_after < rejected_before / 4,
+            "{} rejected",
+            rejected_after
+        );
+        assert!(results.iter().filter(|r| r.is_ok()).count() > 24);
+    }
+}
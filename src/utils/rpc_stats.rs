@@ -0,0 +1,110 @@
+use crate::models::transaction::RpcCallStat;
+use crate::utils::metrics;
+use alloy_json_rpc::{RequestPacket, ResponsePacket};
+use alloy_transport::{TransportError, TransportFut};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+type Recorder = Arc<Mutex<Vec<RpcCallStat>>>;
+
+tokio::task_local! {
+    static RPC_CALLS: Recorder;
+}
+
+/// Runs `future`, collecting every RPC call it makes through an [`RpcStatsLayer`] transport.
+/// Calls answered from a cache don't reach the transport and are not listed.
+pub async fn record_rpc_calls<F: Future>(future: F) -> (F::Output, Vec<RpcCallStat>) {
+    let recorder = Recorder::default();
+    let output = RPC_CALLS.scope(recorder.clone(), future).await;
+    let calls = std::mem::take(&mut *recorder.lock().expect("rpc stats poisoned"));
+    (output, calls)
+}
+
+/// Transport layer counting RPC calls by method, and timing the ones made inside
+/// [`record_rpc_calls`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RpcStatsLayer;
+
+impl<S> Layer<S> for RpcStatsLayer {
+    type Service = RpcStats<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcStats { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcStats<S> {
+    inner: S,
+}
+
+impl<S> Service<RequestPacket> for RpcStats<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let methods: Vec<String> = match &req {
+            RequestPacket::Single(single) => vec![single.method().to_string()],
+            RequestPacket::Batch(batch) => batch.iter().map(|r| r.method().to_string()).collect(),
+        };
+        for method in &methods {
+            metrics::RPC_CALLS.with_label_values(&[method]).inc();
+        }
+        // Outside of a recording scope calls are only counted
+        let recorder = RPC_CALLS.try_with(Recorder::clone).ok();
+        let call = self.inner.call(req);
+
+        Box::pin(async move {
+            let started = Instant::now();
+            let response = call.await;
+
+            if let Some(recorder) = recorder {
+                let duration_ms = started.elapsed().as_millis() as u64;
+                recorder
+                    .lock()
+                    .expect("rpc stats poisoned")
+                    .extend(methods.into_iter().map(|method| RpcCallStat {
+                        method,
+                        duration_ms,
+                    }));
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quantity, MockTransport};
+    use alloy_provider::Provider;
+
+    #[tokio::test]
+    async fn test_records_calls_only_inside_scope() {
+        let transport = MockTransport::new(|_, _| Ok(quantity(1)));
+        let provider = transport.provider();
+
+        let (_, calls) = record_rpc_calls(async {
+            provider.get_chain_id().await.unwrap();
+            provider.get_gas_price().await.unwrap();
+        })
+        .await;
+        let methods: Vec<_> = calls.iter().map(|c| c.method.as_str()).collect();
+        assert_eq!(methods, vec!["eth_chainId", "eth_gasPrice"]);
+
+        // Not recorded anywhere, and must not panic without a scope
+        provider.get_block_number().await.unwrap();
+    }
+}
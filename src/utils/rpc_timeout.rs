@@ -0,0 +1,119 @@
+use alloy_json_rpc::{RequestPacket, ResponsePacket};
+use alloy_transport::{TransportError, TransportErrorKind, TransportFut};
+use std::any::Any;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+
+/// A call to the node that didn't complete within the RPC timeout
+#[derive(Debug, thiserror::Error)]
+#[error("RPC call timed out after {} ms", .0.as_millis())]
+pub struct RpcTimeout(pub Duration);
+
+/// The [`RpcTimeout`] a transport error is, if any
+pub fn as_rpc_timeout<E: Any>(error: &E) -> Option<&RpcTimeout> {
+    match (error as &dyn Any).downcast_ref::<TransportErrorKind>()? {
+        TransportErrorKind::Custom(inner) => inner.downcast_ref(),
+        _ => None,
+    }
+}
+
+/// Transport layer failing calls to the node that take longer than `timeout`
+#[derive(Debug, Clone, Copy)]
+pub struct RpcTimeoutLayer {
+    timeout: Duration,
+}
+
+impl RpcTimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for RpcTimeoutLayer {
+    type Service = RpcTimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcTimeoutService {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcTimeoutService<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> Service<RequestPacket> for RpcTimeoutService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let timeout = self.timeout;
+        let call = self.inner.call(req);
+
+        Box::pin(async move {
+            match tokio::time::timeout(timeout, call).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!("RPC call timed out after {:?}", timeout);
+                    Err(TransportErrorKind::custom(RpcTimeout(timeout)))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use alloy_provider::{Provider, RootProvider};
+    use alloy_rpc_client::RpcClient;
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    /// Node that never answers
+    #[derive(Clone)]
+    struct Stalled;
+
+    impl Service<RequestPacket> for Stalled {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: RequestPacket) -> Self::Future {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_node_times_out_with_504() {
+        let provider: RootProvider = RootProvider::new(
+            RpcClient::builder()
+                .layer(RpcTimeoutLayer::new(Duration::from_millis(50)))
+                .transport(Stalled, true),
+        );
+
+        let started = std::time::Instant::now();
+        let error = Error::from(provider.get_block_number().await.unwrap_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(error, Error::Timeout(_)), "{:?}", error);
+        assert_eq!(error.into_response().status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
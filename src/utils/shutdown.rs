@@ -1,6 +1,7 @@
 use tokio::signal;
 
-pub async fn shutdown_signal() {
+/// Waits for Ctrl+C or, on unix, SIGTERM. Every caller is notified.
+pub async fn wait_for_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -22,6 +23,9 @@ pub async fn shutdown_signal() {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
+}
 
+pub async fn shutdown_signal() {
+    wait_for_signal().await;
     println!("Shutdown signal received, starting graceful shutdown");
 }
@@ -0,0 +1,182 @@
+//! Pushes the Prometheus metrics to a StatsD (DogStatsD) server, for infrastructure
+//! without a Prometheus scraper
+use prometheus::proto::{MetricFamily, MetricType};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::utils::metrics;
+
+/// Largest datagram sent, below the usual Ethernet MTU minus IP and UDP headers
+const MAX_DATAGRAM_BYTES: usize = 1_432;
+
+/// Sends the metrics to `addr` every `interval`. Runs until the process exits.
+pub async fn push_metrics(addr: SocketAddr, interval: Duration) {
+    let local: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = match UdpSocket::bind(local).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::warn!("StatsD export disabled, can't bind a UDP socket: {}", e);
+            return;
+        }
+    };
+
+    let mut exporter = StatsdExporter::default();
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        for datagram in datagrams(&exporter.lines(&metrics::gather())) {
+            if let Err(e) = socket.send_to(datagram.as_bytes(), addr).await {
+                tracing::debug!("Failed to send metrics to StatsD at {}: {}", addr, e);
+            }
+        }
+    }
+}
+
+/// Turns cumulative Prometheus values into StatsD lines, remembering the last value of
+/// each counter so only the increase since the previous push is sent
+#[derive(Debug, Default)]
+struct StatsdExporter {
+    sent: HashMap<String, f64>,
+}
+
+impl StatsdExporter {
+    /// Counters as `|c` increases, tagged with their labels. Histograms can't be
+    /// replayed as individual timings, so they become `.count` and `.sum` counters.
+    fn lines(&mut self, families: &[MetricFamily]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for family in families {
+            for metric in family.get_metric() {
+                let tags = tags(metric);
+                match family.get_field_type() {
+                    MetricType::COUNTER => {
+                        let value = metric.get_counter().get_value();
+                        lines.extend(self.increase(family.get_name(), &tags, value));
+                    }
+                    MetricType::GAUGE => lines.push(format!(
+                        "{}:{}|g{}",
+                        family.get_name(),
+                        metric.get_gauge().get_value(),
+                        tags
+                    )),
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        let count = histogram.get_sample_count() as f64;
+                        let name = format!("{}.count", family.get_name());
+                        lines.extend(self.increase(&name, &tags, count));
+                        let name = format!("{}.sum", family.get_name());
+                        lines.extend(self.increase(&name, &tags, histogram.get_sample_sum()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        lines
+    }
+
+    /// Counter line for the increase of `name` since the last push, if any
+    fn increase(&mut self, name: &str, tags: &str, value: f64) -> Option<String> {
+        let key = format!("{}{}", name, tags);
+        let previous = self.sent.insert(key, value).unwrap_or(0.0);
+        let increase = value - previous;
+        (increase > 0.0).then(|| format!("{}:{}|c{}", name, increase, tags))
+    }
+}
+
+/// DogStatsD tags of the metric's labels, e.g. `|#method:eth_call`
+fn tags(metric: &prometheus::proto::Metric) -> String {
+    let labels: Vec<String> = metric
+        .get_label()
+        .iter()
+        .map(|label| format!("{}:{}", label.get_name(), label.get_value()))
+        .collect();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("|#{}", labels.join(","))
+    }
+}
+
+/// Lines packed into newline-separated datagrams of at most `MAX_DATAGRAM_BYTES`
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = Vec::new();
+    for line in lines {
+        match datagrams.last_mut() {
+            Some(datagram) if datagram.len() + 1 + line.len() <= MAX_DATAGRAM_BYTES => {
+                datagram.push('\n');
+                datagram.push_str(line);
+            }
+            _ => datagrams.push(line.clone()),
+        }
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::metrics::{GAS_ESTIMATION_DURATION, RPC_CALLS};
+
+    #[tokio::test]
+    async fn test_metrics_pushed_to_udp_listener() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        RPC_CALLS.with_label_values(&["eth_statsdTest"]).inc_by(3);
+        GAS_ESTIMATION_DURATION.observe(0.25);
+
+        let pusher = tokio::spawn(push_metrics(addr, Duration::from_millis(10)));
+        let mut received = String::new();
+        let mut buffer = [0; MAX_DATAGRAM_BYTES];
+        while !received.contains("eth_statsdTest") {
+            let (len, _) =
+                tokio::time::timeout(Duration::from_secs(5), listener.recv_from(&mut buffer))
+                    .await
+                    .expect("metrics pushed")
+                    .unwrap();
+            received.push_str(std::str::from_utf8(&buffer[..len]).unwrap());
+            received.push('\n');
+        }
+        pusher.abort();
+
+        assert!(received.contains("rpc_calls_total:3|c|#method:eth_statsdTest"));
+        assert!(received.contains("gas_estimation_duration_seconds.count:"));
+    }
+
+    #[test]
+    fn test_counters_sent_as_increases() {
+        let counter = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("requests_total", "Requests"),
+            &["path"],
+        )
+        .unwrap();
+        let registry = prometheus::Registry::new();
+        registry.register(Box::new(counter.clone())).unwrap();
+        let mut exporter = StatsdExporter::default();
+
+        counter.with_label_values(&["/a"]).inc_by(5);
+        assert_eq!(
+            exporter.lines(&registry.gather()),
+            ["requests_total:5|c|#path:/a"]
+        );
+        // Nothing new, nothing sent
+        assert!(exporter.lines(&registry.gather()).is_empty());
+        counter.with_label_values(&["/a"]).inc_by(2);
+        assert_eq!(
+            exporter.lines(&registry.gather()),
+            ["requests_total:2|c|#path:/a"]
+        );
+    }
+
+    #[test]
+    fn test_datagrams_stay_under_limit() {
+        let lines = vec!["x".repeat(1_000), "y".repeat(400), "z".repeat(100)];
+        let datagrams = datagrams(&lines);
+        assert_eq!(datagrams.len(), 2);
+        assert!(datagrams.iter().all(|d| d.len() <= MAX_DATAGRAM_BYTES));
+    }
+}
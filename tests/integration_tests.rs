@@ -17,11 +17,12 @@ async fn setup_test_app() -> (axum::Router, AnvilInstance) {
         .spawn();
 
     let config = AppConfig {
-        ethereum_rpc_url: anvil.endpoint(),
+        ethereum_rpc_urls: vec![anvil.endpoint()],
         cache_duration: Duration::from_secs(15),
         host: "127.0.0.1".parse().unwrap(),
         port: 8080,
         log_level: "debug".to_string(),
+        ..Default::default()
     };
 
     let app = create_app(config).await.expect("Failed to create app");
@@ -69,7 +70,7 @@ async fn test_gas_estimation_endpoint() {
     let response = app.oneshot(invalid_request).await.unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    let body_bytes = to_bytes(response.into_body().into(), 1000000_usize)
+    let body_bytes = to_bytes(response.into_body(), 1000000_usize)
         .await
         .unwrap();
     let error_response: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();